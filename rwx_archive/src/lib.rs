@@ -0,0 +1,143 @@
+// Object path files, loose or zipped.
+//
+// Object paths serve each object and texture as its own `.zip`, and the
+// file inside is not always named like the archive: `tree01.zip` may hold
+// `Tree01.RWX`. Names are matched by stem ignoring case, both when looking
+// for a file in a directory and when picking the entry out of an archive,
+// so callers can treat `wood.jpg`, `WOOD.JPG` and `wood.zip` alike.
+//
+// An `ObjectLibrary` is a set of such directories seen as one collection
+// of objects, for converting a whole path at once.
+
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use zip::result::{ZipError, ZipResult};
+use zip::ZipArchive;
+
+/// Extensions of the images textures and masks come as.
+pub const IMAGE_EXTENSIONS: [&str; 4] = ["bmp", "jpg", "jpeg", "png"];
+
+/// Extensions of RWX objects.
+pub const RWX_EXTENSIONS: [&str; 1] = ["rwx"];
+
+/// The file for `name` in the first of `dirs` that has one, ignoring case:
+/// `name` itself, then `<name>.<ext>` for each of `extensions` in order,
+/// then `<name>.zip`.
+pub fn find_file(name: &str, dirs: &[PathBuf], extensions: &[&str]) -> Option<PathBuf> {
+    let candidates: Vec<String> = std::iter::once(name.to_string())
+        .chain(extensions.iter().chain(&["zip"]).map(|ext| format!("{name}.{ext}")))
+        .collect();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else { continue };
+        let files: Vec<PathBuf> = entries.filter_map(|e| Some(e.ok()?.path())).filter(|p| p.is_file()).collect();
+        for candidate in &candidates {
+            if let Some(file) = files.iter().find(|p| file_name(p).eq_ignore_ascii_case(candidate)) {
+                return Some(file.clone());
+            }
+        }
+    }
+    None
+}
+
+/// File name and contents of the file at `path`. For a `.zip`, those of the
+/// entry with one of `extensions` whose stem matches the archive's, or
+/// else of the first entry with one of them.
+pub fn read_file(path: &Path, extensions: &[&str]) -> io::Result<(String, Vec<u8>)> {
+    let data = fs::read(path)?;
+    if !is_zip(path) {
+        return Ok((file_name(path), data));
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    extract(&data, &stem, extensions).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display())))
+}
+
+/// The entry of the archive `data` chosen as [`read_file`] does, as its
+/// file name and contents.
+pub fn extract(data: &[u8], stem: &str, extensions: &[&str]) -> ZipResult<(String, Vec<u8>)> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let entry = pick_entry(&archive, stem, extensions).ok_or(ZipError::FileNotFound)?;
+    let mut file = archive.by_name(&entry)?;
+    let mut out = Vec::new();
+    file.read_to_end(&mut out)?;
+    Ok((file_name(Path::new(&entry)), out))
+}
+
+/// Text of the RWX object at `path`, a `.rwx` file or a `.zip` holding one.
+pub fn read_rwx(path: &Path) -> io::Result<String> {
+    let (_, data) = read_file(path, &RWX_EXTENSIONS)?;
+    // object paths predate UTF-8; names in comments are often Latin-1
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+pub fn is_zip(path: &Path) -> bool {
+    has_extension(path, &["zip"])
+}
+
+/// Directories of RWX objects, loose or zipped, looked up by name.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectLibrary {
+    pub dirs: Vec<PathBuf>,
+}
+
+impl ObjectLibrary {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        ObjectLibrary { dirs }
+    }
+
+    /// Name of every object, sorted. A name found in several directories,
+    /// in any case, is listed once as the first directory has it. Archives
+    /// without an RWX entry, such as zipped textures, are left out.
+    pub fn objects(&self) -> io::Result<Vec<String>> {
+        let mut names: Vec<String> = Vec::new();
+        for dir in &self.dirs {
+            let mut files: Vec<PathBuf> = fs::read_dir(dir)?.filter_map(|e| Some(e.ok()?.path())).collect();
+            files.sort();
+            for path in files {
+                let Some(stem) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) else { continue };
+                let is_object = if is_zip(&path) {
+                    fs::read(&path).ok().and_then(|d| ZipArchive::new(Cursor::new(d)).ok()).is_some_and(|a| {
+                        pick_entry(&a, &stem, &RWX_EXTENSIONS).is_some()
+                    })
+                } else {
+                    has_extension(&path, &RWX_EXTENSIONS)
+                };
+                if is_object && !names.iter().any(|n| n.eq_ignore_ascii_case(&stem)) {
+                    names.push(stem);
+                }
+            }
+        }
+        names.sort_by_key(|n| n.to_ascii_lowercase());
+        Ok(names)
+    }
+
+    /// The file holding object `name`.
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        find_file(name, &self.dirs, &RWX_EXTENSIONS)
+    }
+
+    /// Text of object `name`, see [`read_rwx`].
+    pub fn read(&self, name: &str) -> io::Result<String> {
+        let path = self
+            .find(name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no object named {name}")))?;
+        read_rwx(&path)
+    }
+}
+
+fn pick_entry<R: Read + io::Seek>(archive: &ZipArchive<R>, stem: &str, extensions: &[&str]) -> Option<String> {
+    let entries: Vec<&str> = archive.file_names().filter(|n| has_extension(Path::new(n), extensions)).collect();
+    let matching = entries
+        .iter()
+        .find(|n| Path::new(n).file_stem().is_some_and(|s| s.to_string_lossy().eq_ignore_ascii_case(stem)));
+    matching.or(entries.first()).map(|n| n.to_string())
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
@@ -0,0 +1,74 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use rwx_archive::{find_file, read_file, read_rwx, ObjectLibrary, IMAGE_EXTENSIONS, RWX_EXTENSIONS};
+
+const BOX: &str = "ModelBegin\nClumpBegin\nVertex 0 0 0\nClumpEnd\nModelEnd\n";
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn files_are_found_ignoring_case() {
+    let first = dir("rwx_archive_find_first");
+    let second = dir("rwx_archive_find_second");
+    fs::write(second.join("WOOD.JPG"), b"jpg").unwrap();
+    zip(&first.join("Bark.zip"), &[("bark.jpg", b"bark")]);
+    fs::write(second.join("bark.png"), b"png").unwrap();
+
+    let dirs = [first.clone(), second.clone()];
+    assert_eq!(find_file("wood", &dirs, &IMAGE_EXTENSIONS), Some(second.join("WOOD.JPG")));
+    assert_eq!(find_file("wood.jpg", &dirs, &IMAGE_EXTENSIONS), Some(second.join("WOOD.JPG")));
+    // the first directory with a match wins, zipped or not
+    assert_eq!(find_file("bark", &dirs, &IMAGE_EXTENSIONS), Some(first.join("Bark.zip")));
+    assert_eq!(find_file("leaves", &dirs, &IMAGE_EXTENSIONS), None);
+}
+
+#[test]
+fn zip_entries_are_matched_by_stem() {
+    let dir = dir("rwx_archive_read");
+    let path = dir.join("tree01.zip");
+    zip(&path, &[("readme.txt", b"notes"), ("other.rwx", b"other"), ("Tree01.RWX", BOX.as_bytes())]);
+
+    let (name, data) = read_file(&path, &RWX_EXTENSIONS).unwrap();
+    assert_eq!(name, "Tree01.RWX");
+    assert_eq!(data, BOX.as_bytes());
+    assert_eq!(read_rwx(&path).unwrap(), BOX);
+
+    // without a matching stem, the first entry of the right kind
+    let renamed = dir.join("tree02.zip");
+    fs::copy(&path, &renamed).unwrap();
+    assert_eq!(read_file(&renamed, &RWX_EXTENSIONS).unwrap().0, "other.rwx");
+
+    let err = read_file(&path, &IMAGE_EXTENSIONS).unwrap_err();
+    assert!(err.to_string().contains("tree01.zip"));
+}
+
+#[test]
+fn library_lists_loose_and_zipped_objects() {
+    let dir = dir("rwx_archive_library");
+    fs::write(dir.join("chair.rwx"), BOX).unwrap();
+    zip(&dir.join("Table.zip"), &[("TABLE.rwx", BOX.as_bytes())]);
+    zip(&dir.join("wood.zip"), &[("wood.jpg", b"jpg")]);
+    fs::write(dir.join("notes.txt"), b"notes").unwrap();
+
+    let library = ObjectLibrary::new(vec![dir]);
+    assert_eq!(library.objects().unwrap(), ["chair", "Table"]);
+    assert_eq!(library.read("table").unwrap(), BOX);
+    assert_eq!(library.read("CHAIR").unwrap(), BOX);
+    assert!(library.read("lamp").is_err());
+}
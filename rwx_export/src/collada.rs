@@ -0,0 +1,294 @@
+// COLLADA 1.4.1 (.dae) export.
+//
+// The clump tree becomes a `<node>` tree, each node carrying its transform
+// as a `<matrix>` and, when it has geometry, an `<instance_geometry>` with
+// one `<triangles>` block per material. Geometry goes through the
+// normalizer with the COLLADA settings: metres, V from the bottom, and
+// back faces duplicated for double-sided materials, since COLLADA has no
+// standard way to turn culling off.
+//
+// Materials are `<phong>` effects built straight from RWX `Surface`:
+// ambient and diffuse are the colour scaled by their coefficients (a
+// texture replaces the colour, as in RWX), specular is grey, and opacity
+// is `<transparency>`. Textures are `<image>`s referencing
+// `<name>.bmp|jpg|jpeg|png` found in `texture_dirs`, or `<name>.png`;
+// zipped ones are taken out of the archive next to the output.
+// A masked texture is merged with its mask into `<texture>_<mask>.png`,
+// written next to the output, and used as both the diffuse texture and the
+// `<transparent>` alpha.
+//
+// Element ids are generated (`node-3`, `mat-1`, ...) so they are always
+// valid XML ids; the RWX names go in `name` attributes.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rwx_archive::IMAGE_EXTENSIONS;
+use rwx_normalizer::{NormalizeOptions, NormalizedMesh, TargetFormat};
+use rwx_scene::{RwxNode, RwxScene, RwxTransform};
+
+use crate::{by_material, masked_texture, node_mesh, rotation_matrix, texture_file};
+
+/// RWX has no specular exponent; this gives a broad highlight.
+const SHININESS: f32 = 20.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct ColladaOptions {
+    /// Directories searched for texture files
+    pub texture_dirs: Vec<PathBuf>,
+}
+
+/// Writes `scene` to `path` as COLLADA 1.4.1.
+pub fn export_collada(scene: &RwxScene, path: &Path, opts: &ColladaOptions) -> io::Result<()> {
+    let normalize = NormalizeOptions::for_format(TargetFormat::Collada);
+    let mut doc = Document {
+        opts,
+        normalize,
+        images: Vec::new(),
+        files: Vec::new(),
+        geometries: String::new(),
+        geometry_count: 0,
+    };
+
+    let mut effects = String::new();
+    let mut materials = String::new();
+    for (i, mat) in scene.materials.iter().enumerate() {
+        let _ = writeln!(materials, "    <material id=\"mat-{i}\" name=\"{}\">", xml(&mat.name));
+        let _ = writeln!(materials, "      <instance_effect url=\"#effect-{i}\"/>\n    </material>");
+        doc.effect(&mut effects, i, mat)?;
+    }
+
+    let mut nodes = String::new();
+    let mut count = 0;
+    doc.node(&mut nodes, &scene.root, scene, &mut count, 3);
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<COLLADA xmlns=\"http://www.collada.org/2005/11/COLLADASchema\" version=\"1.4.1\">\n");
+    out.push_str("  <asset>\n");
+    out.push_str("    <contributor>\n      <authoring_tool>rwx_export</authoring_tool>\n    </contributor>\n");
+    out.push_str("    <created>1970-01-01T00:00:00Z</created>\n    <modified>1970-01-01T00:00:00Z</modified>\n");
+    out.push_str("    <unit name=\"meter\" meter=\"1\"/>\n    <up_axis>Y_UP</up_axis>\n");
+    out.push_str("  </asset>\n");
+    if !doc.images.is_empty() {
+        out.push_str("  <library_images>\n");
+        for (i, (name, file)) in doc.images.iter().enumerate() {
+            let _ = writeln!(out, "    <image id=\"image-{i}\" name=\"{}\">", xml(name));
+            let _ = writeln!(out, "      <init_from>{}</init_from>\n    </image>", xml(file));
+        }
+        out.push_str("  </library_images>\n");
+    }
+    if !effects.is_empty() {
+        let _ = write!(out, "  <library_effects>\n{effects}  </library_effects>\n");
+        let _ = write!(out, "  <library_materials>\n{materials}  </library_materials>\n");
+    }
+    if !doc.geometries.is_empty() {
+        let _ = write!(out, "  <library_geometries>\n{}  </library_geometries>\n", doc.geometries);
+    }
+    out.push_str("  <library_visual_scenes>\n");
+    let _ = writeln!(out, "    <visual_scene id=\"scene\" name=\"{}\">", xml(&scene.root.name));
+    out.push_str(&nodes);
+    out.push_str("    </visual_scene>\n  </library_visual_scenes>\n");
+    out.push_str("  <scene>\n    <instance_visual_scene url=\"#scene\"/>\n  </scene>\n");
+    out.push_str("</COLLADA>\n");
+
+    for (name, data) in &doc.files {
+        fs::write(path.with_file_name(name), data)?;
+    }
+    fs::write(path, out)
+}
+
+struct Document<'a> {
+    opts: &'a ColladaOptions,
+    normalize: NormalizeOptions,
+    /// (texture name, file name)
+    images: Vec<(String, String)>,
+    /// Merged masked textures and textures taken out of archives, to write
+    /// next to the output
+    files: Vec<(String, Vec<u8>)>,
+    geometries: String,
+    geometry_count: usize,
+}
+
+impl Document<'_> {
+    fn image(&mut self, name: &str) -> io::Result<usize> {
+        if let Some(i) = self.images.iter().position(|(n, _)| n == name) {
+            return Ok(i);
+        }
+        let (file, data) = texture_file(name, &self.opts.texture_dirs, &IMAGE_EXTENSIONS)?;
+        if let Some(data) = data {
+            self.files.push((file.clone(), data));
+        }
+        self.images.push((name.to_string(), file));
+        Ok(self.images.len() - 1)
+    }
+
+    /// Image of `texture` with `mask` merged into its alpha.
+    fn masked_image(&mut self, texture: &str, mask: &str) -> io::Result<usize> {
+        let (file, data) = masked_texture(texture, mask, &self.opts.texture_dirs)?;
+        if let Some(i) = self.images.iter().position(|(_, f)| *f == file) {
+            return Ok(i);
+        }
+        if let Some(data) = data {
+            self.files.push((file.clone(), data));
+        }
+        self.images.push((format!("{texture}_{mask}"), file));
+        Ok(self.images.len() - 1)
+    }
+
+    fn effect(&mut self, out: &mut String, i: usize, mat: &rwx_parser::Material) -> io::Result<()> {
+        let color = if mat.texture.is_some() { [1.0; 3] } else { mat.color };
+        let ambient = color.map(|c| c * mat.ambient);
+        let diffuse = color.map(|c| c * mat.diffuse);
+
+        let _ = writeln!(out, "    <effect id=\"effect-{i}\" name=\"{}\">\n      <profile_COMMON>", xml(&mat.name));
+        let texture = match (&mat.texture, &mat.mask) {
+            (Some(t), Some(m)) => Some(self.masked_image(t, m)?),
+            (Some(t), None) => Some(self.image(t)?),
+            _ => None,
+        };
+        if let Some(image) = texture {
+            let _ = writeln!(out, "        <newparam sid=\"surface-{image}\">");
+            let _ = writeln!(out, "          <surface type=\"2D\">\n            <init_from>image-{image}</init_from>\n          </surface>");
+            let _ = writeln!(out, "        </newparam>\n        <newparam sid=\"sampler-{image}\">");
+            let _ = writeln!(out, "          <sampler2D>\n            <source>surface-{image}</source>\n          </sampler2D>");
+            out.push_str("        </newparam>\n");
+        }
+
+        out.push_str("        <technique sid=\"common\">\n          <phong>\n");
+        let _ = writeln!(out, "            <ambient>\n              <color>{}</color>\n            </ambient>", rgba(ambient));
+        match texture {
+            Some(image) => {
+                let _ = writeln!(
+                    out,
+                    "            <diffuse>\n              <texture texture=\"sampler-{image}\" texcoord=\"UVMap\"/>\n            </diffuse>"
+                );
+            }
+            None => {
+                let _ = writeln!(out, "            <diffuse>\n              <color>{}</color>\n            </diffuse>", rgba(diffuse));
+            }
+        }
+        let _ = writeln!(out, "            <specular>\n              <color>{}</color>\n            </specular>", rgba([mat.specular; 3]));
+        let _ = writeln!(out, "            <shininess>\n              <float>{SHININESS}</float>\n            </shininess>");
+        if let (Some(image), Some(_)) = (texture, &mat.mask) {
+            let _ = writeln!(
+                out,
+                "            <transparent opaque=\"A_ONE\">\n              <texture texture=\"sampler-{image}\" texcoord=\"UVMap\"/>\n            </transparent>"
+            );
+        }
+        let _ = writeln!(out, "            <transparency>\n              <float>{}</float>\n            </transparency>", mat.opacity);
+        out.push_str("          </phong>\n        </technique>\n      </profile_COMMON>\n");
+        out.push_str("    </effect>\n");
+        Ok(())
+    }
+
+    fn node(&mut self, out: &mut String, node: &RwxNode, scene: &RwxScene, count: &mut usize, depth: usize) {
+        let pad = "  ".repeat(depth);
+        let id = *count;
+        *count += 1;
+
+        let _ = writeln!(out, "{pad}<node id=\"node-{id}\" name=\"{}\" type=\"NODE\">", xml(&node.name));
+        let m = matrix(&node.transform, self.normalize.coordinates.scale);
+        let values: Vec<String> = m.iter().map(|v| v.to_string()).collect();
+        let _ = writeln!(out, "{pad}  <matrix sid=\"transform\">{}</matrix>", values.join(" "));
+
+        if let Some(mesh) = node_mesh(node, &scene.materials, &self.normalize) {
+            let geometry = self.geometry(&node.name, &mesh, scene);
+            let _ = writeln!(out, "{pad}  <instance_geometry url=\"#geometry-{geometry}\">");
+            let _ = writeln!(out, "{pad}    <bind_material>\n{pad}      <technique_common>");
+            for (mat, _) in by_material(&mesh.faces) {
+                if let Some(m) = scene.materials.iter().position(|x| x.name == mat) {
+                    let _ = writeln!(out, "{pad}        <instance_material symbol=\"mat-{m}\" target=\"#mat-{m}\">");
+                    let _ = writeln!(
+                        out,
+                        "{pad}          <bind_vertex_input semantic=\"UVMap\" input_semantic=\"TEXCOORD\" input_set=\"0\"/>"
+                    );
+                    let _ = writeln!(out, "{pad}        </instance_material>");
+                }
+            }
+            let _ = writeln!(out, "{pad}      </technique_common>\n{pad}    </bind_material>");
+            let _ = writeln!(out, "{pad}  </instance_geometry>");
+        }
+
+        for child in &node.children {
+            self.node(out, child, scene, count, depth + 1);
+        }
+        let _ = writeln!(out, "{pad}</node>");
+    }
+
+    fn geometry(&mut self, name: &str, mesh: &NormalizedMesh, scene: &RwxScene) -> usize {
+        let g = self.geometry_count;
+        self.geometry_count += 1;
+        let out = &mut self.geometries;
+        let verts = &mesh.vertices;
+
+        let _ = writeln!(out, "    <geometry id=\"geometry-{g}\" name=\"{}\">\n      <mesh>", xml(name));
+
+        let positions: Vec<f32> = verts.iter().flat_map(|v| [v.x, v.y, v.z]).collect();
+        let normals: Vec<f32> = verts.iter().flat_map(|v| v.normal.unwrap_or([0.0, 1.0, 0.0])).collect();
+        let uvs: Vec<f32> = verts.iter().flat_map(|v| [v.u, v.v]).collect();
+        source(out, &format!("geometry-{g}-positions"), &positions, &["X", "Y", "Z"]);
+        source(out, &format!("geometry-{g}-normals"), &normals, &["X", "Y", "Z"]);
+        source(out, &format!("geometry-{g}-uvs"), &uvs, &["S", "T"]);
+
+        let _ = writeln!(out, "        <vertices id=\"geometry-{g}-vertices\">");
+        let _ = writeln!(out, "          <input semantic=\"POSITION\" source=\"#geometry-{g}-positions\"/>\n        </vertices>");
+
+        for (mat, indices) in by_material(&mesh.faces) {
+            let material = match scene.materials.iter().position(|m| m.name == mat) {
+                Some(m) => format!(" material=\"mat-{m}\""),
+                None => String::new(),
+            };
+            let _ = writeln!(out, "        <triangles{material} count=\"{}\">", indices.len() / 3);
+            let _ = writeln!(out, "          <input semantic=\"VERTEX\" source=\"#geometry-{g}-vertices\" offset=\"0\"/>");
+            let _ = writeln!(out, "          <input semantic=\"NORMAL\" source=\"#geometry-{g}-normals\" offset=\"0\"/>");
+            let _ = writeln!(out, "          <input semantic=\"TEXCOORD\" source=\"#geometry-{g}-uvs\" offset=\"0\" set=\"0\"/>");
+            let p: Vec<String> = indices.iter().map(|i| i.to_string()).collect();
+            let _ = writeln!(out, "          <p>{}</p>\n        </triangles>", p.join(" "));
+        }
+
+        out.push_str("      </mesh>\n    </geometry>\n");
+        g
+    }
+}
+
+fn source(out: &mut String, id: &str, values: &[f32], params: &[&str]) {
+    let stride = params.len();
+    let text: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    let _ = writeln!(out, "        <source id=\"{id}\">");
+    let _ = writeln!(out, "          <float_array id=\"{id}-array\" count=\"{}\">{}</float_array>", values.len(), text.join(" "));
+    let _ = writeln!(out, "          <technique_common>");
+    let _ = writeln!(
+        out,
+        "            <accessor source=\"#{id}-array\" count=\"{}\" stride=\"{stride}\">",
+        values.len() / stride
+    );
+    for p in params {
+        let _ = writeln!(out, "              <param name=\"{p}\" type=\"float\"/>");
+    }
+    out.push_str("            </accessor>\n          </technique_common>\n        </source>\n");
+}
+
+/// Row-major `translate * rotate * scale`, with translation in output units.
+fn matrix(tf: &RwxTransform, unit_scale: f32) -> [f32; 16] {
+    let s = tf.scale.unwrap_or([1.0; 3]);
+    let t = tf.translate.unwrap_or([0.0; 3]).map(|c| c * unit_scale);
+    let r = tf.rotate.map_or([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], rotation_matrix);
+
+    [
+        r[0][0] * s[0], r[0][1] * s[1], r[0][2] * s[2], t[0],
+        r[1][0] * s[0], r[1][1] * s[1], r[1][2] * s[2], t[1],
+        r[2][0] * s[0], r[2][1] * s[1], r[2][2] * s[2], t[2],
+        0.0, 0.0, 0.0, 1.0,
+    ]
+}
+
+fn rgba(c: [f32; 3]) -> String {
+    format!("{} {} {} 1", c[0], c[1], c[2])
+}
+
+fn xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,430 @@
+// glTF 2.0 export.
+//
+// Each scene node becomes a glTF node with its transform; nodes with
+// geometry get a mesh with one primitive per material. Geometry goes
+// through the normalizer with the glTF settings (metres, double-sided
+// materials marked rather than duplicated), so normals, tangents and
+// prelight colours come out ready to use.
+//
+// `.gltf` output writes the buffer to a `.bin` next to it; `.glb` output
+// packs JSON, buffer and embedded textures into one file. Textures are
+// looked up in any image format in `texture_dirs`, loose or zipped, and
+// either embedded or copied next to the output; those that are not PNG or
+// JPEG (BMP) are converted to `<name>.png` first. A texture that cannot be
+// found is still referenced by name so it can be added later.
+//
+// A masked texture is merged with its mask into `<texture>_<mask>.png`,
+// whose alpha the `MASK` alpha mode cuts by, and a bump map is converted
+// into the normal map `<bump>_normal.png`. LOD levels use `MSFT_lod`,
+// with the screen coverage at which each level hands over in the node's
+// `extras`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use rwx_normalizer::{generate_lods, NormalizeOptions, NormalizedMesh, TargetFormat};
+use rwx_scene::{AlphaMode, PbrMaterial, RwxNode, RwxScene};
+use serde_json::{json, Map, Value};
+
+use crate::{by_material, file_name, masked_texture, node_mesh, normal_texture, quaternion, read_web_texture};
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+#[derive(Debug, Clone, Default)]
+pub struct GltfOptions {
+    /// Write a single `.glb` instead of `.gltf` + `.bin`
+    pub binary: bool,
+    /// Embed images in the buffer instead of copying them next to the output
+    pub embed_textures: bool,
+    /// Directories searched for texture files
+    pub texture_dirs: Vec<PathBuf>,
+    /// Ratios for extra LOD levels, e.g. `[0.5, 0.25]`; empty for none
+    pub lod_levels: Vec<f32>,
+}
+
+/// Writes `scene` to `path` as glTF 2.0.
+pub fn export_gltf(scene: &RwxScene, path: &Path, opts: &GltfOptions) -> io::Result<()> {
+    let mut writer = Writer::new(opts);
+    let normalize = NormalizeOptions::for_format(TargetFormat::Gltf);
+
+    let pbr = scene.pbr_materials();
+    for mat in &pbr {
+        let m = writer.material(mat)?;
+        writer.materials.push(m);
+    }
+
+    let root = writer.node(&scene.root, scene, &normalize)?;
+
+    let mut doc = Map::new();
+    doc.insert("asset".into(), json!({ "version": "2.0", "generator": "rwx_export" }));
+    doc.insert("scene".into(), json!(0));
+    doc.insert("scenes".into(), json!([{ "name": scene.root.name, "nodes": [root] }]));
+    doc.insert("nodes".into(), Value::Array(writer.nodes));
+    for (key, list) in [
+        ("meshes", writer.meshes),
+        ("materials", writer.materials),
+        ("textures", writer.textures),
+        ("images", writer.images),
+        ("samplers", writer.samplers),
+        ("accessors", writer.accessors),
+        ("bufferViews", writer.views),
+    ] {
+        if !list.is_empty() {
+            doc.insert(key.into(), Value::Array(list));
+        }
+    }
+    if writer.uses_lod {
+        doc.insert("extensionsUsed".into(), json!(["MSFT_lod"]));
+    }
+
+    let mut bin = writer.buffer;
+    pad(&mut bin, 0);
+
+    if opts.binary {
+        if !bin.is_empty() {
+            doc.insert("buffers".into(), json!([{ "byteLength": bin.len() }]));
+        }
+        let mut text = serde_json::to_vec(&Value::Object(doc))?;
+        pad(&mut text, b' ');
+
+        let mut out = Vec::with_capacity(12 + 8 + text.len() + 8 + bin.len());
+        let total = 12 + 8 + text.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+        out.extend_from_slice(b"glTF");
+        out.extend_from_slice(&2u32.to_le_bytes());
+        out.extend_from_slice(&(total as u32).to_le_bytes());
+        out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        out.extend_from_slice(b"JSON");
+        out.extend_from_slice(&text);
+        if !bin.is_empty() {
+            out.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            out.extend_from_slice(b"BIN\0");
+            out.extend_from_slice(&bin);
+        }
+        fs::write(path, out)
+    } else {
+        if !bin.is_empty() {
+            let bin_path = path.with_extension("bin");
+            let uri = file_name(&bin_path);
+            fs::write(&bin_path, &bin)?;
+            doc.insert("buffers".into(), json!([{ "byteLength": bin.len(), "uri": uri }]));
+        }
+        for (name, data) in writer.copies {
+            fs::write(sibling(path, &name), data)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&Value::Object(doc))?)
+    }
+}
+
+struct Writer<'a> {
+    opts: &'a GltfOptions,
+    buffer: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+    materials: Vec<Value>,
+    textures: Vec<Value>,
+    images: Vec<Value>,
+    samplers: Vec<Value>,
+    /// Texture name -> texture index
+    texture_index: Vec<(String, usize)>,
+    /// Files to write next to a `.gltf`
+    copies: Vec<(String, Vec<u8>)>,
+    uses_lod: bool,
+}
+
+impl<'a> Writer<'a> {
+    fn new(opts: &'a GltfOptions) -> Self {
+        Writer {
+            opts,
+            buffer: Vec::new(),
+            views: Vec::new(),
+            accessors: Vec::new(),
+            meshes: Vec::new(),
+            nodes: Vec::new(),
+            materials: Vec::new(),
+            textures: Vec::new(),
+            images: Vec::new(),
+            samplers: Vec::new(),
+            texture_index: Vec::new(),
+            copies: Vec::new(),
+            uses_lod: false,
+        }
+    }
+
+    /// Adds `node` and its children, returning the node index.
+    fn node(&mut self, node: &RwxNode, scene: &RwxScene, normalize: &NormalizeOptions) -> io::Result<usize> {
+        let index = self.nodes.len();
+        self.nodes.push(Value::Null);
+
+        let mut out = Map::new();
+        out.insert("name".into(), json!(node.name));
+
+        // RWX rotations are degrees around an axis; translations are in RWX units
+        let tf = &node.transform;
+        if let Some(t) = tf.translate {
+            let s = normalize.coordinates.scale;
+            out.insert("translation".into(), json!([t[0] * s, t[1] * s, t[2] * s]));
+        }
+        if let Some(r) = tf.rotate {
+            out.insert("rotation".into(), json!(quaternion(r)));
+        }
+        if let Some(s) = tf.scale {
+            out.insert("scale".into(), json!(s));
+        }
+
+        if let Some(mesh) = node_mesh(node, &scene.materials, normalize) {
+            let lods = generate_lods(&mesh, &self.opts.lod_levels);
+            out.insert("mesh".into(), json!(self.mesh(&node.name, &mesh, scene)));
+
+            if lods.len() > 1 {
+                let mut ids = Vec::new();
+                for (i, level) in lods.iter().enumerate().skip(1) {
+                    let mesh_index = self.mesh(&format!("{}_LOD{}", node.name, i), &level.mesh, scene);
+                    ids.push(self.nodes.len());
+                    self.nodes.push(json!({ "name": format!("{}_LOD{}", node.name, i), "mesh": mesh_index }));
+                }
+                // each level hands over at half its triangle ratio; the last culls at 1%
+                let coverage: Vec<f32> = lods
+                    .iter()
+                    .enumerate()
+                    .map(|(i, l)| if i + 1 == lods.len() { 0.01 } else { l.ratio * 0.5 })
+                    .collect();
+                out.insert("extensions".into(), json!({ "MSFT_lod": { "ids": ids } }));
+                out.insert("extras".into(), json!({ "MSFT_screencoverage": coverage }));
+                self.uses_lod = true;
+            }
+        }
+
+        let mut children = Vec::new();
+        for child in &node.children {
+            children.push(self.node(child, scene, normalize)?);
+        }
+        if !children.is_empty() {
+            out.insert("children".into(), json!(children));
+        }
+
+        self.nodes[index] = Value::Object(out);
+        Ok(index)
+    }
+
+    /// Adds a mesh with one primitive per material, returning its index.
+    fn mesh(&mut self, name: &str, mesh: &NormalizedMesh, scene: &RwxScene) -> usize {
+        let verts = &mesh.vertices;
+
+        let mut attributes = Map::new();
+        let positions: Vec<[f32; 3]> = verts.iter().map(|v| [v.x, v.y, v.z]).collect();
+        let (min, max) = bounds(&positions);
+        let view = self.view(&floats(&positions), Some(ARRAY_BUFFER));
+        attributes.insert(
+            "POSITION".into(),
+            json!(self.accessor(view, FLOAT, verts.len(), "VEC3", Some((min.to_vec(), max.to_vec())))),
+        );
+
+        if verts.iter().all(|v| v.normal.is_some()) {
+            let normals: Vec<[f32; 3]> = verts.iter().map(|v| v.normal.unwrap()).collect();
+            let view = self.view(&floats(&normals), Some(ARRAY_BUFFER));
+            attributes.insert("NORMAL".into(), json!(self.accessor(view, FLOAT, verts.len(), "VEC3", None)));
+
+            if verts.iter().all(|v| v.tangent.is_some()) {
+                let tangents: Vec<[f32; 4]> = verts.iter().map(|v| v.tangent.unwrap()).collect();
+                let view = self.view(&floats(&tangents), Some(ARRAY_BUFFER));
+                attributes.insert("TANGENT".into(), json!(self.accessor(view, FLOAT, verts.len(), "VEC4", None)));
+            }
+        }
+
+        let uvs: Vec<[f32; 2]> = verts.iter().map(|v| [v.u, v.v]).collect();
+        let view = self.view(&floats(&uvs), Some(ARRAY_BUFFER));
+        attributes.insert("TEXCOORD_0".into(), json!(self.accessor(view, FLOAT, verts.len(), "VEC2", None)));
+
+        if verts.iter().any(|v| v.color.is_some()) {
+            let colors: Vec<[f32; 3]> = verts.iter().map(|v| v.color.unwrap_or([1.0, 1.0, 1.0])).collect();
+            let view = self.view(&floats(&colors), Some(ARRAY_BUFFER));
+            attributes.insert("COLOR_0".into(), json!(self.accessor(view, FLOAT, verts.len(), "VEC3", None)));
+        }
+
+        let mut primitives = Vec::new();
+        for (mat, indices) in by_material(&mesh.faces) {
+            let bytes: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+            let view = self.view(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+            let accessor = self.accessor(view, UNSIGNED_INT, indices.len(), "SCALAR", None);
+
+            let mut prim = Map::new();
+            prim.insert("attributes".into(), Value::Object(attributes.clone()));
+            prim.insert("indices".into(), json!(accessor));
+            if let Some(m) = scene.materials.iter().position(|m| m.name == mat) {
+                prim.insert("material".into(), json!(m));
+            }
+            primitives.push(Value::Object(prim));
+        }
+
+        self.meshes.push(json!({ "name": name, "primitives": primitives }));
+        self.meshes.len() - 1
+    }
+
+    fn material(&mut self, mat: &PbrMaterial) -> io::Result<Value> {
+        let mut pbr = Map::new();
+        pbr.insert("baseColorFactor".into(), json!(mat.base_color));
+        pbr.insert("metallicFactor".into(), json!(mat.metallic));
+        pbr.insert("roughnessFactor".into(), json!(mat.roughness));
+        if let Some(tex) = &mat.base_color_texture {
+            let index = match &mat.alpha_texture {
+                Some(mask) => self.masked_texture(tex, mask)?,
+                None => self.texture(tex)?,
+            };
+            pbr.insert("baseColorTexture".into(), json!({ "index": index }));
+        }
+
+        let mut out = Map::new();
+        out.insert("name".into(), json!(mat.name));
+        out.insert("pbrMetallicRoughness".into(), Value::Object(pbr));
+        if let Some(tex) = &mat.normal_texture {
+            let index = self.generated_texture(normal_texture(tex, &self.opts.texture_dirs)?);
+            out.insert("normalTexture".into(), json!({ "index": index }));
+        }
+        match mat.alpha_mode {
+            AlphaMode::Opaque => {}
+            AlphaMode::Mask { cutoff } => {
+                out.insert("alphaMode".into(), json!("MASK"));
+                out.insert("alphaCutoff".into(), json!(cutoff));
+            }
+            AlphaMode::Blend => {
+                out.insert("alphaMode".into(), json!("BLEND"));
+            }
+        }
+        if mat.double_sided {
+            out.insert("doubleSided".into(), json!(true));
+        }
+        Ok(Value::Object(out))
+    }
+
+    /// Returns the texture index for `name`, adding the image on first use.
+    fn texture(&mut self, name: &str) -> io::Result<usize> {
+        if let Some((_, i)) = self.texture_index.iter().find(|(n, _)| n == name) {
+            return Ok(*i);
+        }
+        let image = read_web_texture(name, &self.opts.texture_dirs)?.map(|(file, data)| {
+            let mime = mime_type(Path::new(&file));
+            (file, data, mime)
+        });
+        Ok(self.add_texture(name, image, format!("{name}.png")))
+    }
+
+    /// Returns the texture index for `texture` with `mask` merged into its
+    /// alpha, adding the merged image on first use.
+    fn masked_texture(&mut self, texture: &str, mask: &str) -> io::Result<usize> {
+        Ok(self.generated_texture(masked_texture(texture, mask, &self.opts.texture_dirs)?))
+    }
+
+    /// Returns the texture index for a PNG the exporter made, given as its
+    /// file name and contents, adding it on first use.
+    fn generated_texture(&mut self, (file, data): (String, Option<Vec<u8>>)) -> usize {
+        if let Some((_, i)) = self.texture_index.iter().find(|(n, _)| *n == file) {
+            return *i;
+        }
+        let image = data.map(|d| (file.clone(), d, "image/png"));
+        self.add_texture(&file, image, file.clone())
+    }
+
+    /// Adds a texture for `image` (file name, data and MIME type), embedded
+    /// or copied as the options ask, or referencing `missing_uri` if there
+    /// is no image.
+    fn add_texture(&mut self, name: &str, image: Option<(String, Vec<u8>, &str)>, missing_uri: String) -> usize {
+        if self.samplers.is_empty() {
+            // LINEAR_MIPMAP_LINEAR / LINEAR / REPEAT, as RWX renderers filter
+            self.samplers.push(json!({ "magFilter": 9729, "minFilter": 9987, "wrapS": 10497, "wrapT": 10497 }));
+        }
+
+        let image = match image {
+            Some((file_name, data, mime)) => {
+                if self.opts.binary && self.opts.embed_textures {
+                    let view = self.view(&data, None);
+                    json!({ "name": name, "bufferView": view, "mimeType": mime })
+                } else if self.opts.embed_textures {
+                    let encoded = base64::engine::general_purpose::STANDARD.encode(&data);
+                    json!({ "name": name, "uri": format!("data:{mime};base64,{encoded}") })
+                } else {
+                    self.copies.push((file_name.clone(), data));
+                    json!({ "name": name, "uri": file_name })
+                }
+            }
+            None => json!({ "name": name, "uri": missing_uri }),
+        };
+
+        self.images.push(image);
+        self.textures.push(json!({ "sampler": 0, "source": self.images.len() - 1 }));
+        let index = self.textures.len() - 1;
+        self.texture_index.push((name.to_string(), index));
+        index
+    }
+
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        pad(&mut self.buffer, 0);
+        let mut view = Map::new();
+        view.insert("buffer".into(), json!(0));
+        view.insert("byteOffset".into(), json!(self.buffer.len()));
+        view.insert("byteLength".into(), json!(bytes.len()));
+        if let Some(t) = target {
+            view.insert("target".into(), json!(t));
+        }
+        self.buffer.extend_from_slice(bytes);
+        self.views.push(Value::Object(view));
+        self.views.len() - 1
+    }
+
+    fn accessor(
+        &mut self,
+        view: usize,
+        component_type: u32,
+        count: usize,
+        kind: &str,
+        bounds: Option<(Vec<f32>, Vec<f32>)>,
+    ) -> usize {
+        let mut acc = json!({ "bufferView": view, "componentType": component_type, "count": count, "type": kind });
+        if let Some((min, max)) = bounds {
+            acc["min"] = json!(min);
+            acc["max"] = json!(max);
+        }
+        self.accessors.push(acc);
+        self.accessors.len() - 1
+    }
+}
+
+fn bounds(points: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for p in points {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+    }
+    (min, max)
+}
+
+fn floats<const N: usize>(items: &[[f32; N]]) -> Vec<u8> {
+    items.iter().flatten().flat_map(|f| f.to_le_bytes()).collect()
+}
+
+fn pad(bytes: &mut Vec<u8>, fill: u8) {
+    while !bytes.len().is_multiple_of(4) {
+        bytes.push(fill);
+    }
+}
+
+/// MIME type of a texture from `read_web_texture`, always a PNG or JPEG.
+fn mime_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        _ => "image/png",
+    }
+}
+
+fn sibling(path: &Path, name: &str) -> PathBuf {
+    path.parent().map_or_else(|| PathBuf::from(name), |p| p.join(name))
+}
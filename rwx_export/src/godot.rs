@@ -0,0 +1,422 @@
+// Godot 4 text scene (.tscn) export, with materials as .tres resources.
+//
+// The clump tree becomes a `Node3D` tree under a root named after the
+// model; clumps with geometry are `MeshInstance3D`s holding an `ArrayMesh`
+// with one surface per material. Clumps that take part in collision get a
+// `StaticBody3D` child with a `ConcavePolygonShape3D` built from the same
+// triangles, and a clump `Tag` is kept as `metadata/rwx_tag`. Geometry goes
+// through the normalizer with the Godot settings: metres, V from the top,
+// and double-sided materials marked rather than duplicated.
+//
+// Surfaces use the current mesh storage format (Godot 4.2 and newer):
+// positions in `vertex_data` followed by octahedral-encoded normals and
+// tangents, colours and UVs in `attribute_data`. Godot treats clockwise
+// triangles as front facing, so every triangle is written reversed.
+//
+// Each material is a `StandardMaterial3D` written next to the scene as
+// `<scene>_<material>.tres` and built from `PbrMaterial`, so it matches the
+// glTF output. Resources are referenced under `resource_dir`; textures by
+// the file name found in `texture_dirs`, taken out next to the scene if
+// zipped, or `<name>.png`. A masked texture is merged into one
+// `<texture>_<mask>.png` written next to the scene, its alpha taken from
+// the mask.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rwx_archive::IMAGE_EXTENSIONS;
+use rwx_normalizer::{NormalizeOptions, NormalizedMesh, TargetFormat};
+use rwx_parser::{Face, Vertex};
+use rwx_scene::{AlphaMode, PbrMaterial, RwxNode, RwxScene};
+
+use crate::{masked_texture, node_mesh, rotation_matrix, texture_file, unique};
+
+// RenderingServer array format flags
+const FORMAT_VERTEX: u64 = 1;
+const FORMAT_NORMAL: u64 = 1 << 1;
+const FORMAT_TANGENT: u64 = 1 << 2;
+const FORMAT_COLOR: u64 = 1 << 3;
+const FORMAT_TEX_UV: u64 = 1 << 4;
+const FORMAT_INDEX: u64 = 1 << 12;
+const FORMAT_VERSION_2: u64 = 1 << 35;
+
+const PRIMITIVE_TRIANGLES: u32 = 3;
+
+#[derive(Debug, Clone, Default)]
+pub struct GodotOptions {
+    /// Directories searched for texture files
+    pub texture_dirs: Vec<PathBuf>,
+    /// `res://` directory the scene, materials and textures will live in;
+    /// the project root when unset
+    pub resource_dir: Option<String>,
+}
+
+/// Writes `scene` to `path` as a Godot scene, plus one `.tres` per material
+/// next to it.
+pub fn export_godot(scene: &RwxScene, path: &Path, opts: &GodotOptions) -> io::Result<()> {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let res = match &opts.resource_dir {
+        Some(d) => format!("{}/", d.trim_end_matches('/')),
+        None => "res://".to_string(),
+    };
+
+    let pbr = scene.pbr_materials();
+    let mut taken = Vec::new();
+    let mut material_files = Vec::new();
+    for mat in &pbr {
+        let file = format!("{stem}_{}.tres", unique(file_safe(&mat.name), &mut taken));
+        fs::write(dir.join(&file), material_resource(mat, &res, dir, opts)?)?;
+        material_files.push(file);
+    }
+
+    let mut writer = SceneWriter {
+        scene,
+        normalize: NormalizeOptions::for_format(TargetFormat::Godot),
+        materials: vec![None; pbr.len()],
+        ext: String::new(),
+        ext_count: 0,
+        sub: String::new(),
+        sub_count: 0,
+        nodes: String::new(),
+    };
+    writer.node(&scene.root, &prim_name(&scene.root.name), None);
+
+    // materials are listed in the order their surfaces first use them
+    let mut order: Vec<(usize, usize)> = writer.materials.iter().enumerate().filter_map(|(m, id)| Some(((*id)?, m))).collect();
+    order.sort_unstable();
+    for (id, m) in order {
+        let _ = writeln!(writer.ext, "[ext_resource type=\"Material\" path=\"{res}{}\" id=\"{id}\"]", material_files[m]);
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "[gd_scene load_steps={} format=3]\n", writer.ext_count + writer.sub_count + 1);
+    if !writer.ext.is_empty() {
+        let _ = writeln!(out, "{}", writer.ext);
+    }
+    out.push_str(&writer.sub);
+    out.push_str(&writer.nodes);
+    fs::write(path, out)
+}
+
+struct SceneWriter<'a> {
+    scene: &'a RwxScene,
+    normalize: NormalizeOptions,
+    /// ext_resource id of each scene material, once a surface uses it
+    materials: Vec<Option<usize>>,
+    ext: String,
+    ext_count: usize,
+    sub: String,
+    sub_count: usize,
+    nodes: String,
+}
+
+impl SceneWriter<'_> {
+    /// Writes `node` as `name` under `parent`, the path of the parent node
+    /// relative to the root (`None` for the root itself).
+    fn node(&mut self, node: &RwxNode, name: &str, parent: Option<&str>) {
+        let mesh = node_mesh(node, &self.scene.materials, &self.normalize);
+        let kind = if mesh.is_some() { "MeshInstance3D" } else { "Node3D" };
+
+        match parent {
+            Some(p) => {
+                let _ = writeln!(self.nodes, "[node name=\"{name}\" type=\"{kind}\" parent=\"{p}\"]");
+            }
+            None => {
+                let _ = writeln!(self.nodes, "[node name=\"{name}\" type=\"{kind}\"]");
+            }
+        }
+        if let Some(transform) = self.transform(node) {
+            let _ = writeln!(self.nodes, "transform = {transform}");
+        }
+        if let Some(mesh) = &mesh {
+            let id = self.array_mesh(&node.name, mesh);
+            let _ = writeln!(self.nodes, "mesh = SubResource(\"{id}\")");
+        }
+        if let Some(tag) = node.tag {
+            let _ = writeln!(self.nodes, "metadata/rwx_tag = {tag}");
+        }
+        self.nodes.push('\n');
+
+        // paths below the root are relative to it
+        let path = match parent {
+            None => ".".to_string(),
+            Some(".") => name.to_string(),
+            Some(p) => format!("{p}/{name}"),
+        };
+
+        let mut taken = Vec::new();
+        if let (Some(mesh), true) = (&mesh, node.collision) {
+            taken.push("StaticBody3D".to_string());
+            let shape = self.collision_shape(mesh);
+            let _ = writeln!(self.nodes, "[node name=\"StaticBody3D\" type=\"StaticBody3D\" parent=\"{path}\"]\n");
+            let body = if path == "." { "StaticBody3D".to_string() } else { format!("{path}/StaticBody3D") };
+            let _ = writeln!(self.nodes, "[node name=\"CollisionShape3D\" type=\"CollisionShape3D\" parent=\"{body}\"]");
+            let _ = writeln!(self.nodes, "shape = SubResource(\"{shape}\")\n");
+        }
+
+        for child in &node.children {
+            let child_name = unique(prim_name(&child.name), &mut taken);
+            self.node(child, &child_name, Some(&path));
+        }
+    }
+
+    fn transform(&self, node: &RwxNode) -> Option<String> {
+        let tf = &node.transform;
+        if tf.translate.is_none() && tf.rotate.is_none() && tf.scale.is_none() {
+            return None;
+        }
+        let s = tf.scale.unwrap_or([1.0; 3]);
+        let t = tf.translate.unwrap_or([0.0; 3]).map(|c| c * self.normalize.coordinates.scale);
+        let r = tf.rotate.map_or([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], rotation_matrix);
+
+        // basis rows, then origin
+        let mut values = Vec::new();
+        for row in r {
+            values.extend((0..3).map(|k| row[k] * s[k]));
+        }
+        values.extend(t);
+        Some(format!("Transform3D({})", list(&values)))
+    }
+
+    fn material(&mut self, m: usize) -> usize {
+        if let Some(id) = self.materials[m] {
+            return id;
+        }
+        self.ext_count += 1;
+        self.materials[m] = Some(self.ext_count);
+        self.ext_count
+    }
+
+    fn array_mesh(&mut self, name: &str, mesh: &NormalizedMesh) -> String {
+        self.sub_count += 1;
+        let id = format!("ArrayMesh_{}", self.sub_count);
+
+        let mut surfaces = Vec::new();
+        for (mat, triangles) in surfaces_by_material(mesh) {
+            let Some(m) = self.scene.materials.iter().position(|x| x.name == mat) else { continue };
+            let material = self.material(m);
+            surfaces.push(surface(&mat, &triangles, &mesh.vertices, material));
+        }
+
+        let _ = writeln!(self.sub, "[sub_resource type=\"ArrayMesh\" id=\"{id}\"]");
+        let _ = writeln!(self.sub, "resource_name = \"{}\"", escape(name));
+        let _ = writeln!(self.sub, "_surfaces = [{}]\n", surfaces.join(", "));
+        id
+    }
+
+    fn collision_shape(&mut self, mesh: &NormalizedMesh) -> String {
+        self.sub_count += 1;
+        let id = format!("ConcavePolygonShape3D_{}", self.sub_count);
+
+        let mut points = Vec::new();
+        for face in &mesh.faces {
+            let (Face::Quad(idx, _) | Face::Poly(idx, _)) = face;
+            for &i in [idx[0], idx[2], idx[1]].iter() {
+                let v = &mesh.vertices[i as usize];
+                points.extend([v.x, v.y, v.z]);
+            }
+        }
+
+        let _ = writeln!(self.sub, "[sub_resource type=\"ConcavePolygonShape3D\" id=\"{id}\"]");
+        let _ = writeln!(self.sub, "data = PackedVector3Array({})\n", list(&points));
+        id
+    }
+}
+
+/// Triangles grouped by material, each as indices into `mesh.vertices`.
+fn surfaces_by_material(mesh: &NormalizedMesh) -> Vec<(String, Vec<[u32; 3]>)> {
+    let mut groups: Vec<(String, Vec<[u32; 3]>)> = Vec::new();
+    for face in &mesh.faces {
+        let (Face::Quad(idx, mat) | Face::Poly(idx, mat)) = face;
+        let tri = [idx[0], idx[1], idx[2]];
+        match groups.iter_mut().find(|(m, _)| m == mat) {
+            Some((_, list)) => list.push(tri),
+            None => groups.push((mat.clone(), vec![tri])),
+        }
+    }
+    groups
+}
+
+/// One `_surfaces` entry with its own vertex buffer.
+fn surface(name: &str, triangles: &[[u32; 3]], vertices: &[Vertex], material: usize) -> String {
+    // local vertex numbering for this surface
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut used: Vec<&Vertex> = Vec::new();
+    let mut indices = Vec::new();
+    for tri in triangles {
+        for &i in &[tri[0], tri[2], tri[1]] {
+            if remap[i as usize] == u32::MAX {
+                remap[i as usize] = used.len() as u32;
+                used.push(&vertices[i as usize]);
+            }
+            indices.push(remap[i as usize]);
+        }
+    }
+
+    let colors = used.iter().any(|v| v.color.is_some());
+    let mut format = FORMAT_VERTEX | FORMAT_NORMAL | FORMAT_TANGENT | FORMAT_TEX_UV | FORMAT_INDEX | FORMAT_VERSION_2;
+    if colors {
+        format |= FORMAT_COLOR;
+    }
+
+    let mut vertex_data = Vec::new();
+    for v in &used {
+        for c in [v.x, v.y, v.z] {
+            vertex_data.extend(c.to_le_bytes());
+        }
+    }
+    for v in &used {
+        let n = v.normal.unwrap_or([0.0, 1.0, 0.0]);
+        let t = v.tangent.unwrap_or_else(|| fallback_tangent(n));
+        let (nx, ny) = octahedron_encode([n[0], n[1], n[2]]);
+        let (tx, ty) = octahedron_tangent_encode([t[0], t[1], t[2]], t[3]);
+        for c in [nx, ny, tx, ty] {
+            vertex_data.extend(((c * 65535.0).clamp(0.0, 65535.0) as u16).to_le_bytes());
+        }
+    }
+
+    let mut attribute_data = Vec::new();
+    for v in &used {
+        if colors {
+            let c = v.color.unwrap_or([1.0; 3]);
+            attribute_data.extend(c.map(|k| (k.clamp(0.0, 1.0) * 255.0).round() as u8));
+            attribute_data.push(255);
+        }
+        attribute_data.extend(v.u.to_le_bytes());
+        attribute_data.extend(v.v.to_le_bytes());
+    }
+
+    let wide = used.len() > u16::MAX as usize;
+    let index_data: Vec<u8> = if wide {
+        indices.iter().flat_map(|i| i.to_le_bytes()).collect()
+    } else {
+        indices.iter().flat_map(|&i| (i as u16).to_le_bytes()).collect()
+    };
+
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for v in &used {
+        for (k, c) in [v.x, v.y, v.z].into_iter().enumerate() {
+            min[k] = min[k].min(c);
+            max[k] = max[k].max(c);
+        }
+    }
+    let aabb = [min[0], min[1], min[2], max[0] - min[0], max[1] - min[1], max[2] - min[2]];
+
+    let mut out = String::from("{\n");
+    let _ = writeln!(out, "\"aabb\": AABB({}),", list(&aabb));
+    let _ = writeln!(out, "\"attribute_data\": PackedByteArray({}),", bytes(&attribute_data));
+    let _ = writeln!(out, "\"format\": {format},");
+    let _ = writeln!(out, "\"index_count\": {},", indices.len());
+    let _ = writeln!(out, "\"index_data\": PackedByteArray({}),", bytes(&index_data));
+    let _ = writeln!(out, "\"material\": ExtResource(\"{material}\"),");
+    let _ = writeln!(out, "\"name\": \"{}\",", escape(name));
+    let _ = writeln!(out, "\"primitive\": {PRIMITIVE_TRIANGLES},");
+    let _ = writeln!(out, "\"vertex_count\": {},", used.len());
+    let _ = writeln!(out, "\"vertex_data\": PackedByteArray({})", bytes(&vertex_data));
+    out.push('}');
+    out
+}
+
+fn material_resource(mat: &PbrMaterial, res: &str, dir: &Path, opts: &GodotOptions) -> io::Result<String> {
+    let texture = match (&mat.base_color_texture, &mat.alpha_texture) {
+        (Some(name), Some(mask)) => {
+            let (file, data) = masked_texture(name, mask, &opts.texture_dirs)?;
+            if let Some(data) = data {
+                fs::write(dir.join(&file), data)?;
+            }
+            Some(file)
+        }
+        (Some(name), None) => {
+            let (file, data) = texture_file(name, &opts.texture_dirs, &IMAGE_EXTENSIONS)?;
+            if let Some(data) = data {
+                fs::write(dir.join(&file), data)?;
+            }
+            Some(file)
+        }
+        (None, _) => None,
+    };
+
+    let mut out = String::new();
+    let steps = if texture.is_some() { 2 } else { 1 };
+    let _ = writeln!(out, "[gd_resource type=\"StandardMaterial3D\" load_steps={steps} format=3]\n");
+    if let Some(file) = &texture {
+        let _ = writeln!(out, "[ext_resource type=\"Texture2D\" path=\"{res}{file}\" id=\"1\"]\n");
+    }
+    out.push_str("[resource]\n");
+    let _ = writeln!(out, "resource_name = \"{}\"", escape(&mat.name));
+    match mat.alpha_mode {
+        AlphaMode::Opaque => {}
+        // BaseMaterial3D.TRANSPARENCY_ALPHA_SCISSOR
+        AlphaMode::Mask { cutoff } => {
+            let _ = writeln!(out, "transparency = 2\nalpha_scissor_threshold = {cutoff}");
+        }
+        // BaseMaterial3D.TRANSPARENCY_ALPHA
+        AlphaMode::Blend => out.push_str("transparency = 1\n"),
+    }
+    if mat.double_sided {
+        // BaseMaterial3D.CULL_DISABLED
+        out.push_str("cull_mode = 2\n");
+    }
+    let _ = writeln!(out, "albedo_color = Color({})", list(&mat.base_color));
+    if texture.is_some() {
+        out.push_str("albedo_texture = ExtResource(\"1\")\n");
+    }
+    let _ = writeln!(out, "metallic = {}", mat.metallic);
+    let _ = writeln!(out, "roughness = {}", mat.roughness);
+    Ok(out)
+}
+
+/// Godot's `Vector3::octahedron_encode`, in 0..1.
+fn octahedron_encode(n: [f32; 3]) -> (f32, f32) {
+    let sum = n[0].abs() + n[1].abs() + n[2].abs();
+    let n = if sum > 0.0 { n.map(|c| c / sum) } else { [0.0, 0.0, 1.0] };
+    let (x, y) = if n[2] >= 0.0 {
+        (n[0], n[1])
+    } else {
+        ((1.0 - n[1].abs()) * n[0].signum(), (1.0 - n[0].abs()) * n[1].signum())
+    };
+    (x * 0.5 + 0.5, y * 0.5 + 0.5)
+}
+
+/// Godot's `Vector3::octahedron_tangent_encode`: the bitangent sign is
+/// folded into the second component.
+fn octahedron_tangent_encode(t: [f32; 3], sign: f32) -> (f32, f32) {
+    let (x, y) = octahedron_encode(t);
+    let y = y.max(1.0 / 32767.0) * 0.5 + 0.5;
+    (x, if sign >= 0.0 { y } else { 1.0 - y })
+}
+
+/// Any unit vector perpendicular to `n`, for vertices without a tangent.
+fn fallback_tangent(n: [f32; 3]) -> [f32; 4] {
+    let axis = if n[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    let d = axis[0] * n[0] + axis[1] * n[1] + axis[2] * n[2];
+    let t = [axis[0] - n[0] * d, axis[1] - n[1] * d, axis[2] - n[2] * d];
+    let len = (t[0] * t[0] + t[1] * t[1] + t[2] * t[2]).sqrt();
+    [t[0] / len, t[1] / len, t[2] / len, 1.0]
+}
+
+/// A valid node name: Godot reserves `. : @ / " %`.
+fn prim_name(name: &str) -> String {
+    let out: String = name.chars().map(|c| if ".:@/\"%".contains(c) { '_' } else { c }).collect();
+    if out.is_empty() { "_".to_string() } else { out }
+}
+
+fn file_safe(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' }).collect()
+}
+
+fn list(values: &[f32]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn bytes(data: &[u8]) -> String {
+    data.iter().map(|b| b.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,98 @@
+// JSON scene documents.
+//
+// `rwx_tool to-json` writes a `SceneDocument`: a small versioned envelope
+// around the `RwxScene` as serde serializes it. The layout is:
+//
+// - `format`: always `"rwx-scene"`
+// - `version`: `SCENE_FORMAT_VERSION`; raised whenever a change would break
+//   an existing reader (renamed or removed fields, changed meaning). Adding
+//   an optional field does not raise it.
+// - `metadata`: generator, source file and the units positions are in
+// - `scene`: the clump tree (`root`) and its materials
+//
+// Geometry is stored as parsed: positions in RWX units, faces as 1-based
+// indices into their node's vertices, and materials referenced by name.
+// The JSON Schema produced by `scene_schema` is generated from these types
+// and checked into `rwx_export/schema/`, so external tools can validate
+// documents without reading Rust.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rwx_normalizer::RWX_UNIT_METRES;
+use rwx_scene::RwxScene;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const SCENE_FORMAT: &str = "rwx-scene";
+pub const SCENE_FORMAT_VERSION: u32 = 1;
+
+/// An RWX scene as written by `rwx_tool to-json`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SceneDocument {
+    /// Always `rwx-scene`
+    pub format: String,
+    /// Format version; readers reject versions newer than they know
+    pub version: u32,
+    pub metadata: SceneMetadata,
+    pub scene: RwxScene,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SceneMetadata {
+    /// Tool and version that wrote the document
+    pub generator: String,
+    /// RWX file the scene was read from
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Metres per unit of vertex positions and translations
+    pub unit_metres: f32,
+    /// Coordinate convention of the geometry
+    pub coordinate_system: String,
+}
+
+impl SceneDocument {
+    pub fn new(scene: &RwxScene, source: Option<&str>) -> Self {
+        SceneDocument {
+            format: SCENE_FORMAT.to_string(),
+            version: SCENE_FORMAT_VERSION,
+            metadata: SceneMetadata {
+                generator: format!("rwx_export {}", env!("CARGO_PKG_VERSION")),
+                source: source.map(str::to_string),
+                unit_metres: RWX_UNIT_METRES,
+                coordinate_system: "right-handed, Y up, V from the top".to_string(),
+            },
+            scene: scene.clone(),
+        }
+    }
+}
+
+pub fn write_scene_json(scene: &RwxScene, source: Option<&str>, path: &Path) -> io::Result<()> {
+    let doc = SceneDocument::new(scene, source);
+    fs::write(path, serde_json::to_string_pretty(&doc)?)
+}
+
+/// Reads a document written by `write_scene_json`, rejecting other formats
+/// and newer versions.
+pub fn read_scene_json(path: &Path) -> io::Result<RwxScene> {
+    let doc: SceneDocument = serde_json::from_slice(&fs::read(path)?)?;
+    if doc.format != SCENE_FORMAT {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("not an RWX scene document (format {:?})", doc.format),
+        ));
+    }
+    if doc.version > SCENE_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("scene document version {} is newer than supported version {}", doc.version, SCENE_FORMAT_VERSION),
+        ));
+    }
+    Ok(doc.scene)
+}
+
+/// JSON Schema (draft 2020-12) for `SceneDocument`.
+pub fn scene_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(SceneDocument)).expect("schema serializes")
+}
@@ -0,0 +1,216 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rwx_archive::IMAGE_EXTENSIONS;
+use rwx_normalizer::{normalize_with, NormalizeOptions, NormalizedMesh};
+use rwx_parser::{Face, Vertex};
+use rwx_scene::{RwxNode, RwxScene};
+use rwx_semantics::FlatMesh;
+use rwx_texture::{converted_name, height_to_normal, TextureFormat, TextureOptions};
+
+pub mod collada;
+pub mod gltf;
+pub mod godot;
+pub mod json;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+pub mod usd;
+
+pub use collada::{export_collada, ColladaOptions};
+pub use gltf::{export_gltf, GltfOptions};
+pub use godot::{export_godot, GodotOptions};
+pub use obj::{export_mesh_obj, export_obj, ObjOptions};
+pub use ply::{export_ply, PlyOptions};
+pub use stl::{export_stl, StlOptions, Watertightness};
+pub use json::{read_scene_json, scene_schema, write_scene_json, SceneDocument, SceneMetadata, SCENE_FORMAT_VERSION};
+pub use usd::{export_usd, UsdOptions};
+
+/// Slope scale for bump maps; RWX leaves the height range unspecified, and
+/// this gives pronounced but not exaggerated relief on typical 8-bit maps.
+const BUMP_STRENGTH: f32 = 4.0;
+
+/// Normalizes the geometry of one scene node, or returns `None` for nodes
+/// that only group children.
+pub(crate) fn node_mesh(
+    node: &RwxNode,
+    materials: &[rwx_parser::Material],
+    opts: &NormalizeOptions,
+) -> Option<NormalizedMesh> {
+    if node.faces.is_empty() {
+        return None;
+    }
+    let mesh = normalize_with(&node_flat(node, materials), opts);
+    if mesh.faces.is_empty() { None } else { Some(mesh) }
+}
+
+/// The geometry of one scene node as the normalizer takes it.
+pub(crate) fn node_flat(node: &RwxNode, materials: &[rwx_parser::Material]) -> FlatMesh {
+    FlatMesh {
+        name: node.name.clone(),
+        vertices: node.vertices.clone(),
+        faces: node.faces.clone(),
+        materials: materials.to_vec(),
+        tag: node.tag,
+        collision: node.collision,
+    }
+}
+
+/// Every node of `scene` normalized in world space and merged into one
+/// vertex list, for formats without a hierarchy.
+pub(crate) fn merged_mesh(scene: &RwxScene, opts: &NormalizeOptions) -> (Vec<Vertex>, Vec<[u32; 3]>) {
+    let mut vertices = Vec::new();
+    let mut triangles = Vec::new();
+    for node in scene.flatten() {
+        let Some(mesh) = node_mesh(&node, &scene.materials, opts) else { continue };
+        let offset = vertices.len() as u32;
+        for face in &mesh.faces {
+            let (Face::Quad(idx, _) | Face::Poly(idx, _)) = face;
+            triangles.push([idx[0] + offset, idx[1] + offset, idx[2] + offset]);
+        }
+        vertices.extend(mesh.vertices);
+    }
+    (vertices, triangles)
+}
+
+/// Triangle indices grouped by material, in order of first use.
+pub(crate) fn by_material(faces: &[Face]) -> Vec<(&str, Vec<u32>)> {
+    let mut groups: Vec<(&str, Vec<u32>)> = Vec::new();
+    for face in faces {
+        let (idx, mat) = match face {
+            Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat.as_str()),
+        };
+        match groups.iter_mut().find(|(m, _)| *m == mat) {
+            Some((_, list)) => list.extend_from_slice(&idx[..3]),
+            None => groups.push((mat, idx[..3].to_vec())),
+        }
+    }
+    groups
+}
+
+/// `[angle_degrees, x, y, z]` to a unit quaternion `[x, y, z, w]`.
+pub(crate) fn quaternion(r: [f32; 4]) -> [f32; 4] {
+    let len = (r[1] * r[1] + r[2] * r[2] + r[3] * r[3]).sqrt();
+    if len == 0.0 {
+        return [0.0, 0.0, 0.0, 1.0];
+    }
+    let (s, c) = (r[0].to_radians() * 0.5).sin_cos();
+    [r[1] / len * s, r[2] / len * s, r[3] / len * s, c]
+}
+
+/// Row-major rotation matrix for `[angle_degrees, x, y, z]`.
+pub(crate) fn rotation_matrix(r: [f32; 4]) -> [[f32; 3]; 3] {
+    let len = (r[1] * r[1] + r[2] * r[2] + r[3] * r[3]).sqrt();
+    if len == 0.0 {
+        return [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+    }
+    let (x, y, z) = (r[1] / len, r[2] / len, r[3] / len);
+    let (s, c) = r[0].to_radians().sin_cos();
+    let k = 1.0 - c;
+    [
+        [c + x * x * k, x * y * k - z * s, x * z * k + y * s],
+        [y * x * k + z * s, c + y * y * k, y * z * k - x * s],
+        [z * x * k - y * s, z * y * k + x * s, c + z * z * k],
+    ]
+}
+
+/// Looks for texture `name` in each directory, ignoring case, as
+/// `<name>.<ext>` with the extensions in order or zipped as `<name>.zip`.
+pub(crate) fn find_texture(name: &str, dirs: &[PathBuf], extensions: &[&str]) -> Option<PathBuf> {
+    rwx_archive::find_file(name, dirs, extensions)
+}
+
+/// File name and contents of texture `name`, taken out of its archive if
+/// it is zipped, or `None` if it cannot be found.
+pub(crate) fn read_texture(name: &str, dirs: &[PathBuf], extensions: &[&str]) -> io::Result<Option<(String, Vec<u8>)>> {
+    find_texture(name, dirs, extensions).map(|path| rwx_archive::read_file(&path, extensions)).transpose()
+}
+
+/// The file name to reference texture `name` by: the one found in `dirs`,
+/// or `<name>.png`. A zipped texture also comes with the image taken out
+/// of the archive, to be written next to the output.
+pub(crate) fn texture_file(name: &str, dirs: &[PathBuf], extensions: &[&str]) -> io::Result<(String, Option<Vec<u8>>)> {
+    match find_texture(name, dirs, extensions) {
+        Some(path) if rwx_archive::is_zip(&path) => {
+            let (file, data) = rwx_archive::read_file(&path, extensions)?;
+            Ok((file, Some(data)))
+        }
+        Some(path) => Ok((file_name(&path), None)),
+        None => Ok((format!("{name}.png"), None)),
+    }
+}
+
+/// Whether `file` is a PNG or JPEG, the only images glTF and USD viewers
+/// are required to read.
+fn is_png_or_jpeg(file: &str) -> bool {
+    let ext = Path::new(file).extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    matches!(ext.as_deref(), Some("png" | "jpg" | "jpeg"))
+}
+
+/// [`read_texture`] in any image format, with images that are not PNG or
+/// JPEG (BMP) converted to `<name>.png`.
+pub(crate) fn read_web_texture(name: &str, dirs: &[PathBuf]) -> io::Result<Option<(String, Vec<u8>)>> {
+    let Some((file, data)) = read_texture(name, dirs, &IMAGE_EXTENSIONS)? else {
+        return Ok(None);
+    };
+    if is_png_or_jpeg(&file) {
+        return Ok(Some((file, data)));
+    }
+    let png = rwx_texture::convert(&data, None, &TextureOptions::default())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some((converted_name(name, None, TextureFormat::Png), png)))
+}
+
+/// [`texture_file`] in any image format, with images that are not PNG or
+/// JPEG (BMP) converted to `<name>.png` and returned to be written next to
+/// the output.
+pub(crate) fn web_texture_file(name: &str, dirs: &[PathBuf]) -> io::Result<(String, Option<Vec<u8>>)> {
+    let found = texture_file(name, dirs, &IMAGE_EXTENSIONS)?;
+    if is_png_or_jpeg(&found.0) {
+        return Ok(found);
+    }
+    Ok(read_web_texture(name, dirs)?.map_or(found, |(file, data)| (file, Some(data))))
+}
+
+/// `texture` with `mask` merged into its alpha channel, as the name of the
+/// PNG to reference and its contents. The contents are `None` when either
+/// image cannot be found; the name is still referenced so the file can be
+/// made later.
+pub(crate) fn masked_texture(texture: &str, mask: &str, dirs: &[PathBuf]) -> io::Result<(String, Option<Vec<u8>>)> {
+    let name = converted_name(texture, Some(mask), TextureFormat::Png);
+    let (Some((_, base)), Some((_, mask))) = (read_texture(texture, dirs, &IMAGE_EXTENSIONS)?, read_texture(mask, dirs, &IMAGE_EXTENSIONS)?) else {
+        return Ok((name, None));
+    };
+    let data = rwx_texture::convert(&base, Some(&mask), &TextureOptions::default())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((name, Some(data)))
+}
+
+/// Bump map `bump` converted to a tangent-space normal map, as the name of
+/// the PNG to reference and its contents, `None` if the bump map cannot be
+/// found.
+pub(crate) fn normal_texture(bump: &str, dirs: &[PathBuf]) -> io::Result<(String, Option<Vec<u8>>)> {
+    let name = format!("{bump}_normal.png");
+    let Some((_, data)) = read_texture(bump, dirs, &IMAGE_EXTENSIONS)? else {
+        return Ok((name, None));
+    };
+    let invalid = |e| io::Error::new(io::ErrorKind::InvalidData, e);
+    let normals = height_to_normal(&rwx_texture::load(&data).map_err(invalid)?, BUMP_STRENGTH);
+    Ok((name, Some(rwx_texture::encode(&normals, TextureFormat::Png).map_err(invalid)?)))
+}
+
+/// `name`, suffixed with `_2`, `_3`, ... if a sibling already took it.
+pub(crate) fn unique(name: String, taken: &mut Vec<String>) -> String {
+    let mut out = name.clone();
+    let mut n = 1;
+    while taken.contains(&out) {
+        n += 1;
+        out = format!("{name}_{n}");
+    }
+    taken.push(out.clone());
+    out
+}
+
+pub(crate) fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
@@ -0,0 +1,267 @@
+// Wavefront OBJ/MTL export.
+//
+// The scene is flattened to world space and every clump with geometry
+// becomes an `o`/`g` pair. Geometry goes through the normalizer with the
+// OBJ settings: metres, V from the bottom, and double-sided materials
+// duplicated into back faces since OBJ has no way to turn culling off.
+//
+// RWX lighting maps directly onto MTL: Ka/Kd are the colour scaled by the
+// ambient/diffuse factors, a texture replaces the colour as in RWX, and
+// opacity is `d`. Textures are referenced by the file name found in
+// `texture_dirs`, or as `<name>.png` when they cannot be found. A masked
+// texture is merged with its mask into `<texture>_<mask>.png`, written
+// next to the MTL and used for both `map_Kd` and `map_d`. Textures found
+// zipped are taken out of the archive next to the MTL too.
+//
+// With `batch` set, clumps are merged as `rwx_normalizer::batch` does and
+// each batch becomes one object whose faces are grouped by material, so
+// an object made of many clumps loads as a few draw calls.
+//
+// OBJ and MTL statements end at whitespace, so spaces in names become
+// underscores. RWX names never hold whitespace, as the lexer splits on it,
+// but object names taken from file names and texture files found on disk
+// can. An escaped name that would clash with another gets a `_2`, `_3`, ...
+// suffix, so two materials never merge. A texture found under a name with
+// spaces is copied next to the MTL under the escaped name, so the
+// reference still resolves.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rwx_archive::IMAGE_EXTENSIONS;
+use rwx_normalizer::{batch, BatchOptions, NormalizeOptions, NormalizedMesh, TargetFormat};
+use rwx_parser::{Face, Material};
+use rwx_scene::RwxScene;
+
+use crate::{file_name, find_texture, masked_texture, node_flat, node_mesh, texture_file, unique};
+
+#[derive(Debug, Clone, Default)]
+pub struct ObjOptions {
+    /// Directories searched for texture files
+    pub texture_dirs: Vec<PathBuf>,
+    /// Merge clumps into batches instead of writing one object per clump
+    pub batch: Option<BatchOptions>,
+}
+
+/// Writes `path` and an MTL file next to it, one object per clump or per
+/// batch.
+pub fn export_obj(scene: &RwxScene, path: &Path, opts: &ObjOptions) -> io::Result<()> {
+    let normalize = NormalizeOptions::for_format(TargetFormat::Obj);
+    let nodes = scene.flatten();
+    let objects: Vec<(String, NormalizedMesh)> = match &opts.batch {
+        Some(batch_opts) => {
+            let clumps: Vec<_> =
+                nodes.iter().filter(|n| !n.faces.is_empty()).map(|n| node_flat(n, &scene.materials)).collect();
+            batch(&clumps, &normalize, batch_opts)
+                .into_iter()
+                .filter(|b| !b.mesh.faces.is_empty())
+                .map(|b| (b.name, b.mesh))
+                .collect()
+        }
+        None => nodes
+            .iter()
+            .filter_map(|node| Some((node.name.clone(), node_mesh(node, &scene.materials, &normalize)?)))
+            .collect(),
+    };
+
+    write_files(&objects, &scene.materials, path, opts)
+}
+
+/// Writes an already normalized triangle mesh, e.g. one LOD level. The mesh
+/// is expected to be in OBJ coordinates already.
+pub fn export_mesh_obj(mesh: &NormalizedMesh, path: &Path, opts: &ObjOptions) -> io::Result<()> {
+    let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    write_files(&[(name, mesh.clone())], &mesh.materials, path, opts)
+}
+
+fn write_files(
+    objects: &[(String, NormalizedMesh)],
+    materials: &[Material],
+    path: &Path,
+    opts: &ObjOptions,
+) -> io::Result<()> {
+    let mtl_path = path.with_extension("mtl");
+
+    let material_names: Vec<String> = materials.iter().map(|m| m.name.clone()).collect();
+    let material_names = escape_names(&material_names, false);
+
+    let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+    let files = write_mtl(&mut mtl, materials, &material_names, opts)?;
+    mtl.flush()?;
+    for (name, data) in files {
+        fs::write(mtl_path.with_file_name(name), data)?;
+    }
+
+    let mut obj = BufWriter::new(File::create(path)?);
+    write_obj(&mut obj, objects, materials, &material_names, &file_name(&mtl_path))?;
+    obj.flush()
+}
+
+fn write_obj(
+    out: &mut impl Write,
+    objects: &[(String, NormalizedMesh)],
+    materials: &[Material],
+    material_names: &[String],
+    mtl_name: &str,
+) -> io::Result<()> {
+    // relative, so the pair can be moved together
+    writeln!(out, "mtllib {}", escape(mtl_name))?;
+
+    let object_names: Vec<String> = objects.iter().map(|(name, _)| name.clone()).collect();
+    let material_name = |name: &str| match materials.iter().position(|m| m.name == name) {
+        Some(i) => material_names[i].clone(),
+        None => escape(name),
+    };
+
+    // OBJ indices are 1-based and global across the file
+    let mut offset = 1;
+    for ((_, mesh), name) in objects.iter().zip(escape_names(&object_names, false)) {
+        writeln!(out, "o {name}")?;
+        writeln!(out, "g {name}")?;
+
+        for v in &mesh.vertices {
+            writeln!(out, "v {} {} {}", v.x, v.y, v.z)?;
+        }
+        for v in &mesh.vertices {
+            writeln!(out, "vt {} {}", v.u, v.v)?;
+        }
+        for v in &mesh.vertices {
+            let n = v.normal.unwrap_or([0.0, 1.0, 0.0]);
+            writeln!(out, "vn {} {} {}", n[0], n[1], n[2])?;
+        }
+
+        let mut last_material = None;
+        for face in &mesh.faces {
+            let (idx, mat) = match face {
+                Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat),
+            };
+            if last_material != Some(mat) {
+                writeln!(out, "usemtl {}", material_name(mat))?;
+                last_material = Some(mat);
+            }
+
+            write!(out, "f")?;
+            for ix in idx {
+                write!(out, " {0}/{0}/{0}", ix + offset)?;
+            }
+            writeln!(out)?;
+        }
+
+        offset += mesh.vertices.len() as u32;
+    }
+
+    Ok(())
+}
+
+/// Writes the materials under `names`, returning the merged masked
+/// textures, the textures taken out of archives and the ones copied under
+/// an escaped name, to write next to the MTL.
+fn write_mtl(
+    out: &mut impl Write,
+    materials: &[Material],
+    names: &[String],
+    opts: &ObjOptions,
+) -> io::Result<Vec<(String, Vec<u8>)>> {
+    // every texture is found first, so escaped file names can be kept apart
+    // from all the others
+    let mut found = Vec::new();
+    for mat in materials {
+        let Some(tex) = &mat.texture else { continue };
+        found.push(match &mat.mask {
+            Some(mask) => masked_texture(tex, mask, &opts.texture_dirs)?,
+            None => texture(tex, &opts.texture_dirs)?,
+        });
+        if let Some(bump) = &mat.bump {
+            found.push(texture(bump, &opts.texture_dirs)?);
+        }
+    }
+    let found_names: Vec<String> = found.iter().map(|(file, _)| file.clone()).collect();
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut textures = Vec::new();
+    for (name, (_, data)) in escape_names(&found_names, true).into_iter().zip(found) {
+        if let Some(data) = data {
+            if !files.iter().any(|(n, _)| *n == name) {
+                files.push((name.clone(), data));
+            }
+        }
+        textures.push(name);
+    }
+    let mut textures = textures.into_iter();
+
+    for (mat, name) in materials.iter().zip(names) {
+        let color = if mat.texture.is_some() { [1.0; 3] } else { mat.color };
+        let ka = color.map(|c| c * mat.ambient);
+        let kd = color.map(|c| c * mat.diffuse);
+
+        writeln!(out, "newmtl {name}")?;
+        writeln!(out, "Ka {} {} {}", ka[0], ka[1], ka[2])?;
+        writeln!(out, "Kd {} {} {}", kd[0], kd[1], kd[2])?;
+        writeln!(out, "Ks {0} {0} {0}", mat.specular)?;
+        writeln!(out, "d {}", mat.opacity)?;
+        writeln!(out, "illum 2")?;
+        if mat.texture.is_some() {
+            let file = textures.next().unwrap();
+            writeln!(out, "map_Kd {file}")?;
+            if mat.mask.is_some() {
+                writeln!(out, "map_d {file}")?;
+            }
+            if mat.bump.is_some() {
+                writeln!(out, "map_bump {}", textures.next().unwrap())?;
+            }
+        }
+        writeln!(out)?;
+    }
+
+    Ok(files)
+}
+
+/// The file for texture `name`, as [`texture_file`] finds it. A file found
+/// on disk under a name that has to be escaped comes with its contents, so
+/// it is copied next to the MTL under the name the MTL gives.
+fn texture(name: &str, dirs: &[PathBuf]) -> io::Result<(String, Option<Vec<u8>>)> {
+    let (file, data) = texture_file(name, dirs, &IMAGE_EXTENSIONS)?;
+    if data.is_some() || escape(&file) == file {
+        return Ok((file, data));
+    }
+    match find_texture(name, dirs, &IMAGE_EXTENSIONS) {
+        Some(path) => Ok((file, Some(fs::read(path)?))),
+        None => Ok((file, None)),
+    }
+}
+
+/// OBJ and MTL names for `names`, in order. Names that need no escaping
+/// keep their spelling; an escaped name that would clash with another goes
+/// through [`unique`], with the suffix before the extension for `files`.
+fn escape_names(names: &[String], files: bool) -> Vec<String> {
+    let mut taken: Vec<String> = names.iter().filter(|n| escape(n) == **n).cloned().collect();
+    let mut escaped: Vec<(&str, String)> = Vec::new();
+    names
+        .iter()
+        .map(|name| {
+            let plain = escape(name);
+            if plain == *name {
+                return plain;
+            }
+            if let Some((_, e)) = escaped.iter().find(|(n, _)| n == name) {
+                return e.clone();
+            }
+            let e = match plain.rsplit_once('.') {
+                Some((stem, ext)) if files => {
+                    let suffix = format!(".{ext}");
+                    let mut stems = taken.iter().filter_map(|t| t.strip_suffix(&suffix)).map(str::to_string).collect();
+                    let e = format!("{}{suffix}", unique(stem.to_string(), &mut stems));
+                    taken.push(e.clone());
+                    e
+                }
+                _ => unique(plain, &mut taken),
+            };
+            escaped.push((name, e.clone()));
+            e
+        })
+        .collect()
+}
+
+fn escape(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join("_")
+}
@@ -0,0 +1,89 @@
+// PLY export for point-cloud and analysis tools.
+//
+// Every clump is merged in world space into one vertex element carrying
+// position, normal, UV (`s`, `t`) and, when any vertex has a prelight
+// colour, `red`/`green`/`blue`, plus a face element of triangles. Geometry
+// goes through the normalizer with the PLY settings: Z-up metres and V
+// from the bottom, as scanning tools expect.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rwx_normalizer::{NormalizeOptions, TargetFormat};
+use rwx_parser::Vertex;
+use rwx_scene::RwxScene;
+
+use crate::merged_mesh;
+
+#[derive(Debug, Clone)]
+pub struct PlyOptions {
+    /// Binary little-endian PLY; ASCII otherwise
+    pub binary: bool,
+}
+
+impl Default for PlyOptions {
+    fn default() -> Self {
+        PlyOptions { binary: true }
+    }
+}
+
+/// Writes `scene` to `path` as PLY.
+pub fn export_ply(scene: &RwxScene, path: &Path, opts: &PlyOptions) -> io::Result<()> {
+    let (vertices, triangles) = merged_mesh(scene, &NormalizeOptions::for_format(TargetFormat::Ply));
+    let colors = vertices.iter().any(|v| v.color.is_some());
+
+    let mut out = BufWriter::new(File::create(path)?);
+    let format = if opts.binary { "binary_little_endian" } else { "ascii" };
+    writeln!(out, "ply")?;
+    writeln!(out, "format {format} 1.0")?;
+    writeln!(out, "comment generated by rwx_export from {}", scene.root.name)?;
+    writeln!(out, "element vertex {}", vertices.len())?;
+    for p in ["x", "y", "z", "nx", "ny", "nz", "s", "t"] {
+        writeln!(out, "property float {p}")?;
+    }
+    if colors {
+        for p in ["red", "green", "blue"] {
+            writeln!(out, "property uchar {p}")?;
+        }
+    }
+    writeln!(out, "element face {}", triangles.len())?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    for v in &vertices {
+        let n = v.normal.unwrap_or([0.0, 0.0, 1.0]);
+        let floats = [v.x, v.y, v.z, n[0], n[1], n[2], v.u, v.v];
+        let rgb = colors.then(|| color(v));
+        if opts.binary {
+            for f in floats {
+                out.write_all(&f.to_le_bytes())?;
+            }
+            if let Some(rgb) = rgb {
+                out.write_all(&rgb)?;
+            }
+        } else {
+            let mut line: Vec<String> = floats.iter().map(|f| f.to_string()).collect();
+            line.extend(rgb.iter().flatten().map(|c| c.to_string()));
+            writeln!(out, "{}", line.join(" "))?;
+        }
+    }
+
+    for t in &triangles {
+        if opts.binary {
+            out.write_all(&[3])?;
+            for i in t {
+                out.write_all(&i.to_le_bytes())?;
+            }
+        } else {
+            writeln!(out, "3 {} {} {}", t[0], t[1], t[2])?;
+        }
+    }
+
+    out.flush()
+}
+
+/// Prelight colour as bytes; vertices without one are white.
+fn color(v: &Vertex) -> [u8; 3] {
+    v.color.unwrap_or([1.0; 3]).map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
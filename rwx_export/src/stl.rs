@@ -0,0 +1,173 @@
+// STL export for printing.
+//
+// STL is a bare triangle soup, so every clump is merged in world space.
+// Geometry goes through the normalizer with the STL settings: Z-up metres,
+// as slicers expect, and double-sided faces left single so shells stay
+// closed. `scale` converts the metres to the units the receiving tool
+// assumes; most slicers read millimetres.
+//
+// Facet normals are recomputed from each triangle rather than taken from
+// the smoothed vertex normals, since STL readers use them to decide which
+// side is outside.
+//
+// Printing needs a closed, consistently wound surface. The export reports
+// how far the mesh is from that, counting edges by position so UV seams and
+// material borders do not count as openings.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use rwx_normalizer::{NormalizeOptions, TargetFormat};
+use rwx_parser::Vertex;
+use rwx_scene::RwxScene;
+
+use crate::merged_mesh;
+
+#[derive(Debug, Clone)]
+pub struct StlOptions {
+    /// Binary STL; ASCII otherwise
+    pub binary: bool,
+    /// Multiplier on metre positions, e.g. 1000 for millimetres
+    pub scale: f32,
+}
+
+impl Default for StlOptions {
+    fn default() -> Self {
+        StlOptions { binary: true, scale: 1.0 }
+    }
+}
+
+/// Edges that keep a mesh from being a closed, consistently wound surface.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Watertightness {
+    /// Edges used by one triangle only
+    pub boundary_edges: usize,
+    /// Edges used by more than two triangles
+    pub non_manifold_edges: usize,
+    /// Edges shared by two triangles running them the same way
+    pub inconsistent_edges: usize,
+}
+
+impl Watertightness {
+    pub fn is_watertight(&self) -> bool {
+        *self == Watertightness::default()
+    }
+}
+
+impl fmt::Display for Watertightness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_watertight() {
+            return write!(f, "watertight");
+        }
+        write!(
+            f,
+            "not watertight: {} boundary, {} non-manifold and {} inconsistently wound edges",
+            self.boundary_edges, self.non_manifold_edges, self.inconsistent_edges
+        )
+    }
+}
+
+/// Writes `scene` to `path` as one STL solid and reports whether it is
+/// watertight.
+pub fn export_stl(scene: &RwxScene, path: &Path, opts: &StlOptions) -> io::Result<Watertightness> {
+    let (vertices, triangles) = merged_mesh(scene, &NormalizeOptions::for_format(TargetFormat::Stl));
+    let facets: Vec<[[f32; 3]; 3]> = triangles
+        .iter()
+        .map(|t| t.map(|i| {
+            let v = &vertices[i as usize];
+            [v.x * opts.scale, v.y * opts.scale, v.z * opts.scale]
+        }))
+        .collect();
+
+    let mut out = BufWriter::new(File::create(path)?);
+    if opts.binary {
+        write_binary(&mut out, &facets)?;
+    } else {
+        write_ascii(&mut out, &scene.root.name, &facets)?;
+    }
+    out.flush()?;
+
+    Ok(watertightness(&vertices, &triangles))
+}
+
+fn write_binary(out: &mut impl Write, facets: &[[[f32; 3]; 3]]) -> io::Result<()> {
+    // the header must not start with "solid", or readers take it for ASCII
+    let mut header = [0u8; 80];
+    let text = b"rwx_export binary STL";
+    header[..text.len()].copy_from_slice(text);
+    out.write_all(&header)?;
+    out.write_all(&(facets.len() as u32).to_le_bytes())?;
+
+    for facet in facets {
+        for c in normal(facet).iter().chain(facet.iter().flatten()) {
+            out.write_all(&c.to_le_bytes())?;
+        }
+        out.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_ascii(out: &mut impl Write, name: &str, facets: &[[[f32; 3]; 3]]) -> io::Result<()> {
+    let name: String = name.split_whitespace().collect::<Vec<_>>().join("_");
+    writeln!(out, "solid {name}")?;
+    for facet in facets {
+        let n = normal(facet);
+        writeln!(out, "  facet normal {:e} {:e} {:e}", n[0], n[1], n[2])?;
+        writeln!(out, "    outer loop")?;
+        for p in facet {
+            writeln!(out, "      vertex {:e} {:e} {:e}", p[0], p[1], p[2])?;
+        }
+        writeln!(out, "    endloop")?;
+        writeln!(out, "  endfacet")?;
+    }
+    writeln!(out, "endsolid {name}")
+}
+
+/// Unit normal of a counter-clockwise triangle, or zero if it is degenerate.
+fn normal(p: &[[f32; 3]; 3]) -> [f32; 3] {
+    let a = [p[1][0] - p[0][0], p[1][1] - p[0][1], p[1][2] - p[0][2]];
+    let b = [p[2][0] - p[0][0], p[2][1] - p[0][1], p[2][2] - p[0][2]];
+    let n = [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]];
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    if len > 0.0 { n.map(|c| c / len) } else { [0.0; 3] }
+}
+
+fn watertightness(vertices: &[Vertex], triangles: &[[u32; 3]]) -> Watertightness {
+    let mut ids: HashMap<[u32; 3], usize> = HashMap::new();
+    let pid: Vec<usize> = vertices
+        .iter()
+        .map(|v| {
+            let key = [v.x, v.y, v.z].map(|c| if c == 0.0 { 0 } else { c.to_bits() });
+            let next = ids.len();
+            *ids.entry(key).or_insert(next)
+        })
+        .collect();
+
+    // (uses, uses running from the lower id to the higher)
+    let mut edges: HashMap<(usize, usize), (usize, usize)> = HashMap::new();
+    for t in triangles {
+        for k in 0..3 {
+            let (a, b) = (pid[t[k] as usize], pid[t[(k + 1) % 3] as usize]);
+            if a == b {
+                continue;
+            }
+            let entry = edges.entry((a.min(b), a.max(b))).or_default();
+            entry.0 += 1;
+            entry.1 += usize::from(a < b);
+        }
+    }
+
+    let mut report = Watertightness::default();
+    for &(uses, forward) in edges.values() {
+        match uses {
+            1 => report.boundary_edges += 1,
+            2 if forward != 1 => report.inconsistent_edges += 1,
+            2 => {}
+            _ => report.non_manifold_edges += 1,
+        }
+    }
+    report
+}
@@ -0,0 +1,410 @@
+// USD ASCII (.usda) export.
+//
+// The clump tree becomes a tree of `Xform` prims under a default prim named
+// after the model. A clump with geometry gets a `Mesh` child holding
+// triangles, vertex normals, `primvars:st` and, when present, prelight
+// colours as `primvars:displayColor`. Faces bind their material directly,
+// or through one `GeomSubset` per material when a clump uses several.
+// Geometry goes through the normalizer with the USD settings: metres,
+// V from the bottom, and double-sided materials marked with `doubleSided`.
+//
+// Materials are `UsdPreviewSurface` networks built from `PbrMaterial`, so
+// they match the glTF output. Textures are `UsdUVTexture` shaders reading
+// the image found in `texture_dirs`, taken out next to the layer if zipped
+// and converted to `<name>.png` there if it is not PNG or JPEG (BMP), or
+// `<name>.png` if there is none. A masked texture is merged into one
+// `<texture>_<mask>.png` written next to the layer, whose alpha drives
+// `opacity` with the glTF cutoff as `opacityThreshold`. Bump maps are
+// height maps, which UsdPreviewSurface cannot use, and are left out.
+//
+// Each `ProtoInstance`d prototype is written once, under the abstract
+// `/Prototypes` class, with its own `Materials` scope so its bindings stay
+// inside the referenced prim. Every placement is an instanceable `Xform`
+// referencing it, leaving the geometry shared.
+//
+// The file is plain text so the structure can be checked without the USD
+// runtime.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rwx_normalizer::{NormalizeOptions, NormalizedMesh, TargetFormat};
+use rwx_parser::Face;
+use rwx_scene::{AlphaMode, PbrMaterial, RwxNode, RwxScene};
+
+use crate::{masked_texture, node_mesh, quaternion, unique, web_texture_file};
+
+/// Root of the abstract prototype prims.
+const PROTOTYPES: &str = "/Prototypes";
+
+#[derive(Debug, Clone, Default)]
+pub struct UsdOptions {
+    /// Directories searched for texture files
+    pub texture_dirs: Vec<PathBuf>,
+}
+
+/// Writes `scene` to `path` as a USD ASCII layer.
+pub fn export_usd(scene: &RwxScene, path: &Path, opts: &UsdOptions) -> io::Result<()> {
+    let mut taken = Vec::new();
+    let material_names = scene.materials.iter().map(|m| unique(prim_name(&m.name), &mut taken)).collect();
+
+    let pbr = scene.pbr_materials();
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut textures = Vec::with_capacity(pbr.len());
+    for mat in &pbr {
+        let file = match (&mat.base_color_texture, &mat.alpha_texture) {
+            (Some(name), Some(mask)) => {
+                let (file, data) = masked_texture(name, mask, &opts.texture_dirs)?;
+                if let Some(data) = data {
+                    fs::write(dir.join(&file), data)?;
+                }
+                Some(file)
+            }
+            (Some(name), None) => {
+                let (file, data) = web_texture_file(name, &opts.texture_dirs)?;
+                if let Some(data) = data {
+                    fs::write(dir.join(&file), data)?;
+                }
+                Some(file)
+            }
+            (None, _) => None,
+        };
+        textures.push(file);
+    }
+
+    let mut stage = Stage {
+        scene,
+        normalize: NormalizeOptions::for_format(TargetFormat::Usd),
+        pbr,
+        textures,
+        material_names,
+        prototypes: Vec::new(),
+        out: String::new(),
+    };
+    stage.collect_prototypes(&scene.root);
+
+    let root = format!("/{}", prim_name(&scene.root.name));
+    let _ = writeln!(stage.out, "#usda 1.0");
+    let _ = writeln!(stage.out, "(");
+    let _ = writeln!(stage.out, "    defaultPrim = \"{}\"", &root[1..]);
+    let _ = writeln!(stage.out, "    metersPerUnit = 1");
+    let _ = writeln!(stage.out, "    upAxis = \"Y\"");
+    let _ = writeln!(stage.out, ")\n");
+    stage.node(&scene.root, &root, &format!("{root}/Materials"), false, true);
+
+    if !stage.prototypes.is_empty() {
+        let _ = writeln!(stage.out, "\nclass \"{}\"\n{{", &PROTOTYPES[1..]);
+        for (i, (prim, node)) in stage.prototypes.clone().into_iter().enumerate() {
+            if i > 0 {
+                stage.out.push('\n');
+            }
+            let path = format!("{PROTOTYPES}/{prim}");
+            // the placement carries the transform
+            stage.node(node, &path, &format!("{path}/Materials"), true, false);
+        }
+        let _ = writeln!(stage.out, "}}");
+    }
+
+    fs::write(path, stage.out)
+}
+
+struct Stage<'a> {
+    scene: &'a RwxScene,
+    normalize: NormalizeOptions,
+    pbr: Vec<PbrMaterial>,
+    /// Texture file of each material, the merged one when it has a mask
+    textures: Vec<Option<String>>,
+    /// Prim name of each scene material, in `scene.materials` order
+    material_names: Vec<String>,
+    /// Prim name and first placement of each instanced prototype
+    prototypes: Vec<(String, &'a RwxNode)>,
+    out: String,
+}
+
+impl<'a> Stage<'a> {
+    fn collect_prototypes(&mut self, node: &'a RwxNode) {
+        if let Some(proto) = &node.proto {
+            if !self.prototypes.iter().any(|(_, n)| n.proto.as_ref() == Some(proto)) {
+                let mut taken: Vec<String> = self.prototypes.iter().map(|(p, _)| p.clone()).collect();
+                let prim = unique(prim_name(proto), &mut taken);
+                self.prototypes.push((prim, node));
+            }
+            return;
+        }
+        for child in &node.children {
+            self.collect_prototypes(child);
+        }
+    }
+
+    fn prototype_path(&self, proto: &str) -> String {
+        let (prim, _) = self.prototypes.iter().find(|(_, n)| n.proto.as_deref() == Some(proto)).unwrap();
+        format!("{PROTOTYPES}/{prim}")
+    }
+
+    /// Writes `node` as the Xform at `path`. Inside a prototype, nested
+    /// instances are written out in full rather than referenced.
+    fn node(&mut self, node: &RwxNode, path: &str, materials: &str, in_proto: bool, transform: bool) {
+        let depth = path.matches('/').count() - 1;
+        let pad = "    ".repeat(depth);
+        let name = &path[path.rfind('/').unwrap() + 1..];
+
+        if let (Some(proto), false) = (&node.proto, in_proto) {
+            let target = self.prototype_path(proto);
+            let _ = writeln!(self.out, "{pad}def Xform \"{name}\" (");
+            let _ = writeln!(self.out, "{pad}    instanceable = true");
+            let _ = writeln!(self.out, "{pad}    prepend references = <{target}>");
+            let _ = writeln!(self.out, "{pad})\n{pad}{{");
+            self.transform(node, &pad);
+            let _ = writeln!(self.out, "{pad}}}");
+            return;
+        }
+
+        let _ = writeln!(self.out, "{pad}def Xform \"{name}\"\n{pad}{{");
+        if transform {
+            self.transform(node, &pad);
+        }
+
+        let mut taken = vec!["Mesh".to_string()];
+        if materials == format!("{path}/Materials") {
+            taken.push("Materials".to_string());
+            let mut used = Vec::new();
+            self.used_materials(node, in_proto, &mut used);
+            used.sort_unstable();
+            if !used.is_empty() {
+                self.gap();
+                let _ = writeln!(self.out, "{pad}    def Scope \"Materials\"\n{pad}    {{");
+                for (k, &m) in used.iter().enumerate() {
+                    if k > 0 {
+                        self.out.push('\n');
+                    }
+                    self.material(m, materials, depth + 2);
+                }
+                let _ = writeln!(self.out, "{pad}    }}");
+            }
+        }
+
+        if let Some(mesh) = node_mesh(node, &self.scene.materials, &self.normalize) {
+            self.gap();
+            self.mesh(&mesh, &format!("{path}/Mesh"), materials);
+        }
+
+        for child in &node.children {
+            let child_name = unique(prim_name(&child.name), &mut taken);
+            self.gap();
+            self.node(child, &format!("{path}/{child_name}"), materials, in_proto, true);
+        }
+        let _ = writeln!(self.out, "{pad}}}");
+    }
+
+    /// Blank line before a prim that is not the first thing in its parent.
+    fn gap(&mut self) {
+        if !self.out.ends_with("{\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn transform(&mut self, node: &RwxNode, pad: &str) {
+        let tf = &node.transform;
+        let mut order = Vec::new();
+        if let Some(t) = tf.translate {
+            let t = t.map(|c| c * self.normalize.coordinates.scale);
+            let _ = writeln!(self.out, "{pad}    double3 xformOp:translate = {}", tuple(&t));
+            order.push("\"xformOp:translate\"");
+        }
+        if let Some(r) = tf.rotate {
+            let q = quaternion(r);
+            let _ = writeln!(self.out, "{pad}    quatf xformOp:orient = {}", tuple(&[q[3], q[0], q[1], q[2]]));
+            order.push("\"xformOp:orient\"");
+        }
+        if let Some(s) = tf.scale {
+            let _ = writeln!(self.out, "{pad}    float3 xformOp:scale = {}", tuple(&s));
+            order.push("\"xformOp:scale\"");
+        }
+        if !order.is_empty() {
+            let _ = writeln!(self.out, "{pad}    uniform token[] xformOpOrder = [{}]", order.join(", "));
+        }
+    }
+
+    /// Indices into `scene.materials` used by faces under `node`.
+    fn used_materials(&self, node: &RwxNode, in_proto: bool, used: &mut Vec<usize>) {
+        if node.proto.is_some() && !in_proto {
+            return;
+        }
+        for face in &node.faces {
+            let (Face::Quad(_, mat) | Face::Poly(_, mat)) = face;
+            if let Some(m) = self.scene.materials.iter().position(|x| &x.name == mat) {
+                if !used.contains(&m) {
+                    used.push(m);
+                }
+            }
+        }
+        for child in &node.children {
+            self.used_materials(child, in_proto, used);
+        }
+    }
+
+    fn material(&mut self, index: usize, scope: &str, depth: usize) {
+        let pad = "    ".repeat(depth);
+        let mat = self.pbr[index].clone();
+        let path = format!("{scope}/{}", self.material_names[index]);
+        let color = [mat.base_color[0], mat.base_color[1], mat.base_color[2]];
+        let opacity = mat.base_color[3];
+
+        let _ = writeln!(self.out, "{pad}def Material \"{}\"\n{pad}{{", self.material_names[index]);
+        let _ = writeln!(self.out, "{pad}    token outputs:surface.connect = <{path}/Surface.outputs:surface>\n");
+        let _ = writeln!(self.out, "{pad}    def Shader \"Surface\"\n{pad}    {{");
+        let _ = writeln!(self.out, "{pad}        uniform token info:id = \"UsdPreviewSurface\"");
+        match &mat.base_color_texture {
+            Some(_) => {
+                let _ = writeln!(self.out, "{pad}        color3f inputs:diffuseColor.connect = <{path}/Texture.outputs:rgb>");
+            }
+            None => {
+                let _ = writeln!(self.out, "{pad}        color3f inputs:diffuseColor = {}", tuple(&color));
+            }
+        }
+        let _ = writeln!(self.out, "{pad}        float inputs:metallic = {}", mat.metallic);
+        match &mat.alpha_texture {
+            Some(_) => {
+                let _ = writeln!(self.out, "{pad}        float inputs:opacity.connect = <{path}/Texture.outputs:a>");
+            }
+            None => {
+                let _ = writeln!(self.out, "{pad}        float inputs:opacity = {opacity}");
+            }
+        }
+        if let AlphaMode::Mask { cutoff } = mat.alpha_mode {
+            let _ = writeln!(self.out, "{pad}        float inputs:opacityThreshold = {cutoff}");
+        }
+        let _ = writeln!(self.out, "{pad}        float inputs:roughness = {}", mat.roughness);
+        let _ = writeln!(self.out, "{pad}        token outputs:surface\n{pad}    }}");
+
+        if mat.base_color_texture.is_some() {
+            let _ = writeln!(self.out, "\n{pad}    def Shader \"UV\"\n{pad}    {{");
+            let _ = writeln!(self.out, "{pad}        uniform token info:id = \"UsdPrimvarReader_float2\"");
+            let _ = writeln!(self.out, "{pad}        string inputs:varname = \"st\"");
+            let _ = writeln!(self.out, "{pad}        float2 outputs:result\n{pad}    }}");
+        }
+        if let Some(file) = self.textures[index].clone() {
+            // the lighting factor tints the texture, as in glTF
+            let alpha = mat.alpha_texture.is_some();
+            let scale = [color[0], color[1], color[2], if alpha { opacity } else { 1.0 }];
+            self.texture(&file, scale, alpha, &path, &pad);
+        }
+        let _ = writeln!(self.out, "{pad}}}");
+    }
+
+    fn texture(&mut self, file: &str, scale: [f32; 4], alpha: bool, path: &str, pad: &str) {
+        let _ = writeln!(self.out, "\n{pad}    def Shader \"Texture\"\n{pad}    {{");
+        let _ = writeln!(self.out, "{pad}        uniform token info:id = \"UsdUVTexture\"");
+        let _ = writeln!(self.out, "{pad}        asset inputs:file = @{file}@");
+        if scale != [1.0; 4] {
+            let _ = writeln!(self.out, "{pad}        float4 inputs:scale = {}", tuple(&scale));
+        }
+        let _ = writeln!(self.out, "{pad}        token inputs:sourceColorSpace = \"sRGB\"");
+        let _ = writeln!(self.out, "{pad}        float2 inputs:st.connect = <{path}/UV.outputs:result>");
+        let _ = writeln!(self.out, "{pad}        token inputs:wrapS = \"repeat\"");
+        let _ = writeln!(self.out, "{pad}        token inputs:wrapT = \"repeat\"");
+        if alpha {
+            let _ = writeln!(self.out, "{pad}        float outputs:a");
+        }
+        let _ = writeln!(self.out, "{pad}        float3 outputs:rgb\n{pad}    }}");
+    }
+
+    fn mesh(&mut self, mesh: &NormalizedMesh, path: &str, materials: &str) {
+        let depth = path.matches('/').count() - 1;
+        let pad = "    ".repeat(depth);
+        let verts = &mesh.vertices;
+
+        // per material, in order of first use, with the triangles using it
+        let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+        for (f, face) in mesh.faces.iter().enumerate() {
+            let (Face::Quad(_, mat) | Face::Poly(_, mat)) = face;
+            let Some(m) = self.scene.materials.iter().position(|x| &x.name == mat) else { continue };
+            match groups.iter_mut().find(|(g, _)| *g == m) {
+                Some((_, list)) => list.push(f),
+                None => groups.push((m, vec![f])),
+            }
+        }
+        // doubleSided is per mesh; showing a few extra back faces beats
+        // losing the ones a double-sided material needs
+        let double_sided = groups.iter().any(|(m, _)| self.scene.materials[*m].double_sided);
+
+        let points: Vec<[f32; 3]> = verts.iter().map(|v| [v.x, v.y, v.z]).collect();
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in &points {
+            for k in 0..3 {
+                min[k] = min[k].min(p[k]);
+                max[k] = max[k].max(p[k]);
+            }
+        }
+        let indices: Vec<String> = mesh
+            .faces
+            .iter()
+            .flat_map(|face| {
+                let (Face::Quad(idx, _) | Face::Poly(idx, _)) = face;
+                idx.iter().map(|i| i.to_string()).collect::<Vec<_>>()
+            })
+            .collect();
+        let counts: Vec<String> = mesh.faces.iter().map(|_| "3".to_string()).collect();
+
+        let _ = writeln!(self.out, "{pad}def Mesh \"Mesh\" (\n{pad}    prepend apiSchemas = [\"MaterialBindingAPI\"]\n{pad})\n{pad}{{");
+        let _ = writeln!(self.out, "{pad}    uniform bool doubleSided = {double_sided}");
+        let _ = writeln!(self.out, "{pad}    float3[] extent = [{}, {}]", tuple(&min), tuple(&max));
+        let _ = writeln!(self.out, "{pad}    int[] faceVertexCounts = [{}]", counts.join(", "));
+        let _ = writeln!(self.out, "{pad}    int[] faceVertexIndices = [{}]", indices.join(", "));
+        let normals: Vec<String> = verts.iter().map(|v| tuple(&v.normal.unwrap_or([0.0, 1.0, 0.0]))).collect();
+        let _ = writeln!(self.out, "{pad}    normal3f[] normals = [{}] (", normals.join(", "));
+        let _ = writeln!(self.out, "{pad}        interpolation = \"vertex\"\n{pad}    )");
+        let points: Vec<String> = points.iter().map(|p| tuple(p)).collect();
+        let _ = writeln!(self.out, "{pad}    point3f[] points = [{}]", points.join(", "));
+        if verts.iter().any(|v| v.color.is_some()) {
+            let colors: Vec<String> = verts.iter().map(|v| tuple(&v.color.unwrap_or([1.0; 3]))).collect();
+            let _ = writeln!(self.out, "{pad}    color3f[] primvars:displayColor = [{}] (", colors.join(", "));
+            let _ = writeln!(self.out, "{pad}        interpolation = \"vertex\"\n{pad}    )");
+        }
+        let uvs: Vec<String> = verts.iter().map(|v| tuple(&[v.u, v.v])).collect();
+        let _ = writeln!(self.out, "{pad}    texCoord2f[] primvars:st = [{}] (", uvs.join(", "));
+        let _ = writeln!(self.out, "{pad}        interpolation = \"vertex\"\n{pad}    )");
+        let _ = writeln!(self.out, "{pad}    uniform token subdivisionScheme = \"none\"");
+
+        match &groups[..] {
+            [] => {}
+            [(m, _)] => {
+                let _ = writeln!(self.out, "{pad}    rel material:binding = <{materials}/{}>", self.material_names[*m]);
+            }
+            _ => {
+                let _ = writeln!(self.out, "{pad}    uniform token subsetFamily:materialBind:familyType = \"partition\"");
+                let mut taken = Vec::new();
+                for (m, faces) in &groups {
+                    let name = unique(self.material_names[*m].clone(), &mut taken);
+                    let faces: Vec<String> = faces.iter().map(|f| f.to_string()).collect();
+                    let _ = writeln!(self.out, "\n{pad}    def GeomSubset \"{name}\" (");
+                    let _ = writeln!(self.out, "{pad}        prepend apiSchemas = [\"MaterialBindingAPI\"]\n{pad}    )\n{pad}    {{");
+                    let _ = writeln!(self.out, "{pad}        uniform token elementType = \"face\"");
+                    let _ = writeln!(self.out, "{pad}        uniform token familyName = \"materialBind\"");
+                    let _ = writeln!(self.out, "{pad}        int[] indices = [{}]", faces.join(", "));
+                    let _ = writeln!(self.out, "{pad}        rel material:binding = <{materials}/{}>", self.material_names[*m]);
+                    let _ = writeln!(self.out, "{pad}    }}");
+                }
+            }
+        }
+        let _ = writeln!(self.out, "{pad}}}");
+    }
+}
+
+/// A valid prim name: letters, digits and underscores, not starting with
+/// a digit.
+fn prim_name(name: &str) -> String {
+    let mut out: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if out.is_empty() || out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+fn tuple(values: &[f32]) -> String {
+    let parts: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    format!("({})", parts.join(", "))
+}
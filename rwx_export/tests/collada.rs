@@ -0,0 +1,92 @@
+mod common;
+
+use std::fs;
+
+use common::{out_dir, scene};
+use rwx_export::{export_collada, ColladaOptions};
+
+
+fn export(name: &str) -> String {
+    let path = out_dir(name).join("table.dae");
+    export_collada(&scene(), &path, &ColladaOptions::default()).unwrap();
+    fs::read_to_string(path).unwrap()
+}
+
+fn children<'a>(node: roxmltree::Node<'a, 'a>, tag: &str) -> Vec<roxmltree::Node<'a, 'a>> {
+    node.children().filter(|n| n.has_tag_name(tag)).collect()
+}
+
+#[test]
+fn collada_keeps_clump_hierarchy() {
+    let text = export("collada_nodes");
+    let doc = roxmltree::Document::parse(&text).expect("exported COLLADA should be well-formed XML");
+
+    let scene = doc.descendants().find(|n| n.has_tag_name("visual_scene")).unwrap();
+    let roots = children(scene, "node");
+    assert_eq!(roots.len(), 1);
+    // the model root only groups the top-level clump
+    assert!(children(roots[0], "instance_geometry").is_empty());
+    let clumps = children(roots[0], "node");
+    assert_eq!(clumps.len(), 1);
+    assert_eq!(children(clumps[0], "node").len(), 2);
+
+    let nodes: Vec<_> = doc.descendants().filter(|n| n.has_tag_name("node")).collect();
+    assert_eq!(nodes.len(), 4);
+    for node in nodes {
+        let matrix = children(node, "matrix")[0].text().unwrap();
+        assert_eq!(matrix.split_whitespace().count(), 16);
+    }
+    assert_eq!(doc.descendants().filter(|n| n.has_tag_name("instance_geometry")).count(), 3);
+}
+
+#[test]
+fn collada_references_resolve() {
+    let text = export("collada_refs");
+    let doc = roxmltree::Document::parse(&text).unwrap();
+    let ids: Vec<&str> = doc.descendants().filter_map(|n| n.attribute("id")).collect();
+
+    for url in doc.descendants().filter_map(|n| n.attribute("url").or(n.attribute("target")).or(n.attribute("source"))) {
+        let id = url.strip_prefix('#').expect("references should be local");
+        assert!(ids.contains(&id), "dangling reference {url}");
+    }
+
+    // every <triangles> names a material bound by its instance
+    let symbols: Vec<&str> = doc.descendants().filter_map(|n| n.attribute("symbol")).collect();
+    let triangles: Vec<_> = doc.descendants().filter(|n| n.has_tag_name("triangles")).collect();
+    assert_eq!(triangles.len(), 3);
+    for t in triangles {
+        assert!(symbols.contains(&t.attribute("material").unwrap()));
+        let count: usize = t.attribute("count").unwrap().parse().unwrap();
+        let p = children(t, "p")[0].text().unwrap().split_whitespace().count();
+        assert_eq!(p, count * 3);
+    }
+}
+
+#[test]
+fn collada_effects_follow_surface() {
+    let text = export("collada_effects");
+    let doc = roxmltree::Document::parse(&text).unwrap();
+
+    let images: Vec<&str> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("image"))
+        .map(|n| children(n, "init_from")[0].text().unwrap())
+        .collect();
+    // the mask is merged into the texture's alpha
+    assert_eq!(images, ["leaves_leavesm.png"]);
+
+    let phongs: Vec<_> = doc.descendants().filter(|n| n.has_tag_name("phong")).collect();
+    assert_eq!(phongs.len(), 3);
+
+    // Color 0.5 0.25 0 with Surface 0.3 0.7 0.5
+    let diffuse = children(children(phongs[0], "diffuse")[0], "color")[0].text().unwrap();
+    assert_eq!(diffuse, "0.35 0.175 0 1");
+    let ambient = children(children(phongs[0], "ambient")[0], "color")[0].text().unwrap();
+    assert_eq!(ambient, "0.15 0.075 0 1");
+
+    // the textured material samples the image instead of a colour
+    assert_eq!(children(children(phongs[1], "diffuse")[0], "texture").len(), 1);
+
+    let opacity = children(children(phongs[2], "transparency")[0], "float")[0].text().unwrap();
+    assert_eq!(opacity, "0.5");
+}
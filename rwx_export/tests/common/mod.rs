@@ -0,0 +1,66 @@
+// Fixtures shared by the exporter tests.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rwx_lexer::lex;
+use rwx_parser::parse;
+use rwx_scene::RwxScene;
+
+/// A tagged table top with two child clumps: a masked, double-sided,
+/// walk-through leaf and a half-transparent panel.
+pub const TABLE: &str = "
+ModelBegin
+ClumpBegin
+Tag 100
+Color 0.5 0.25 0
+Surface 0.3 0.7 0.5
+VertexExt 0 0 0 UV 0 0
+VertexExt 1 0 0 UV 1 0
+VertexExt 1 0 1 UV 1 1
+VertexExt 0 0 1 UV 0 1
+VertexExt 0 1 0 UV 0 0
+VertexExt 1 1 0 UV 1 0
+Quad 4 3 2 1
+Quad 1 2 6 5
+ClumpBegin
+Collision off
+Texture leaves Mask leavesm
+MaterialModes double
+VertexExt 0 2 0 UV 0 0
+VertexExt 1 2 0 UV 1 0
+VertexExt 1 3 0 UV 1 1
+Polygon 3 1 2 3
+ClumpEnd
+ClumpBegin
+Opacity 0.5
+VertexExt 2 0 0 UV 0 0
+VertexExt 3 0 0 UV 1 0
+VertexExt 3 1 0 UV 1 1
+VertexExt 2 1 0 UV 0 1
+Quad 1 2 3 4
+ClumpEnd
+ClumpEnd
+ModelEnd
+";
+
+pub fn scene() -> RwxScene {
+    scene_from(TABLE)
+}
+
+pub fn scene_from(source: &str) -> RwxScene {
+    RwxScene::from_model(&parse(&lex(source)).unwrap())
+}
+
+/// A fresh, empty directory for one test, so tests running in parallel (or
+/// left over from an earlier run) never see each other's files.
+pub fn out_dir(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("rwx_export_{name}_{}_{n}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
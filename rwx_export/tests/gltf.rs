@@ -0,0 +1,224 @@
+mod common;
+
+use std::fs;
+
+use common::{out_dir, scene, scene_from};
+use jsonschema::Resource;
+use rwx_export::{export_gltf, GltfOptions};
+use serde_json::Value;
+
+/// Schema errors in a glTF JSON document, checked against the Khronos
+/// glTF 2.0 schema in `tests/gltf_schema`.
+fn schema_errors(doc: &Value) -> Vec<String> {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/gltf_schema");
+    let mut root = None;
+    let mut opts = jsonschema::options();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let schema: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        // the schemas refer to each other by their relative `$id`s
+        let id = schema["$id"].as_str().unwrap().to_string();
+        if id == "glTF.schema.json" {
+            root = Some(schema.clone());
+        }
+        opts = opts.with_resource(format!("json-schema:///{id}"), Resource::from_contents(schema));
+    }
+    let validator = opts.build(&root.unwrap()).unwrap();
+    validator.iter_errors(doc).map(|e| format!("{}: {e}", e.instance_path())).collect()
+}
+
+/// The JSON chunk of a GLB file.
+fn glb_json(data: &[u8]) -> Value {
+    let len = u32::from_le_bytes(data[12..16].try_into().unwrap()) as usize;
+    assert_eq!(&data[16..20], b"JSON");
+    serde_json::from_slice(&data[20..20 + len]).unwrap()
+}
+
+#[test]
+fn gltf_passes_validation() {
+    let path = out_dir("gltf").join("table.gltf");
+    export_gltf(&scene(), &path, &GltfOptions::default()).unwrap();
+
+    let json: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    let errors = schema_errors(&json);
+    assert!(errors.is_empty(), "{errors:#?}");
+    // the referenced schemas are applied too
+    let mut broken = json.clone();
+    broken["accessors"][0].as_object_mut().unwrap().remove("count");
+    broken["nodes"][0]["rotation"] = serde_json::json!([0.0, 2.0, 0.0, 1.0]);
+    assert_eq!(schema_errors(&broken).len(), 2);
+
+    let doc = gltf::Gltf::open(&path).expect("exported glTF should load");
+    assert_eq!(doc.nodes().count(), 4);
+    assert_eq!(doc.meshes().count(), 3);
+    assert!(path.with_extension("bin").is_file());
+
+    let modes: Vec<_> = doc.materials().map(|m| m.alpha_mode()).collect();
+    assert!(modes.contains(&gltf::material::AlphaMode::Opaque));
+    assert!(modes.contains(&gltf::material::AlphaMode::Mask));
+    assert!(modes.contains(&gltf::material::AlphaMode::Blend));
+    assert!(doc.materials().any(|m| m.double_sided()));
+
+    for prim in doc.meshes().flat_map(|m| m.primitives().collect::<Vec<_>>()) {
+        assert!(prim.get(&gltf::Semantic::Positions).is_some());
+        assert!(prim.get(&gltf::Semantic::Normals).is_some());
+        assert!(prim.get(&gltf::Semantic::TexCoords(0)).is_some());
+        assert!(prim.get(&gltf::Semantic::Tangents).is_some());
+    }
+}
+
+#[test]
+fn glb_passes_validation_and_reads_back() {
+    let path = out_dir("glb").join("table.glb");
+    let opts = GltfOptions { binary: true, ..Default::default() };
+    export_gltf(&scene(), &path, &opts).unwrap();
+
+    let data = fs::read(&path).unwrap();
+    let errors = schema_errors(&glb_json(&data));
+    assert!(errors.is_empty(), "{errors:#?}");
+
+    let doc = gltf::Gltf::from_slice(&data).expect("exported GLB should load");
+    let blob = doc.blob.as_deref().expect("GLB should carry a BIN chunk");
+
+    let mesh = doc.meshes().next().unwrap();
+    let prim = mesh.primitives().next().unwrap();
+    let reader = prim.reader(|_| Some(blob));
+    let positions: Vec<[f32; 3]> = reader.read_positions().unwrap().collect();
+    let indices: Vec<u32> = reader.read_indices().unwrap().into_u32().collect();
+
+    // two quads in metres
+    assert_eq!(indices.len(), 12);
+    assert!(positions.iter().all(|p| p.iter().all(|&c| c == 0.0 || c == 10.0)));
+}
+
+#[test]
+fn masks_are_merged_into_texture_alpha() {
+    let dir = out_dir("gltf_mask");
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    image::RgbImage::from_pixel(2, 2, image::Rgb([200, 100, 0])).save(textures.join("leaves.bmp")).unwrap();
+    image::GrayImage::from_fn(2, 2, |x, _| image::Luma([if x == 0 { 255 } else { 0 }])).save(textures.join("leavesm.bmp")).unwrap();
+
+    let path = dir.join("table.gltf");
+    let opts = GltfOptions { texture_dirs: vec![textures], ..Default::default() };
+    export_gltf(&scene(), &path, &opts).unwrap();
+
+    let doc = gltf::Gltf::open(&path).unwrap();
+    let uris: Vec<_> = doc
+        .images()
+        .filter_map(|i| match i.source() {
+            gltf::image::Source::Uri { uri, .. } => Some(uri.to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(uris, ["leaves_leavesm.png"]);
+
+    let merged = image::open(dir.join("leaves_leavesm.png")).unwrap().to_rgba8();
+    assert_eq!(merged.get_pixel(0, 0).0, [200, 100, 0, 255]);
+    assert_eq!(merged.get_pixel(1, 1).0, [200, 100, 0, 0]);
+}
+
+#[test]
+fn zipped_textures_are_read() {
+    let dir = out_dir("gltf_zip");
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    let image = |img: image::DynamicImage| {
+        let mut data = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut data, image::ImageFormat::Bmp).unwrap();
+        data.into_inner()
+    };
+    // as object paths serve them: one archive each, the entry named in another case
+    for (archive, entry, data) in [
+        ("Leaves.zip", "LEAVES.BMP", image(image::RgbImage::from_pixel(1, 1, image::Rgb([1, 2, 3])).into())),
+        ("leavesm.zip", "LeavesM.bmp", image(image::GrayImage::from_pixel(1, 1, image::Luma([255])).into())),
+    ] {
+        let mut zip = zip::ZipWriter::new(fs::File::create(textures.join(archive)).unwrap());
+        zip.start_file(entry, zip::write::SimpleFileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut zip, &data).unwrap();
+        zip.finish().unwrap();
+    }
+
+    let path = dir.join("table.gltf");
+    let opts = GltfOptions { texture_dirs: vec![textures], ..Default::default() };
+    export_gltf(&scene(), &path, &opts).unwrap();
+
+    let merged = image::open(dir.join("leaves_leavesm.png")).unwrap().to_rgba8();
+    assert_eq!(merged.get_pixel(0, 0).0, [1, 2, 3, 255]);
+}
+
+#[test]
+fn lod_levels_use_msft_lod() {
+    let path = out_dir("lod").join("table.gltf");
+    let opts = GltfOptions { lod_levels: vec![0.5], ..Default::default() };
+    export_gltf(&scene(), &path, &opts).unwrap();
+
+    let doc = gltf::Gltf::open(&path).expect("exported glTF should validate");
+    assert!(doc.extensions_used().any(|e| e == "MSFT_lod"));
+
+    let json: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    let lod_nodes = json["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|n| n["extensions"]["MSFT_lod"].is_object())
+        .count();
+    assert_eq!(lod_nodes, 3);
+}
+
+#[test]
+fn bump_maps_become_normal_maps() {
+    let dir = out_dir("gltf_bump");
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    // rising to the right
+    image::GrayImage::from_fn(4, 4, |x, _| image::Luma([x as u8 * 60])).save(textures.join("woodb.bmp")).unwrap();
+
+    let rwx = "ModelBegin\nClumpBegin\nTexture wood Bump woodb\nVertexExt 0 0 0 UV 0 0\nVertexExt 1 0 0 UV 1 0\n\
+               VertexExt 1 1 0 UV 1 1\nVertexExt 0 1 0 UV 0 1\nQuad 1 2 3 4\nClumpEnd\nModelEnd\n";
+    let path = dir.join("wood.gltf");
+    let opts = GltfOptions { texture_dirs: vec![textures], ..Default::default() };
+    export_gltf(&scene_from(rwx), &path, &opts).unwrap();
+
+    let doc = gltf::Gltf::open(&path).unwrap();
+    let normal = doc.materials().next().unwrap().normal_texture().expect("bump map should give a normal texture");
+    match normal.texture().source().source() {
+        gltf::image::Source::Uri { uri, .. } => assert_eq!(uri, "woodb_normal.png"),
+        _ => panic!("normal map should be copied next to the output"),
+    }
+    let [r, g, b, _] = image::open(dir.join("woodb_normal.png")).unwrap().to_rgba8().get_pixel(1, 1).0;
+    assert!(r < 128 && g == 128 && b > 128);
+}
+
+#[test]
+fn bmp_textures_are_converted_to_png() {
+    let dir = out_dir("gltf_bmp");
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    image::RgbImage::from_pixel(2, 2, image::Rgb([200, 100, 0])).save(textures.join("wood.bmp")).unwrap();
+    let rwx = "ModelBegin\nClumpBegin\nTexture wood\nVertexExt 0 0 0 UV 0 0\nVertexExt 1 0 0 UV 1 0\n\
+               VertexExt 1 1 0 UV 1 1\nPolygon 3 1 2 3\nClumpEnd\nModelEnd\n";
+
+    let path = dir.join("wood.gltf");
+    let opts = GltfOptions { texture_dirs: vec![textures.clone()], ..Default::default() };
+    export_gltf(&scene_from(rwx), &path, &opts).unwrap();
+    let doc = gltf::Gltf::open(&path).unwrap();
+    match doc.images().next().unwrap().source() {
+        gltf::image::Source::Uri { uri, .. } => assert_eq!(uri, "wood.png"),
+        _ => panic!("texture should be copied next to the output"),
+    }
+    let copied = fs::read(dir.join("wood.png")).unwrap();
+    assert_eq!(image::guess_format(&copied).unwrap(), image::ImageFormat::Png);
+
+    let path = dir.join("wood.glb");
+    let opts = GltfOptions { texture_dirs: vec![textures], binary: true, embed_textures: true, ..Default::default() };
+    export_gltf(&scene_from(rwx), &path, &opts).unwrap();
+    let doc = gltf::Gltf::open(&path).unwrap();
+    match doc.images().next().unwrap().source() {
+        gltf::image::Source::View { mime_type, .. } => assert_eq!(mime_type, "image/png"),
+        _ => panic!("texture should be embedded"),
+    }
+}
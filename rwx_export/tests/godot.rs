@@ -0,0 +1,154 @@
+mod common;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use common::{out_dir, scene};
+use rwx_export::{export_godot, GodotOptions};
+
+
+fn export(name: &str) -> (PathBuf, String) {
+    let dir = out_dir(name);
+    let path = dir.join("table.tscn");
+    export_godot(&scene(), &path, &GodotOptions::default()).unwrap();
+    let text = fs::read_to_string(&path).unwrap();
+    (dir, text)
+}
+
+/// `[...]` section headers, in order.
+fn headers(text: &str) -> Vec<&str> {
+    text.lines().filter(|l| l.starts_with('[') && l.ends_with(']') && !l.starts_with("[{")).collect()
+}
+
+fn attribute<'a>(header: &'a str, key: &str) -> Option<&'a str> {
+    let start = header.find(&format!(" {key}=\""))? + key.len() + 3;
+    Some(&header[start..start + header[start..].find('"')?])
+}
+
+fn byte_array(text: &str, key: &str) -> Vec<u8> {
+    let start = text.find(&format!("\"{key}\": PackedByteArray(")).unwrap() + key.len() + 20;
+    let end = start + text[start..].find(')').unwrap();
+    text[start..end].split(", ").filter(|s| !s.is_empty()).map(|b| b.parse().unwrap()).collect()
+}
+
+fn number(text: &str, key: &str) -> u64 {
+    let start = text.find(&format!("\"{key}\": ")).unwrap() + key.len() + 4;
+    text[start..start + text[start..].find(',').unwrap()].parse().unwrap()
+}
+
+fn octahedron_decode(x: f32, y: f32) -> [f32; 3] {
+    let (x, y) = (x * 2.0 - 1.0, y * 2.0 - 1.0);
+    let z = 1.0 - x.abs() - y.abs();
+    let t = (-z).max(0.0);
+    let n = [x + if x >= 0.0 { -t } else { t }, y + if y >= 0.0 { -t } else { t }, z];
+    let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+    n.map(|c| c / len)
+}
+
+#[test]
+fn godot_references_resolve() {
+    let (dir, text) = export("godot_refs");
+    let headers = headers(&text);
+    assert!(headers[0].starts_with("[gd_scene load_steps="));
+
+    let ext: Vec<&str> = headers.iter().filter(|h| h.starts_with("[ext_resource")).map(|h| attribute(h, "id").unwrap()).collect();
+    let sub: Vec<&str> = headers.iter().filter(|h| h.starts_with("[sub_resource")).map(|h| attribute(h, "id").unwrap()).collect();
+    let steps: usize = headers[0].split("load_steps=").nth(1).unwrap().split(' ').next().unwrap().parse().unwrap();
+    assert_eq!(steps, ext.len() + sub.len() + 1);
+
+    for (kind, ids) in [("ExtResource(\"", &ext), ("SubResource(\"", &sub)] {
+        for part in text.split(kind).skip(1) {
+            let id = &part[..part.find('"').unwrap()];
+            assert!(ids.contains(&id), "missing resource {id}");
+        }
+    }
+    for h in headers.iter().filter(|h| h.starts_with("[ext_resource")) {
+        let file = attribute(h, "path").unwrap().strip_prefix("res://").unwrap();
+        assert!(dir.join(file).is_file(), "{file} not written");
+    }
+
+    // every parent is a node declared before it
+    let mut nodes: HashSet<String> = HashSet::new();
+    for (i, h) in headers.iter().filter(|h| h.starts_with("[node")).enumerate() {
+        let name = attribute(h, "name").unwrap();
+        match attribute(h, "parent") {
+            None => assert_eq!(i, 0, "only the root has no parent"),
+            Some(".") => {
+                nodes.insert(name.to_string());
+            }
+            Some(parent) => {
+                assert!(nodes.contains(parent), "unknown parent {parent}");
+                nodes.insert(format!("{parent}/{name}"));
+            }
+        }
+    }
+}
+
+#[test]
+fn godot_scene_mirrors_clumps() {
+    let (_, text) = export("godot_nodes");
+    let nodes: Vec<&str> = headers(&text).into_iter().filter(|h| h.starts_with("[node")).collect();
+
+    assert_eq!(nodes.iter().filter(|h| h.contains("type=\"MeshInstance3D\"")).count(), 3);
+    // the leaves clump has collision off
+    assert_eq!(nodes.iter().filter(|h| h.contains("type=\"StaticBody3D\"")).count(), 2);
+    assert_eq!(nodes.iter().filter(|h| h.contains("type=\"CollisionShape3D\"")).count(), 2);
+    assert!(text.contains("metadata/rwx_tag = 100"));
+    assert!(nodes.contains(&"[node name=\"clump_2\" type=\"MeshInstance3D\" parent=\"clump_1\"]"));
+}
+
+#[test]
+fn godot_surfaces_are_well_formed() {
+    let (_, text) = export("godot_surfaces");
+    let surfaces: Vec<&str> = text.split("{\n\"aabb\"").skip(1).collect();
+    assert_eq!(surfaces.len(), 3);
+
+    for s in surfaces {
+        let format = number(s, "format");
+        assert_eq!(format, (1 << 35) | 4096 | 16 | 4 | 2 | 1);
+        let vertices = number(s, "vertex_count") as usize;
+        let indices = number(s, "index_count") as usize;
+        let vertex_data = byte_array(s, "vertex_data");
+        assert_eq!(vertex_data.len(), vertices * 20);
+        assert_eq!(byte_array(s, "attribute_data").len(), vertices * 8);
+        let index_data = byte_array(s, "index_data");
+        assert_eq!(index_data.len(), indices * 2);
+        assert!(indices.is_multiple_of(3));
+
+        // front faces are clockwise: the right-handed triangle normal points
+        // away from the stored vertex normal
+        let index = |k: usize| u16::from_le_bytes([index_data[k * 2], index_data[k * 2 + 1]]) as usize;
+        let position = |i: usize| -> [f32; 3] {
+            let f = |k: usize| f32::from_le_bytes(vertex_data[i * 12 + k * 4..i * 12 + k * 4 + 4].try_into().unwrap());
+            [f(0), f(1), f(2)]
+        };
+        let normal = |i: usize| -> [f32; 3] {
+            let at = vertices * 12 + i * 8;
+            let u = |k: usize| u16::from_le_bytes([vertex_data[at + k * 2], vertex_data[at + k * 2 + 1]]) as f32 / 65535.0;
+            octahedron_decode(u(0), u(1))
+        };
+        let (a, b, c) = (position(index(0)), position(index(1)), position(index(2)));
+        let e1 = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+        let e2 = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+        let cross = [e1[1] * e2[2] - e1[2] * e2[1], e1[2] * e2[0] - e1[0] * e2[2], e1[0] * e2[1] - e1[1] * e2[0]];
+        let n = normal(index(0));
+        assert!(cross[0] * n[0] + cross[1] * n[1] + cross[2] * n[2] < 0.0);
+    }
+}
+
+#[test]
+fn godot_materials_follow_surface() {
+    let (dir, _) = export("godot_materials");
+    let leaves = fs::read_to_string(dir.join("table_leaves.tres")).unwrap();
+    assert!(leaves.starts_with("[gd_resource type=\"StandardMaterial3D\""));
+    // the mask is merged into the texture's alpha
+    assert!(leaves.contains("path=\"res://leaves_leavesm.png\""));
+    assert!(leaves.contains("transparency = 2"));
+    assert!(leaves.contains("cull_mode = 2"));
+
+    let glass = fs::read_to_string(dir.join("table_NULL_2.tres")).unwrap();
+    assert!(glass.contains("transparency = 1"));
+    // the colour is inherited from the parent clump
+    assert!(glass.contains("albedo_color = Color(0.5, 0.25, 0, 0.5)"));
+}
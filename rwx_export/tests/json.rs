@@ -0,0 +1,87 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::out_dir;
+use rwx_export::{read_scene_json, scene_schema, write_scene_json, SceneDocument, SCENE_FORMAT_VERSION};
+use rwx_lexer::lex;
+use rwx_parser::parse;
+use rwx_scene::RwxScene;
+
+const SIGN: &str = "
+ModelBegin
+ClumpBegin
+Color 0.2 0.4 0.6
+VertexExt 0 0 0 UV 0 1
+VertexExt 1 0 0 UV 1 1
+VertexExt 1 0.5 0 UV 1 0
+VertexExt 0 0.5 0 UV 0 0
+Quad 1 2 3 4
+ClumpBegin
+Tag 100
+Collision off
+Texture sign Mask signm
+MaterialModes double
+VertexExt 0.1 0.1 0.01 UV 0 1
+VertexExt 0.9 0.1 0.01 UV 1 1
+VertexExt 0.9 0.4 0.01 UV 1 0
+Polygon 3 1 2 3
+ClumpEnd
+ClumpEnd
+ModelEnd
+";
+
+fn scene() -> RwxScene {
+    RwxScene::from_model(&parse(&lex(SIGN)).unwrap())
+}
+
+fn out_path(name: &str) -> PathBuf {
+    out_dir("json").join(name)
+}
+
+#[test]
+fn scene_round_trips() {
+    let path = out_path("sign.json");
+    let scene = scene();
+    write_scene_json(&scene, Some("sign.rwx"), &path).unwrap();
+    let back = read_scene_json(&path).unwrap();
+
+    assert_eq!(serde_json::to_value(&scene).unwrap(), serde_json::to_value(&back).unwrap());
+    let sign = &back.root.children[0].children[0];
+    assert_eq!(sign.tag, Some(100));
+    assert!(!sign.collision);
+}
+
+#[test]
+fn document_matches_schema() {
+    let path = out_path("sign_schema.json");
+    write_scene_json(&scene(), None, &path).unwrap();
+    let doc: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+
+    let validator = jsonschema::validator_for(&scene_schema()).unwrap();
+    let errors: Vec<String> = validator.iter_errors(&doc).map(|e| e.to_string()).collect();
+    assert!(errors.is_empty(), "{errors:?}");
+}
+
+#[test]
+fn checked_in_schema_is_current() {
+    let file = concat!(env!("CARGO_MANIFEST_DIR"), "/schema/rwx_scene.schema.json");
+    let checked_in: serde_json::Value = serde_json::from_slice(&fs::read(file).unwrap()).unwrap();
+    assert_eq!(
+        checked_in,
+        scene_schema(),
+        "regenerate with `rwx_tool to-json --schema rwx_export/schema/rwx_scene.schema.json`"
+    );
+}
+
+#[test]
+fn newer_versions_are_rejected() {
+    let path = out_path("future.json");
+    let mut doc = SceneDocument::new(&scene(), None);
+    doc.version = SCENE_FORMAT_VERSION + 1;
+    fs::write(&path, serde_json::to_string(&doc).unwrap()).unwrap();
+
+    let err = read_scene_json(&path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
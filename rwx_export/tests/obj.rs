@@ -0,0 +1,157 @@
+// Golden-file tests for the OBJ exporter.
+//
+// `data/couch2c.rwx` is the couch sample (`couch2c_unity.obj` at the
+// repository root) written back out as RWX: its vertices, UVs, faces and
+// textures. Set `UPDATE_GOLDEN=1` to rewrite the expected files after an
+// intended change to the output.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::out_dir;
+use rwx_export::{export_mesh_obj, export_obj, ObjOptions};
+use rwx_lexer::lex;
+use rwx_normalizer::{BatchOptions, NormalizedMesh};
+use rwx_parser::{parse, Face, Material, Vertex};
+use rwx_scene::RwxScene;
+
+fn data(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+fn couch() -> RwxScene {
+    let text = fs::read_to_string(data("data/couch2c.rwx")).unwrap();
+    RwxScene::from_model(&parse(&lex(&text)).unwrap())
+}
+
+fn export_couch(dir: &str) -> PathBuf {
+    let path = out_dir(dir).join("couch2c.obj");
+    export_obj(&couch(), &path, &ObjOptions::default()).unwrap();
+    path
+}
+
+fn check_golden(actual: &Path, golden: &str) {
+    let actual = fs::read_to_string(actual).unwrap();
+    let golden = data(golden);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &actual).unwrap();
+    }
+    let expected = fs::read_to_string(&golden).unwrap();
+    assert!(actual == expected, "{} differs; rerun with UPDATE_GOLDEN=1 if intended", golden.display());
+}
+
+#[test]
+fn couch_matches_golden_files() {
+    let path = export_couch("obj_golden");
+    check_golden(&path, "golden/couch2c.obj");
+    check_golden(&path.with_extension("mtl"), "golden/couch2c.mtl");
+}
+
+#[test]
+fn couch_output_is_consistent() {
+    let path = export_couch("obj_consistent");
+    let obj = fs::read_to_string(&path).unwrap();
+    let mtl = fs::read_to_string(path.with_extension("mtl")).unwrap();
+
+    assert_eq!(obj.lines().next(), Some("mtllib couch2c.mtl"));
+
+    let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+    let (v, vt, vn) = (count("v "), count("vt "), count("vn "));
+    assert_eq!(v, vt);
+    assert_eq!(v, vn);
+
+    // every face is a triangle with in-range v/vt/vn indices
+    for face in obj.lines().filter(|l| l.starts_with("f ")) {
+        let corners: Vec<&str> = face.split_whitespace().skip(1).collect();
+        assert_eq!(corners.len(), 3, "{face}");
+        for c in corners {
+            let idx: Vec<usize> = c.split('/').map(|i| i.parse().unwrap()).collect();
+            assert!(idx.iter().all(|&i| i >= 1 && i <= v), "{face}");
+        }
+    }
+
+    // every material used is defined
+    let defined: Vec<&str> = mtl.lines().filter_map(|l| l.strip_prefix("newmtl ")).collect();
+    for used in obj.lines().filter_map(|l| l.strip_prefix("usemtl ")) {
+        assert!(defined.contains(&used), "{used} is not in the MTL");
+    }
+    assert!(mtl.contains("map_Kd couch2a.png"));
+}
+
+#[test]
+fn batched_couch_draws_each_material_once() {
+    let plain = fs::read_to_string(export_couch("obj_unbatched")).unwrap();
+    let dir = out_dir("obj_batched");
+    let path = dir.join("couch2c.obj");
+    let opts = ObjOptions { batch: Some(BatchOptions::default()), ..Default::default() };
+    export_obj(&couch(), &path, &opts).unwrap();
+    let batched = fs::read_to_string(&path).unwrap();
+
+    let faces = |obj: &str| obj.lines().filter(|l| l.starts_with("f ")).count();
+    assert_eq!(faces(&batched), faces(&plain));
+    let mut used: Vec<&str> = batched.lines().filter_map(|l| l.strip_prefix("usemtl ")).collect();
+    let runs = used.len();
+    used.sort();
+    used.dedup();
+    assert_eq!(runs, used.len(), "a material is drawn in more than one run");
+    assert_eq!(runs, 10);
+}
+
+#[test]
+fn textures_with_spaces_are_copied_under_the_escaped_name() {
+    let dir = out_dir("obj_spaces");
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    fs::write(textures.join("old wood.jpg"), b"jpg").unwrap();
+
+    let corner = |x: f32, z: f32| Vertex { x, y: 0.0, z, u: x, v: z, normal: None, tangent: None, color: None };
+    let material = Material { name: "old wood".to_string(), texture: Some("old wood".to_string()), ..Default::default() };
+    let mesh = NormalizedMesh {
+        vertices: vec![corner(0.0, 0.0), corner(0.0, 1.0), corner(1.0, 0.0)],
+        faces: vec![Face::Poly(vec![0, 1, 2], material.name.clone())],
+        materials: vec![material],
+        report: Default::default(),
+    };
+    let path = dir.join("plank.obj");
+    export_mesh_obj(&mesh, &path, &ObjOptions { texture_dirs: vec![textures], ..Default::default() }).unwrap();
+
+    let mtl = fs::read_to_string(path.with_extension("mtl")).unwrap();
+    assert!(mtl.contains("newmtl old_wood\n"));
+    assert!(mtl.contains("map_Kd old_wood.jpg\n"));
+    assert_eq!(fs::read(dir.join("old_wood.jpg")).unwrap(), b"jpg");
+}
+
+#[test]
+fn escaped_names_never_clash() {
+    let dir = out_dir("obj_clash");
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    fs::write(textures.join("old wood.jpg"), b"spaced").unwrap();
+    fs::write(textures.join("old_wood.jpg"), b"plain").unwrap();
+
+    let corner = |x: f32, z: f32| Vertex { x, y: 0.0, z, u: x, v: z, normal: None, tangent: None, color: None };
+    let materials: Vec<Material> = ["old wood", "old_wood"]
+        .map(|n| Material { name: n.to_string(), texture: Some(n.to_string()), ..Default::default() })
+        .to_vec();
+    let mesh = NormalizedMesh {
+        vertices: vec![corner(0.0, 0.0), corner(0.0, 1.0), corner(1.0, 0.0), corner(1.0, 1.0)],
+        faces: vec![Face::Poly(vec![0, 1, 2], "old wood".to_string()), Face::Poly(vec![2, 1, 3], "old_wood".to_string())],
+        materials,
+        report: Default::default(),
+    };
+    let path = dir.join("planks.obj");
+    export_mesh_obj(&mesh, &path, &ObjOptions { texture_dirs: vec![textures], ..Default::default() }).unwrap();
+
+    // the name that needed no escaping keeps its spelling
+    let mtl = fs::read_to_string(path.with_extension("mtl")).unwrap();
+    assert!(mtl.contains("newmtl old_wood_2\nKa 0 0 0\nKd 1 1 1\nKs 0 0 0\nd 1\nillum 2\nmap_Kd old_wood_2.jpg\n"), "{mtl}");
+    assert!(mtl.contains("newmtl old_wood\nKa 0 0 0\nKd 1 1 1\nKs 0 0 0\nd 1\nillum 2\nmap_Kd old_wood.jpg\n"), "{mtl}");
+    assert_eq!(fs::read(dir.join("old_wood_2.jpg")).unwrap(), b"spaced");
+    assert!(!dir.join("old_wood.jpg").exists());
+
+    let obj = fs::read_to_string(&path).unwrap();
+    let used: Vec<&str> = obj.lines().filter_map(|l| l.strip_prefix("usemtl ")).collect();
+    assert_eq!(used, ["old_wood_2", "old_wood"]);
+}
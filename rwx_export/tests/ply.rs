@@ -0,0 +1,64 @@
+mod common;
+
+use std::fs;
+
+use common::out_dir;
+use rwx_export::{export_ply, PlyOptions};
+use rwx_lexer::lex;
+use rwx_parser::parse;
+use rwx_scene::RwxScene;
+
+const LIT: &str = "
+ModelBegin
+ClumpBegin
+Vertex 0 0 0 UV 0 0 Prelight 1 0 0
+Vertex 1 0 0 UV 1 0 Prelight 0 1 0
+Vertex 1 1 0 UV 1 1 Prelight 0 0 1
+Vertex 0 1 0 UV 0 1 Prelight 1 1 1
+Quad 1 2 3 4
+ClumpEnd
+ModelEnd
+";
+
+fn export(name: &str, binary: bool) -> Vec<u8> {
+    let path = out_dir("ply").join(name);
+    let scene = RwxScene::from_model(&parse(&lex(LIT)).unwrap());
+    export_ply(&scene, &path, &PlyOptions { binary }).unwrap();
+    fs::read(path).unwrap()
+}
+
+fn split_header(data: &[u8]) -> (String, &[u8]) {
+    let end = data.windows(11).position(|w| w == b"end_header\n").unwrap() + 11;
+    (String::from_utf8(data[..end].to_vec()).unwrap(), &data[end..])
+}
+
+#[test]
+fn prelight_becomes_vertex_colours() {
+    let model = parse(&lex(LIT)).unwrap();
+    assert_eq!(model.vertices[1].color, Some([0.0, 1.0, 0.0]));
+
+    let data = export("lit.ply", false);
+    let (header, body) = split_header(&data);
+    assert!(header.starts_with("ply\nformat ascii 1.0\n"));
+    assert!(header.contains("element vertex 4\n"));
+    assert!(header.contains("property uchar red\n"));
+    assert!(header.contains("element face 2\n"));
+
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 6);
+    // x y z nx ny nz s t r g b
+    let first: Vec<&str> = lines[0].split(' ').collect();
+    assert_eq!(first.len(), 11);
+    assert_eq!(&first[8..], ["255", "0", "0"]);
+    assert!(lines[4..].iter().all(|l| l.starts_with("3 ")));
+}
+
+#[test]
+fn binary_ply_matches_header() {
+    let data = export("lit.bin.ply", true);
+    let (header, body) = split_header(&data);
+    assert!(header.contains("format binary_little_endian 1.0\n"));
+    // 8 floats and 3 colour bytes per vertex, count byte and 3 uints per face
+    assert_eq!(body.len(), 4 * (8 * 4 + 3) + 2 * (1 + 3 * 4));
+}
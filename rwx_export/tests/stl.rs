@@ -0,0 +1,108 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::out_dir;
+use rwx_export::{export_stl, StlOptions};
+use rwx_lexer::lex;
+use rwx_parser::parse;
+use rwx_scene::RwxScene;
+
+// two clumps; the cube is closed, the sign in front of it is not
+const CUBE: &str = "
+ModelBegin
+ClumpBegin
+Vertex 0 0 0 UV 0 0
+Vertex 1 0 0 UV 1 0
+Vertex 1 1 0 UV 1 1
+Vertex 0 1 0 UV 0 1
+Vertex 0 0 1 UV 0 0
+Vertex 1 0 1 UV 1 0
+Vertex 1 1 1 UV 1 1
+Vertex 0 1 1 UV 0 1
+Quad 1 4 3 2
+Quad 5 6 7 8
+Quad 1 2 6 5
+Quad 2 3 7 6
+Quad 3 4 8 7
+Quad 4 1 5 8
+ClumpEnd
+ModelEnd
+";
+
+const SIGN: &str = "
+ModelBegin
+ClumpBegin
+Vertex 0 0 2 UV 0 0
+Vertex 1 0 2 UV 1 0
+Vertex 1 1 2 UV 1 1
+Vertex 0 1 2 UV 0 1
+Quad 1 2 3 4
+ClumpEnd
+ModelEnd
+";
+
+fn out(name: &str) -> PathBuf {
+    out_dir("stl").join(name)
+}
+
+fn scene(text: &str) -> RwxScene {
+    RwxScene::from_model(&parse(&lex(text)).unwrap())
+}
+
+#[test]
+fn binary_stl_has_outward_facets() {
+    let path = out("cube.stl");
+    let report = export_stl(&scene(CUBE), &path, &StlOptions::default()).unwrap();
+    assert!(report.is_watertight(), "{report}");
+
+    let data = fs::read(&path).unwrap();
+    assert!(!data.starts_with(b"solid"));
+    let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+    assert_eq!(count, 12);
+    assert_eq!(data.len(), 84 + count * 50);
+
+    // the cube spans 0..10 m on every axis
+    let float = |at: usize| f32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    for f in 0..count {
+        let at = 84 + f * 50;
+        let n = [float(at), float(at + 4), float(at + 8)];
+        let mut centroid = [0.0; 3];
+        for v in 0..3 {
+            for (k, c) in centroid.iter_mut().enumerate() {
+                *c += float(at + 12 + v * 12 + k * 4) / 3.0;
+            }
+        }
+        let out: f32 = (0..3).map(|k| n[k] * (centroid[k] - [5.0, -5.0, 5.0][k])).sum();
+        assert!(out > 0.0, "facet {f} points inward");
+    }
+}
+
+#[test]
+fn ascii_stl_is_scaled() {
+    let path = out("cube.ascii.stl");
+    let opts = StlOptions { binary: false, scale: 1000.0 };
+    export_stl(&scene(CUBE), &path, &opts).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("solid model\n"));
+    assert!(text.trim_end().ends_with("endsolid model"));
+    assert_eq!(text.matches("facet normal").count(), 12);
+
+    let max = text
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("vertex "))
+        .flat_map(|l| l.split(' ').map(|c| c.parse::<f32>().unwrap().abs()).collect::<Vec<_>>())
+        .fold(0.0f32, f32::max);
+    assert_eq!(max, 10_000.0);
+}
+
+#[test]
+fn open_mesh_is_reported() {
+    let report = export_stl(&scene(SIGN), &out("sign.stl"), &StlOptions::default()).unwrap();
+    assert!(!report.is_watertight());
+    assert_eq!(report.boundary_edges, 4);
+    assert_eq!(report.non_manifold_edges, 0);
+    assert_eq!(report.inconsistent_edges, 0);
+}
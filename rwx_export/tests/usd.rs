@@ -0,0 +1,142 @@
+mod common;
+
+use std::collections::HashSet;
+use std::fs;
+
+use common::{out_dir, scene_from};
+use rwx_export::{export_usd, UsdOptions};
+use rwx_lexer::lex;
+use rwx_parser::parse;
+use rwx_scene::RwxScene;
+
+const ROOM: &str = "
+ProtoBegin chair
+Color 0.4 0.2 0
+VertexExt 0 0 0 UV 0 0
+VertexExt 1 0 0 UV 1 0
+VertexExt 1 1 0 UV 1 1
+VertexExt 0 1 0 UV 0 1
+Quad 1 2 3 4
+ProtoEnd
+ModelBegin
+ClumpBegin
+Texture leaves Mask leavesm
+VertexExt 0 2 0 UV 0 0
+VertexExt 1 2 0 UV 1 0
+VertexExt 1 3 0 UV 1 1
+Polygon 3 1 2 3
+Texture NULL
+Opacity 0.5
+VertexExt 2 0 0 UV 0 0
+VertexExt 3 0 0 UV 1 0
+VertexExt 3 1 0 UV 1 1
+Polygon 3 4 5 6
+ProtoInstance chair
+ProtoInstance chair
+ClumpEnd
+ModelEnd
+";
+
+fn export(name: &str) -> String {
+    let path = out_dir(name).join("room.usda");
+    let scene = RwxScene::from_model(&parse(&lex(ROOM)).unwrap());
+    export_usd(&scene, &path, &UsdOptions::default()).unwrap();
+    fs::read_to_string(path).unwrap()
+}
+
+/// Path of every prim, worked out from the four-space indentation.
+fn prim_paths(text: &str) -> HashSet<String> {
+    let mut stack: Vec<String> = Vec::new();
+    let mut paths = HashSet::new();
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if !(trimmed.starts_with("def ") || trimmed.starts_with("class ")) {
+            continue;
+        }
+        let depth = (line.len() - trimmed.len()) / 4;
+        let name = trimmed.split('"').nth(1).unwrap();
+        stack.truncate(depth);
+        stack.push(name.to_string());
+        paths.insert(format!("/{}", stack.join("/")));
+    }
+    paths
+}
+
+#[test]
+fn prototypes_are_parsed_and_placed() {
+    let model = parse(&lex(ROOM)).unwrap();
+    assert_eq!(model.prototypes.len(), 1);
+
+    // both placements are in the tree and in the merged lists, the
+    // definition itself is not
+    let clump = &model.root.children[0];
+    let instances: Vec<_> = clump.children.iter().filter(|c| c.proto.as_deref() == Some("chair")).collect();
+    assert_eq!(instances.len(), 2);
+    assert_eq!(model.faces.len(), 4);
+    assert_eq!(model.vertices.len(), 14);
+}
+
+#[test]
+fn usd_structure_is_consistent() {
+    let text = export("usd_structure");
+    assert!(text.starts_with("#usda 1.0\n"));
+    assert_eq!(text.matches('{').count(), text.matches('}').count());
+    assert_eq!(text.matches('(').count(), text.matches(')').count());
+
+    // every relationship and connection points at an existing prim
+    let paths = prim_paths(&text);
+    for target in text.split('<').skip(1).map(|s| &s[..s.find('>').unwrap()]) {
+        let prim = target.split('.').next().unwrap();
+        assert!(paths.contains(prim), "dangling target {target}");
+    }
+
+    for line in text.lines().map(str::trim) {
+        if let Some(counts) = line.strip_prefix("int[] faceVertexCounts = ") {
+            assert!(counts.trim_matches(['[', ']']).split(", ").all(|c| c == "3"));
+        }
+    }
+}
+
+#[test]
+fn prototypes_are_instanced() {
+    let text = export("usd_instances");
+    assert_eq!(text.matches("class \"Prototypes\"").count(), 1);
+    assert_eq!(text.matches("instanceable = true").count(), 2);
+    assert_eq!(text.matches("prepend references = </Prototypes/chair>").count(), 2);
+    // the chair mesh is written once, inside the prototype
+    assert_eq!(text.matches("def Mesh").count(), 2);
+
+    let paths = prim_paths(&text);
+    assert!(paths.contains("/Prototypes/chair/Materials/NULL"));
+    assert!(paths.contains("/model/clump_1/chair"));
+    assert!(paths.contains("/model/clump_1/chair_2"));
+}
+
+#[test]
+fn usd_materials_use_preview_surface() {
+    let text = export("usd_materials");
+    // the mask is merged into the texture's alpha
+    assert!(text.contains("asset inputs:file = @leaves_leavesm.png@"));
+    assert!(text.contains("float inputs:opacity.connect = <"));
+    assert!(text.contains("float inputs:opacityThreshold = 0.5"));
+    assert!(text.contains("float inputs:opacity = 0.5"));
+    // two materials on one clump are bound through subsets
+    assert_eq!(text.matches("def GeomSubset").count(), 2);
+}
+
+#[test]
+fn bmp_textures_are_written_as_png() {
+    let dir = out_dir("usd_bmp");
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    image::RgbImage::from_pixel(2, 2, image::Rgb([200, 100, 0])).save(textures.join("wood.bmp")).unwrap();
+    let rwx = "ModelBegin\nClumpBegin\nTexture wood\nVertexExt 0 0 0 UV 0 0\nVertexExt 1 0 0 UV 1 0\n\
+               VertexExt 1 1 0 UV 1 1\nPolygon 3 1 2 3\nClumpEnd\nModelEnd\n";
+
+    let path = dir.join("wood.usda");
+    let opts = UsdOptions { texture_dirs: vec![textures] };
+    export_usd(&scene_from(rwx), &path, &opts).unwrap();
+    assert!(fs::read_to_string(&path).unwrap().contains("asset inputs:file = @wood.png@"));
+    let written = fs::read(dir.join("wood.png")).unwrap();
+    assert_eq!(image::guess_format(&written).unwrap(), image::ImageFormat::Png);
+}
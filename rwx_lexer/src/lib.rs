@@ -1,5 +1,3 @@
-use std::str::FromStr;
-
 #[derive(Debug, Clone)]
 pub enum Token {
     VertexExt,
@@ -18,9 +16,7 @@ pub fn lex(input: &str) -> Vec<Token> {
         let line = line.trim();
         if line.is_empty() { continue; }
 
-        let mut parts = line.split_whitespace();
-
-        while let Some(word) = parts.next() {
+        for word in line.split_whitespace() {
             match word {
                 "VertexExt" => tokens.push(Token::VertexExt),
                 "Vertex"    => tokens.push(Token::Vertex),
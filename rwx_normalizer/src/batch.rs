@@ -0,0 +1,120 @@
+// Draw-call batching across clumps.
+//
+// Every clump normalizes to its own mesh, so an object made of twenty clumps
+// sharing one texture costs twenty draw calls. Batching merges the clumps
+// into one mesh and sorts its triangles by material, giving one submesh (one
+// draw call) per material.
+//
+// Clumps with a tag usually need to stay addressable on their own (signs,
+// pictures), so they can be kept out of the merge. Clumps with collision
+// switched off (walk-through foliage) are never merged with solid ones:
+// one collision flag could not describe the merged mesh.
+
+use rwx_parser::Face;
+use rwx_semantics::FlatMesh;
+
+use crate::{normalize_with, NormalizeOptions, NormalizeReport, NormalizedMesh};
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Keep each tagged clump in a batch of its own
+    pub split_tagged: bool,
+}
+
+/// Range of `Batch::mesh.faces` drawn with one material.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submesh {
+    pub material: String,
+    pub first_face: usize,
+    pub face_count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Batch {
+    /// Name of the first clump merged into this batch
+    pub name: String,
+    pub tag: Option<u32>,
+    /// Whether every clump in the batch takes part in collision; clumps
+    /// that differ are never batched together
+    pub collision: bool,
+    /// Faces sorted so each material is one contiguous run
+    pub mesh: NormalizedMesh,
+    pub submeshes: Vec<Submesh>,
+    /// Normalize reports of the merged clumps
+    pub reports: Vec<NormalizeReport>,
+}
+
+/// Normalizes every clump and merges them into as few batches as `opts`
+/// allows: one for the untagged clumps with collision and one for those
+/// without, plus one per tagged clump if they are split.
+pub fn batch(clumps: &[FlatMesh], normalize_opts: &NormalizeOptions, opts: &BatchOptions) -> Vec<Batch> {
+    let mut batches: Vec<Batch> = Vec::new();
+
+    for clump in clumps {
+        let mesh = normalize_with(clump, normalize_opts);
+        let separate = opts.split_tagged && clump.tag.is_some();
+
+        let target = if separate {
+            None
+        } else {
+            batches.iter_mut().find(|b| b.tag.is_none() && b.collision == clump.collision)
+        };
+
+        match target {
+            Some(b) => {
+                append(&mut b.mesh, &mesh);
+                b.reports.push(mesh.report);
+            }
+            None => batches.push(Batch {
+                name: clump.name.clone(),
+                tag: if separate { clump.tag } else { None },
+                collision: clump.collision,
+                reports: vec![mesh.report.clone()],
+                mesh,
+                submeshes: Vec::new(),
+            }),
+        }
+    }
+
+    for b in &mut batches {
+        b.submeshes = sort_by_material(&mut b.mesh);
+    }
+    batches
+}
+
+fn append(dst: &mut NormalizedMesh, src: &NormalizedMesh) {
+    let offset = dst.vertices.len() as u32;
+    dst.vertices.extend(src.vertices.iter().cloned());
+    dst.faces.extend(src.faces.iter().map(|f| match f {
+        Face::Quad(idx, mat) => Face::Quad(idx.iter().map(|i| i + offset).collect(), mat.clone()),
+        Face::Poly(idx, mat) => Face::Poly(idx.iter().map(|i| i + offset).collect(), mat.clone()),
+    }));
+
+    // clumps of one model share its material list, so this rarely adds anything
+    for m in &src.materials {
+        if !dst.materials.iter().any(|d| d.name == m.name) {
+            dst.materials.push(m.clone());
+        }
+    }
+}
+
+/// Stable sort of the faces by material, in order of first use.
+fn sort_by_material(mesh: &mut NormalizedMesh) -> Vec<Submesh> {
+    let mut groups: Vec<(String, Vec<Face>)> = Vec::new();
+    for face in mesh.faces.drain(..) {
+        let mat = match &face {
+            Face::Quad(_, mat) | Face::Poly(_, mat) => mat.clone(),
+        };
+        match groups.iter_mut().find(|(m, _)| *m == mat) {
+            Some((_, faces)) => faces.push(face),
+            None => groups.push((mat, vec![face])),
+        }
+    }
+
+    let mut submeshes = Vec::with_capacity(groups.len());
+    for (material, faces) in groups {
+        submeshes.push(Submesh { material, first_face: mesh.faces.len(), face_count: faces.len() });
+        mesh.faces.extend(faces);
+    }
+    submeshes
+}
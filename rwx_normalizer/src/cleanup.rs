@@ -0,0 +1,165 @@
+// Degenerate and duplicate triangle removal.
+//
+// Runs on welded triangles, before normals are generated, so positions in
+// the report are in RWX coordinates and can be matched against the source.
+//
+// Back-to-back pairs (the same corners wound in opposite directions) are the
+// usual way to model a two-sided surface with single-sided materials, so
+// they are only removed when one of the pair is already double-sided; then
+// the pair z-fights and the later face is redundant.
+
+use std::fmt;
+
+use hashbrown::HashMap;
+use rwx_parser::Face;
+
+use crate::math::{cross, length, sub};
+use crate::optimize::OptimizeReport;
+use crate::repair::RepairReport;
+use crate::NormalizedMesh;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    RepeatedVertex,
+    ZeroArea,
+    Duplicate,
+    BackToBack,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemovedFace {
+    /// 0-based index of the source face in `FlatMesh::faces`
+    pub source_face: usize,
+    pub reason: RemovalReason,
+    pub positions: [[f32; 3]; 3],
+}
+
+/// What the normalizer changed in one mesh.
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeReport {
+    /// Clump name from `FlatMesh::name`
+    pub clump: String,
+    /// 0-based source faces skipped for having fewer than three corners or
+    /// a vertex index outside the mesh
+    pub invalid_faces: Vec<usize>,
+    pub removed_faces: Vec<RemovedFace>,
+    pub repair: RepairReport,
+    /// Set when `NormalizeOptions::optimize` reordered the triangles
+    pub optimize: Option<OptimizeReport>,
+}
+
+impl NormalizeReport {
+    pub fn is_empty(&self) -> bool {
+        self.invalid_faces.is_empty() && self.removed_faces.is_empty() && self.repair.is_empty()
+    }
+
+    pub fn count(&self, reason: RemovalReason) -> usize {
+        self.removed_faces.iter().filter(|r| r.reason == reason).count()
+    }
+}
+
+impl fmt::Display for NormalizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} removed ({} repeated vertex, {} zero area, {} duplicate, {} back-to-back)",
+            self.clump,
+            self.removed_faces.len(),
+            self.count(RemovalReason::RepeatedVertex),
+            self.count(RemovalReason::ZeroArea),
+            self.count(RemovalReason::Duplicate),
+            self.count(RemovalReason::BackToBack),
+        )?;
+        for &face in &self.invalid_faces {
+            writeln!(f, "  face {} skipped: invalid vertex indices", face + 1)?;
+        }
+        for r in &self.removed_faces {
+            let p = r.positions;
+            writeln!(
+                f,
+                "  face {} {:?}: ({} {} {}) ({} {} {}) ({} {} {})",
+                r.source_face + 1,
+                r.reason,
+                p[0][0], p[0][1], p[0][2],
+                p[1][0], p[1][1], p[1][2],
+                p[2][0], p[2][1], p[2][2],
+            )?;
+        }
+        write!(f, "{}", self.repair)?;
+        if let Some(optimize) = &self.optimize {
+            writeln!(f, "  {optimize}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Removes bad triangles from `mesh`; `source_faces` runs parallel to
+/// `mesh.faces` and is filtered the same way.
+pub(crate) fn cleanup(mesh: &mut NormalizedMesh, source_faces: &mut Vec<usize>, min_area: f32) -> Vec<RemovedFace> {
+    let double_sided: Vec<&str> = mesh
+        .materials
+        .iter()
+        .filter(|m| m.double_sided)
+        .map(|m| m.name.as_str())
+        .collect();
+
+    let mut removed = Vec::new();
+    let mut keep = vec![true; mesh.faces.len()];
+    // canonical corner positions -> (face, winding), for faces kept so far
+    let mut seen: HashMap<[[u32; 3]; 3], usize> = HashMap::new();
+
+    for (fi, face) in mesh.faces.iter().enumerate() {
+        let (idx, mat) = match face {
+            Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat),
+        };
+        let positions = [0, 1, 2].map(|k| {
+            let v = &mesh.vertices[idx[k] as usize];
+            [v.x, v.y, v.z]
+        });
+
+        let reason = if idx[0] == idx[1] || idx[1] == idx[2] || idx[0] == idx[2] {
+            Some(RemovalReason::RepeatedVertex)
+        } else if length(cross(sub(positions[1], positions[0]), sub(positions[2], positions[0]))) * 0.5 <= min_area {
+            Some(RemovalReason::ZeroArea)
+        } else {
+            let keys = positions.map(|p| p.map(|c| if c == 0.0 { 0 } else { c.to_bits() }));
+            let forward = canonical(keys);
+            let backward = canonical([keys[0], keys[2], keys[1]]);
+
+            if seen.contains_key(&forward) {
+                Some(RemovalReason::Duplicate)
+            } else if let Some(&other) = seen.get(&backward) {
+                let other_mat = match &mesh.faces[other] {
+                    Face::Quad(_, m) | Face::Poly(_, m) => m,
+                };
+                if double_sided.contains(&mat.as_str()) || double_sided.contains(&other_mat.as_str()) {
+                    Some(RemovalReason::BackToBack)
+                } else {
+                    seen.insert(forward, fi);
+                    None
+                }
+            } else {
+                seen.insert(forward, fi);
+                None
+            }
+        };
+
+        if let Some(reason) = reason {
+            keep[fi] = false;
+            removed.push(RemovedFace { source_face: source_faces[fi], reason, positions });
+        }
+    }
+
+    let mut k = keep.iter();
+    mesh.faces.retain(|_| *k.next().unwrap());
+    let mut k = keep.iter();
+    source_faces.retain(|_| *k.next().unwrap());
+
+    removed
+}
+
+/// Rotates the corners so the smallest comes first, keeping the winding.
+fn canonical(keys: [[u32; 3]; 3]) -> [[u32; 3]; 3] {
+    let first = (0..3).min_by_key(|&k| keys[k]).unwrap();
+    [keys[first], keys[(first + 1) % 3], keys[(first + 2) % 3]]
+}
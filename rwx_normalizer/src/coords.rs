@@ -0,0 +1,152 @@
+// Coordinate-system and unit conversion.
+//
+// RWX is right-handed and Y-up, one unit is ten metres, and texture V runs
+// from the top of the image. Each profile maps that onto a target's axes,
+// units and UV origin; a profile that mirrors the axes also reverses
+// triangle winding so front faces stay in front.
+
+use rwx_parser::{Face, Vertex};
+
+use crate::NormalizedMesh;
+
+/// Metres per RWX unit.
+pub const RWX_UNIT_METRES: f32 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordinateProfile {
+    pub name: &'static str,
+    /// Output axis `i` is `sign * input[axis]`, given as `(axis, sign)`
+    pub axes: [(usize, f32); 3],
+    /// Uniform scale applied to positions
+    pub scale: f32,
+    /// Replace `v` with `1 - v` for targets whose V runs from the bottom
+    pub flip_v: bool,
+}
+
+impl CoordinateProfile {
+    /// RWX as authored.
+    pub fn rwx() -> Self {
+        CoordinateProfile { name: "rwx", axes: [(0, 1.0), (1, 1.0), (2, 1.0)], scale: 1.0, flip_v: false }
+    }
+
+    /// Right-handed Y-up metres, V from the top.
+    pub fn gltf() -> Self {
+        CoordinateProfile { name: "gltf", scale: RWX_UNIT_METRES, ..Self::rwx() }
+    }
+
+    /// Right-handed Y-up metres, V from the bottom.
+    pub fn obj() -> Self {
+        CoordinateProfile { name: "obj", scale: RWX_UNIT_METRES, flip_v: true, ..Self::rwx() }
+    }
+
+    /// Left-handed Y-up metres, V from the bottom; X is mirrored as Unity's
+    /// own importers do.
+    pub fn unity() -> Self {
+        CoordinateProfile {
+            name: "unity",
+            axes: [(0, -1.0), (1, 1.0), (2, 1.0)],
+            scale: RWX_UNIT_METRES,
+            flip_v: true,
+        }
+    }
+
+    /// Right-handed Y-up metres, V from the top.
+    pub fn godot() -> Self {
+        CoordinateProfile { name: "godot", ..Self::gltf() }
+    }
+
+    /// Right-handed Y-up metres, V from the bottom.
+    pub fn collada() -> Self {
+        CoordinateProfile { name: "collada", ..Self::obj() }
+    }
+
+    /// Right-handed Y-up metres, V from the bottom.
+    pub fn usd() -> Self {
+        CoordinateProfile { name: "usd", ..Self::obj() }
+    }
+
+    /// Right-handed Z-up metres, as printing and scanning tools expect.
+    pub fn z_up() -> Self {
+        CoordinateProfile {
+            name: "z_up",
+            axes: [(0, 1.0), (2, -1.0), (1, 1.0)],
+            scale: RWX_UNIT_METRES,
+            flip_v: true,
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Self> {
+        [
+            Self::rwx(),
+            Self::gltf(),
+            Self::obj(),
+            Self::unity(),
+            Self::godot(),
+            Self::collada(),
+            Self::usd(),
+            Self::z_up(),
+        ]
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+
+    /// True when the axis mapping is a mirror, which reverses winding.
+    pub fn flips_handedness(&self) -> bool {
+        let mut m = [[0.0f32; 3]; 3];
+        for (row, &(axis, sign)) in self.axes.iter().enumerate() {
+            m[row][axis] = sign;
+        }
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        det < 0.0
+    }
+
+    fn map_axes(&self, p: [f32; 3]) -> [f32; 3] {
+        self.axes.map(|(axis, sign)| p[axis] * sign)
+    }
+
+    pub fn apply(&self, v: &Vertex) -> Vertex {
+        let p = self.map_axes([v.x, v.y, v.z]);
+
+        // a mirror or a V flip each reverse the bitangent
+        let mut w_sign = if self.flips_handedness() { -1.0 } else { 1.0 };
+        if self.flip_v {
+            w_sign = -w_sign;
+        }
+
+        Vertex {
+            x: p[0] * self.scale,
+            y: p[1] * self.scale,
+            z: p[2] * self.scale,
+            u: v.u,
+            v: if self.flip_v { 1.0 - v.v } else { v.v },
+            normal: v.normal.map(|n| self.map_axes(n)),
+            tangent: v.tangent.map(|t| {
+                let d = self.map_axes([t[0], t[1], t[2]]);
+                [d[0], d[1], d[2], t[3] * w_sign]
+            }),
+            color: v.color,
+        }
+    }
+
+    /// Converts every vertex and fixes the winding if the mapping mirrors.
+    pub fn apply_mesh(&self, mesh: &mut NormalizedMesh) {
+        for v in &mut mesh.vertices {
+            *v = self.apply(v);
+        }
+        if self.flips_handedness() {
+            for face in &mut mesh.faces {
+                match face {
+                    Face::Quad(idx, _) | Face::Poly(idx, _) => idx.reverse(),
+                }
+            }
+        }
+    }
+}
+
+impl Default for CoordinateProfile {
+    fn default() -> Self {
+        Self::rwx()
+    }
+}
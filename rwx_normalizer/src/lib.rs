@@ -1,79 +1,421 @@
 use hashbrown::HashMap;
-use rwx_parser::{Vertex, Face};
+use rwx_parser::{Vertex, Face, Material};
 use rwx_semantics::FlatMesh;
 
+mod batch;
+mod cleanup;
+mod coords;
+mod math;
+mod normals;
+mod optimize;
+mod repair;
+mod simplify;
+mod tangents;
+mod triangulate;
+mod weld;
+
+pub use batch::{batch, Batch, BatchOptions, Submesh};
+pub use cleanup::{NormalizeReport, RemovalReason, RemovedFace};
+pub use coords::{CoordinateProfile, RWX_UNIT_METRES};
+pub use optimize::{acmr, optimize, OptimizeOptions, OptimizeReport};
+pub use repair::{Hole, RepairReport};
+pub use simplify::{generate_lods, simplify, LodLevel};
+pub use triangulate::triangulate;
+
+use weld::Welder;
+
+/// Triangle mesh; every face is a `Face::Poly` of three 0-based indices
+/// into `vertices`.
 #[derive(Debug, Clone)]
 pub struct NormalizedMesh {
     pub vertices: Vec<Vertex>,
     pub faces: Vec<Face>,
+    /// Materials as the target should apply them; `double_sided` is cleared
+    /// where the back faces were duplicated into the geometry
+    pub materials: Vec<Material>,
+    pub report: NormalizeReport,
 }
 
-pub fn normalize(flat: &FlatMesh) -> NormalizedMesh {
-    let mut out_vertices = Vec::new();
-    let mut remap = HashMap::new();
-
-    for (i, v) in flat.vertices.iter().enumerate() {
-        let key = (
-            (v.x * 10000.0) as i32,
-            (v.y * 10000.0) as i32,
-            (v.z * 10000.0) as i32,
-            (v.u * 10000.0) as i32,
-            (v.v * 10000.0) as i32,
-        );
+/// How `MaterialModes double` reaches the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoubleSidedMode {
+    /// Keep the geometry single-sided and leave `double_sided` set on the
+    /// material, for formats that can disable culling per material
+    Mark,
+    /// Add a reversed copy of every double-sided triangle with flipped
+    /// normals, for formats that always cull back faces
+    Duplicate,
+}
 
-        if let Some(&new_index) = remap.get(&key) {
-            remap.insert(key, new_index);
-        } else {
-            let new_index = out_vertices.len();
-            out_vertices.push(v.clone());
-            remap.insert(key, new_index);
+/// Output formats, each with the normalizer settings it needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetFormat {
+    Obj,
+    Gltf,
+    Unity,
+    Godot,
+    Collada,
+    Usd,
+    Stl,
+    Ply,
+}
+
+impl TargetFormat {
+    pub fn double_sided_mode(self) -> DoubleSidedMode {
+        match self {
+            // glTF `doubleSided`, Godot `cull_disabled`, USD `doubleSided`
+            TargetFormat::Gltf | TargetFormat::Godot | TargetFormat::Usd => DoubleSidedMode::Mark,
+            // back faces would make printed and scanned shells non-manifold
+            TargetFormat::Stl | TargetFormat::Ply => DoubleSidedMode::Mark,
+            TargetFormat::Obj | TargetFormat::Unity | TargetFormat::Collada => DoubleSidedMode::Duplicate,
         }
     }
 
-    let mut out_faces = Vec::new();
+    /// Formats drawn by game engines, where triangle order decides how well
+    /// the vertex cache is used.
+    pub fn is_realtime(self) -> bool {
+        matches!(self, TargetFormat::Gltf | TargetFormat::Unity | TargetFormat::Godot)
+    }
 
-    for face in &flat.faces {
-        match face {
-            Face::Quad(idx, mat) => {
-                let a = remap_vertex(idx[0], &flat.vertices, &remap);
-                let b = remap_vertex(idx[1], &flat.vertices, &remap);
-                let c = remap_vertex(idx[2], &flat.vertices, &remap);
-                let d = remap_vertex(idx[3], &flat.vertices, &remap);
-
-                out_faces.push(Face::Poly(vec![a, b, c], mat.clone()));
-                out_faces.push(Face::Poly(vec![a, c, d], mat.clone()));
-            }
+    pub fn coordinate_profile(self) -> CoordinateProfile {
+        match self {
+            TargetFormat::Obj => CoordinateProfile::obj(),
+            TargetFormat::Gltf => CoordinateProfile::gltf(),
+            TargetFormat::Unity => CoordinateProfile::unity(),
+            TargetFormat::Godot => CoordinateProfile::godot(),
+            TargetFormat::Collada => CoordinateProfile::collada(),
+            TargetFormat::Usd => CoordinateProfile::usd(),
+            TargetFormat::Stl | TargetFormat::Ply => CoordinateProfile::z_up(),
+        }
+    }
+}
 
-            Face::Poly(indices, mat) => {
-                if indices.len() < 3 {
-                    continue;
-                }
-                for t in 1..(indices.len() - 1) {
-                    let a = remap_vertex(indices[0], &flat.vertices, &remap);
-                    let b = remap_vertex(indices[t], &flat.vertices, &remap);
-                    let c = remap_vertex(indices[t + 1], &flat.vertices, &remap);
-                    out_faces.push(Face::Poly(vec![a, b, c], mat.clone()));
+/// How face normals are weighted when averaged into a vertex normal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// By triangle area; large faces dominate
+    Area,
+    /// By the angle of the triangle at the vertex; independent of tessellation
+    Angle,
+}
+
+#[derive(Debug, Clone)]
+pub struct NormalizeOptions {
+    /// Largest per-axis distance at which two positions are welded
+    pub position_epsilon: f32,
+    pub uv_epsilon: f32,
+    /// Per-component tolerance for normals; vertices with and without a
+    /// normal are never welded
+    pub normal_epsilon: f32,
+    /// Per-component tolerance for prelight colours
+    pub color_epsilon: f32,
+    /// Weld vertices shared by faces of different materials
+    pub weld_across_materials: bool,
+    /// Drop vertices that no face references
+    pub drop_unreferenced: bool,
+    /// Emit vertices in source order; otherwise in order of first use by a
+    /// face
+    pub keep_order: bool,
+    /// Remove degenerate, duplicate and redundant back-to-back triangles
+    pub cleanup: bool,
+    /// Triangles with a smaller area count as zero-area
+    pub min_area: f32,
+    /// Wind each connected piece consistently, outward where it is closed,
+    /// and look for small holes
+    pub repair: bool,
+    /// Close the holes `repair` finds
+    pub fill_holes: bool,
+    /// Largest boundary loop, in edges, that counts as a hole
+    pub max_hole_edges: usize,
+    /// Replace vertex normals with generated smooth/faceted ones
+    pub generate_normals: bool,
+    /// Faces meeting at more than this angle (degrees) get a hard edge
+    pub crease_angle: f32,
+    pub normal_weighting: NormalWeighting,
+    /// Generate MikkTSpace tangents for meshes with UVs and normals
+    pub generate_tangents: bool,
+    pub double_sided: DoubleSidedMode,
+    /// Axes, units and UV origin of the output
+    pub coordinates: CoordinateProfile,
+    /// Reorder triangles for the vertex cache as the last step
+    pub optimize: Option<OptimizeOptions>,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        NormalizeOptions {
+            position_epsilon: 1e-4,
+            uv_epsilon: 1e-4,
+            normal_epsilon: 1e-3,
+            color_epsilon: 1.0 / 255.0,
+            weld_across_materials: true,
+            drop_unreferenced: true,
+            keep_order: true,
+            cleanup: true,
+            min_area: 1e-12,
+            repair: true,
+            fill_holes: false,
+            max_hole_edges: 8,
+            generate_normals: true,
+            crease_angle: 60.0,
+            normal_weighting: NormalWeighting::Angle,
+            generate_tangents: true,
+            double_sided: DoubleSidedMode::Mark,
+            coordinates: CoordinateProfile::rwx(),
+            optimize: None,
+        }
+    }
+}
+
+impl NormalizeOptions {
+    /// Default options adjusted for what `format` supports.
+    pub fn for_format(format: TargetFormat) -> Self {
+        NormalizeOptions {
+            double_sided: format.double_sided_mode(),
+            coordinates: format.coordinate_profile(),
+            optimize: format.is_realtime().then(OptimizeOptions::default),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn normalize(flat: &FlatMesh) -> NormalizedMesh {
+    normalize_with(flat, &NormalizeOptions::default())
+}
+
+pub fn normalize_with(flat: &FlatMesh, opts: &NormalizeOptions) -> NormalizedMesh {
+    // triangulate first, keeping source indices and a weld group per corner
+    let mut groups: HashMap<&str, u32> = HashMap::new();
+    let mut triangles: Vec<([usize; 3], u32, &String, usize)> = Vec::new();
+    let mut invalid_faces = Vec::new();
+
+    for (fi, face) in flat.faces.iter().enumerate() {
+        let (indices, mat) = match face {
+            Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat),
+        };
+        if indices.len() < 3 || indices.iter().any(|&i| i == 0 || i as usize > flat.vertices.len()) {
+            invalid_faces.push(fi);
+            continue;
+        }
+
+        let group = if opts.weld_across_materials {
+            0
+        } else {
+            let next = groups.len() as u32;
+            *groups.entry(mat.as_str()).or_insert(next)
+        };
+
+        let corners: Vec<usize> = indices.iter().map(|&i| i as usize - 1).collect();
+        let points: Vec<[f32; 3]> = corners
+            .iter()
+            .map(|&i| [flat.vertices[i].x, flat.vertices[i].y, flat.vertices[i].z])
+            .collect();
+
+        for [a, b, c] in triangulate(&points) {
+            triangles.push(([corners[a], corners[b], corners[c]], group, mat, fi));
+        }
+    }
+
+    // every (source vertex, group) pair that needs an output vertex, in output order
+    let mut slots: Vec<(usize, u32)> = Vec::new();
+    if opts.keep_order {
+        let mut used: Vec<Vec<u32>> = vec![Vec::new(); flat.vertices.len()];
+        for (tri, group, _, _) in &triangles {
+            for &i in tri {
+                if !used[i].contains(group) {
+                    used[i].push(*group);
                 }
             }
         }
+        for (i, groups) in used.iter_mut().enumerate() {
+            if groups.is_empty() && !opts.drop_unreferenced {
+                groups.push(0);
+            }
+            groups.sort_unstable();
+            slots.extend(groups.iter().map(|&g| (i, g)));
+        }
+    } else {
+        let mut referenced = vec![false; flat.vertices.len()];
+        for (tri, group, _, _) in &triangles {
+            for &i in tri {
+                referenced[i] = true;
+                slots.push((i, *group));
+            }
+        }
+        if !opts.drop_unreferenced {
+            slots.extend((0..flat.vertices.len()).filter(|&i| !referenced[i]).map(|i| (i, 0)));
+        }
+    }
+
+    let mut welder = Welder::new(opts);
+    let mut remap: HashMap<(usize, u32), u32> = HashMap::new();
+    for (i, group) in slots {
+        if !remap.contains_key(&(i, group)) {
+            let out = welder.insert(&flat.vertices[i], group);
+            remap.insert((i, group), out);
+        }
     }
 
-    NormalizedMesh {
-        vertices: out_vertices,
+    let mut source_faces: Vec<usize> = triangles.iter().map(|t| t.3).collect();
+    let out_faces = triangles
+        .into_iter()
+        .map(|(tri, group, mat, _)| {
+            Face::Poly(tri.iter().map(|&i| remap[&(i, group)]).collect(), mat.clone())
+        })
+        .collect();
+
+    let mut mesh = NormalizedMesh {
+        vertices: welder.vertices,
         faces: out_faces,
+        materials: flat.materials.clone(),
+        report: NormalizeReport { clump: flat.name.clone(), invalid_faces, ..Default::default() },
+    };
+
+    if opts.cleanup {
+        mesh.report.removed_faces = cleanup::cleanup(&mut mesh, &mut source_faces, opts.min_area);
+        if opts.drop_unreferenced {
+            drop_unreferenced(&mut mesh);
+        }
+    }
+
+    if opts.repair {
+        mesh.report.repair = repair::repair(&mut mesh, &mut source_faces, opts.fill_holes, opts.max_hole_edges);
+    }
+
+    if opts.generate_normals {
+        normals::generate(
+            &mut mesh,
+            &flat.materials,
+            opts.crease_angle,
+            opts.normal_weighting,
+            opts.normal_epsilon,
+        );
+    }
+
+    if opts.generate_tangents {
+        tangents::generate(&mut mesh);
+    }
+
+    if opts.double_sided == DoubleSidedMode::Duplicate {
+        duplicate_back_faces(&mut mesh);
+    }
+
+    opts.coordinates.apply_mesh(&mut mesh);
+
+    if let Some(optimize_opts) = &opts.optimize {
+        mesh.report.optimize = Some(optimize(&mut mesh, optimize_opts));
+    }
+
+    mesh
+}
+
+/// Appends a reversed copy of every triangle whose material is double-sided,
+/// using vertex copies with flipped normals, and clears the material flag.
+pub fn duplicate_back_faces(mesh: &mut NormalizedMesh) {
+    let double: Vec<&str> = mesh
+        .materials
+        .iter()
+        .filter(|m| m.double_sided)
+        .map(|m| m.name.as_str())
+        .collect();
+    if double.is_empty() {
+        return;
+    }
+
+    let mut flipped: HashMap<u32, u32> = HashMap::new();
+    let mut back_faces = Vec::new();
+    for face in &mesh.faces {
+        let (idx, mat) = match face {
+            Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat),
+        };
+        if !double.contains(&mat.as_str()) {
+            continue;
+        }
+
+        let back: Vec<u32> = [idx[0], idx[2], idx[1]]
+            .iter()
+            .map(|&i| {
+                *flipped.entry(i).or_insert_with(|| {
+                    let mut v = mesh.vertices[i as usize].clone();
+                    v.normal = v.normal.map(|n| [-n[0], -n[1], -n[2]]);
+                    // keep the bitangent pointing the same way under the flipped normal
+                    v.tangent = v.tangent.map(|t| [t[0], t[1], t[2], -t[3]]);
+                    mesh.vertices.push(v);
+                    (mesh.vertices.len() - 1) as u32
+                })
+            })
+            .collect();
+        back_faces.push(Face::Poly(back, mat.clone()));
+    }
+
+    mesh.faces.extend(back_faces);
+    for m in &mut mesh.materials {
+        m.double_sided = false;
+    }
+}
+
+/// Removes vertices no face uses any more, keeping the rest in order.
+fn drop_unreferenced(mesh: &mut NormalizedMesh) {
+    let mut used = vec![false; mesh.vertices.len()];
+    for face in &mesh.faces {
+        match face {
+            Face::Quad(idx, _) | Face::Poly(idx, _) => idx.iter().for_each(|&i| used[i as usize] = true),
+        }
+    }
+
+    let mut new_index = vec![0u32; mesh.vertices.len()];
+    let mut next = 0;
+    for (i, &u) in used.iter().enumerate() {
+        if u {
+            new_index[i] = next;
+            next += 1;
+        }
+    }
+
+    let mut k = used.iter();
+    mesh.vertices.retain(|_| *k.next().unwrap());
+    for face in &mut mesh.faces {
+        match face {
+            Face::Quad(idx, _) | Face::Poly(idx, _) => idx.iter_mut().for_each(|i| *i = new_index[*i as usize]),
+        }
     }
 }
 
-fn remap_vertex(original: u32, verts: &[Vertex], map: &HashMap<(i32,i32,i32,i32,i32), usize>) -> u32 {
-    let v = &verts[original as usize];
+/// Stores one value per triangle corner on the corner's vertex, duplicating
+/// the vertex when its corners need values that `same` considers different.
+pub(crate) fn assign_per_corner<T: Copy>(
+    mesh: &mut NormalizedMesh,
+    values: &[[T; 3]],
+    same: impl Fn(&Vertex, T) -> bool,
+    set: impl Fn(&mut Vertex, T),
+) {
+    let mut assigned = vec![false; mesh.vertices.len()];
+    let mut copies: HashMap<usize, Vec<u32>> = HashMap::new();
 
-    let key = (
-        (v.x * 10000.0) as i32,
-        (v.y * 10000.0) as i32,
-        (v.z * 10000.0) as i32,
-        (v.u * 10000.0) as i32,
-        (v.v * 10000.0) as i32,
-    );
+    for (fi, face) in mesh.faces.iter_mut().enumerate() {
+        let idx = match face {
+            Face::Quad(idx, _) | Face::Poly(idx, _) => idx,
+        };
+        for k in 0..3 {
+            let vi = idx[k] as usize;
+            let value = values[fi][k];
 
-    *map.get(&key).unwrap() as u32
+            if !assigned[vi] {
+                assigned[vi] = true;
+                set(&mut mesh.vertices[vi], value);
+            } else if !same(&mesh.vertices[vi], value) {
+                let list = copies.entry(vi).or_default();
+                idx[k] = match list.iter().find(|&&c| same(&mesh.vertices[c as usize], value)) {
+                    Some(&c) => c,
+                    None => {
+                        let mut v = mesh.vertices[vi].clone();
+                        set(&mut v, value);
+                        mesh.vertices.push(v);
+                        list.push((mesh.vertices.len() - 1) as u32);
+                        (mesh.vertices.len() - 1) as u32
+                    }
+                };
+            }
+        }
+    }
 }
@@ -0,0 +1,31 @@
+// Small vector helpers shared by the geometry passes.
+
+pub(crate) fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// Unit vector along `a`, or `a` unchanged when it has no length.
+pub(crate) fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = length(a);
+    if len > 0.0 {
+        [a[0] / len, a[1] / len, a[2] / len]
+    } else {
+        a
+    }
+}
@@ -0,0 +1,113 @@
+// Per-vertex normal generation.
+//
+// Each triangle corner averages the normals of the triangles around the same
+// position whose normals lie within the crease angle of its own, so hard
+// edges stay sharp and curved surfaces shade smoothly. Vertices whose corners
+// end up with different normals are split.
+//
+// Materials with `LightSampling Facet`, or with non-solid `GeometrySampling`,
+// are faceted: their corners take the plain face normal and do not
+// contribute to neighbouring smooth surfaces.
+
+use hashbrown::HashMap;
+use rwx_parser::{Face, GeometrySampling, LightSampling, Material};
+
+use crate::math::{cross, dot, length, normalize, sub};
+use crate::{assign_per_corner, NormalizedMesh, NormalWeighting};
+
+pub(crate) fn generate(
+    mesh: &mut NormalizedMesh,
+    materials: &[Material],
+    crease_angle: f32,
+    weighting: NormalWeighting,
+    normal_epsilon: f32,
+) {
+    let tris: Vec<([usize; 3], bool)> = mesh
+        .faces
+        .iter()
+        .map(|f| {
+            let (idx, mat) = match f {
+                Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat),
+            };
+            let faceted = materials.iter().find(|m| m.name == *mat).is_some_and(is_faceted);
+            ([idx[0] as usize, idx[1] as usize, idx[2] as usize], faceted)
+        })
+        .collect();
+
+    let pos = |i: usize| {
+        let v = &mesh.vertices[i];
+        [v.x, v.y, v.z]
+    };
+
+    // unit face normal, plus each corner's weight
+    let mut face_normals = Vec::with_capacity(tris.len());
+    let mut corner_weights = Vec::with_capacity(tris.len());
+    for (t, _) in &tris {
+        let p = [pos(t[0]), pos(t[1]), pos(t[2])];
+        let n = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+        let double_area = length(n);
+        face_normals.push(if double_area > 0.0 { normalize(n) } else { [0.0, 1.0, 0.0] });
+
+        let mut w = [0.0f32; 3];
+        for k in 0..3 {
+            w[k] = match weighting {
+                NormalWeighting::Area => double_area,
+                NormalWeighting::Angle => corner_angle(p[k], p[(k + 1) % 3], p[(k + 2) % 3]),
+            };
+        }
+        corner_weights.push(w);
+    }
+
+    // smooth triangles touching each position, with the corner index used there
+    let mut around: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+    for (ti, (t, faceted)) in tris.iter().enumerate() {
+        if *faceted {
+            continue;
+        }
+        for (k, &vi) in t.iter().enumerate() {
+            around.entry(position_key(pos(vi))).or_default().push((ti, k));
+        }
+    }
+
+    let cos_crease = crease_angle.to_radians().cos();
+    let mut corner_normals = vec![[[0.0f32; 3]; 3]; tris.len()];
+    for (ti, (t, faceted)) in tris.iter().enumerate() {
+        let own = face_normals[ti];
+        for (k, &vi) in t.iter().enumerate() {
+            if *faceted {
+                corner_normals[ti][k] = own;
+                continue;
+            }
+
+            let mut sum = [0.0f32; 3];
+            for &(other, ok) in &around[&position_key(pos(vi))] {
+                let n = face_normals[other];
+                if dot(n, own) >= cos_crease {
+                    let w = corner_weights[other][ok];
+                    sum = [sum[0] + n[0] * w, sum[1] + n[1] * w, sum[2] + n[2] * w];
+                }
+            }
+            corner_normals[ti][k] = if length(sum) > 0.0 { normalize(sum) } else { own };
+        }
+    }
+
+    let same = |v: &rwx_parser::Vertex, n: [f32; 3]| {
+        v.normal.is_some_and(|a| (0..3).all(|k| (a[k] - n[k]).abs() <= normal_epsilon))
+    };
+    assign_per_corner(mesh, &corner_normals, same, |v, n| v.normal = Some(n));
+}
+
+fn is_faceted(m: &Material) -> bool {
+    m.light_sampling == LightSampling::Facet || m.geometry_sampling != GeometrySampling::Solid
+}
+
+fn position_key(p: [f32; 3]) -> [u32; 3] {
+    // +0.0 and -0.0 are the same position
+    p.map(|c| if c == 0.0 { 0 } else { c.to_bits() })
+}
+
+fn corner_angle(at: [f32; 3], a: [f32; 3], b: [f32; 3]) -> f32 {
+    let e1 = normalize(sub(a, at));
+    let e2 = normalize(sub(b, at));
+    dot(e1, e2).clamp(-1.0, 1.0).acos()
+}
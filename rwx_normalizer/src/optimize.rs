@@ -0,0 +1,323 @@
+// Index buffer optimisation for rendering.
+//
+// Optional pass run after `normalize`. Triangles of each material stay
+// together (each material is its own draw call) and are reordered inside
+// their group:
+//
+// 1. Tom Forsyth's linear-speed vertex cache optimisation, scoring vertices
+//    by their position in a simulated LRU cache and by how many unprocessed
+//    triangles still use them.
+// 2. Optionally, overdraw reduction: the cache-friendly order is cut into
+//    clusters wherever a triangle misses the cache on every corner, and the
+//    clusters are sorted so those facing away from the mesh centre (the
+//    ones most likely to be in front) draw first. The new order is kept
+//    only if its ACMR stays within `overdraw_threshold` of the cache order.
+// 3. Optionally, vertices are renumbered in order of first use so vertex
+//    fetch walks memory forwards.
+//
+// Forsyth's scoring is a heuristic and can do worse than an order that is
+// already good, such as the row-by-row order of a grid, so a group keeps
+// its original order unless the new one misses the cache less, and the
+// mesh keeps its original triangle order unless the overall ACMR improves.
+
+use std::fmt;
+
+use rwx_parser::Face;
+
+use crate::math::{cross, dot, normalize, sub};
+use crate::NormalizedMesh;
+
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRI_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+#[derive(Debug, Clone)]
+pub struct OptimizeOptions {
+    /// Size of the simulated post-transform cache
+    pub cache_size: usize,
+    pub reduce_overdraw: bool,
+    /// Largest ACMR growth accepted for overdraw reduction, e.g. 1.05
+    pub overdraw_threshold: f32,
+    pub reorder_vertices: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            cache_size: 32,
+            reduce_overdraw: false,
+            overdraw_threshold: 1.05,
+            reorder_vertices: true,
+        }
+    }
+}
+
+/// Average cache miss ratio (transformed vertices per triangle) before and
+/// after optimisation, measured with a FIFO cache of `cache_size`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizeReport {
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl fmt::Display for OptimizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ACMR {:.3} -> {:.3}", self.acmr_before, self.acmr_after)
+    }
+}
+
+pub fn optimize(mesh: &mut NormalizedMesh, opts: &OptimizeOptions) -> OptimizeReport {
+    let before = acmr(&mesh.faces, opts.cache_size);
+
+    // group triangles by material in order of first appearance
+    let mut groups: Vec<(String, Vec<[u32; 3]>)> = Vec::new();
+    for face in &mesh.faces {
+        let (idx, mat) = match face {
+            Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat),
+        };
+        let tri = [idx[0], idx[1], idx[2]];
+        match groups.iter_mut().find(|(m, _)| m == mat) {
+            Some((_, tris)) => tris.push(tri),
+            None => groups.push((mat.clone(), vec![tri])),
+        }
+    }
+
+    let mut faces = Vec::with_capacity(mesh.faces.len());
+    for (mat, tris) in groups {
+        let mut order = forsyth(&tris, mesh.vertices.len(), opts.cache_size);
+        if opts.reduce_overdraw {
+            order = reduce_overdraw(mesh, order, opts);
+        }
+        if misses(&order, opts.cache_size) >= misses(&tris, opts.cache_size) {
+            order = tris;
+        }
+        faces.extend(order.into_iter().map(|t| Face::Poly(t.to_vec(), mat.clone())));
+    }
+    if acmr(&faces, opts.cache_size) < before {
+        mesh.faces = faces;
+    }
+
+    if opts.reorder_vertices {
+        reorder_vertices(mesh);
+    }
+
+    OptimizeReport { acmr_before: before, acmr_after: acmr(&mesh.faces, opts.cache_size) }
+}
+
+/// Vertices transformed per triangle with a FIFO cache of `cache_size`.
+pub fn acmr(faces: &[Face], cache_size: usize) -> f32 {
+    if faces.is_empty() {
+        return 0.0;
+    }
+    let tris: Vec<[u32; 3]> = faces
+        .iter()
+        .map(|f| match f {
+            Face::Quad(idx, _) | Face::Poly(idx, _) => [idx[0], idx[1], idx[2]],
+        })
+        .collect();
+    misses(&tris, cache_size) as f32 / tris.len() as f32
+}
+
+fn misses(tris: &[[u32; 3]], cache_size: usize) -> usize {
+    let mut cache: std::collections::VecDeque<u32> = std::collections::VecDeque::with_capacity(cache_size);
+    let mut count = 0;
+    for t in tris {
+        for &v in t {
+            if !cache.contains(&v) {
+                count += 1;
+                if cache.len() == cache_size {
+                    cache.pop_front();
+                }
+                cache.push_back(v);
+            }
+        }
+    }
+    count
+}
+
+fn vertex_score(cache_pos: Option<usize>, remaining: usize, cache_size: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let mut score = match cache_pos {
+        // the triangle just drawn gets a fixed score so it isn't favoured too much
+        Some(p) if p < 3 => LAST_TRI_SCORE,
+        Some(p) if p < cache_size => {
+            let scale = 1.0 / (cache_size - 3) as f32;
+            (1.0 - (p - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+        _ => 0.0,
+    };
+
+    // boost vertices with few triangles left so they get finished off
+    score += VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER);
+    score
+}
+
+fn forsyth(tris: &[[u32; 3]], vertex_count: usize, cache_size: usize) -> Vec<[u32; 3]> {
+    let cache_size = cache_size.max(4);
+
+    let mut vert_tris: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for (ti, t) in tris.iter().enumerate() {
+        for &v in t {
+            vert_tris[v as usize].push(ti);
+        }
+    }
+
+    let mut remaining: Vec<usize> = vert_tris.iter().map(|l| l.len()).collect();
+    let mut cache_pos: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vscore: Vec<f32> = (0..vertex_count).map(|v| vertex_score(None, remaining[v], cache_size)).collect();
+    let mut tscore: Vec<f32> = tris.iter().map(|t| t.iter().map(|&v| vscore[v as usize]).sum()).collect();
+    let mut done = vec![false; tris.len()];
+
+    let mut cache: Vec<u32> = Vec::with_capacity(cache_size + 3);
+    let mut out = Vec::with_capacity(tris.len());
+    let mut best = best_triangle(&tscore, &done, 0..tris.len());
+
+    while let Some(ti) = best {
+        done[ti] = true;
+        out.push(tris[ti]);
+
+        // move the triangle's vertices to the front of the cache
+        for &v in &tris[ti] {
+            remaining[v as usize] -= 1;
+            cache.retain(|&c| c != v);
+        }
+        for &v in tris[ti].iter().rev() {
+            cache.insert(0, v);
+        }
+        let evicted: Vec<u32> = if cache.len() > cache_size { cache.split_off(cache_size) } else { Vec::new() };
+
+        // rescore vertices that moved and the triangles using them
+        let mut touched: Vec<usize> = Vec::new();
+        for (p, &v) in cache.iter().enumerate() {
+            cache_pos[v as usize] = Some(p);
+        }
+        for &v in &evicted {
+            cache_pos[v as usize] = None;
+        }
+        for &v in cache.iter().chain(evicted.iter()) {
+            let v = v as usize;
+            let new = vertex_score(cache_pos[v], remaining[v], cache_size);
+            let delta = new - vscore[v];
+            vscore[v] = new;
+            for &t in &vert_tris[v] {
+                if !done[t] {
+                    tscore[t] += delta;
+                    touched.push(t);
+                }
+            }
+        }
+
+        // the next triangle is usually one touching the cache; otherwise scan everything
+        best = best_triangle(&tscore, &done, touched.into_iter())
+            .or_else(|| best_triangle(&tscore, &done, 0..tris.len()));
+    }
+
+    out
+}
+
+fn best_triangle(tscore: &[f32], done: &[bool], candidates: impl Iterator<Item = usize>) -> Option<usize> {
+    candidates
+        .filter(|&t| !done[t])
+        .max_by(|&a, &b| tscore[a].total_cmp(&tscore[b]).then(b.cmp(&a)))
+}
+
+fn reduce_overdraw(mesh: &NormalizedMesh, order: Vec<[u32; 3]>, opts: &OptimizeOptions) -> Vec<[u32; 3]> {
+    if order.len() < 2 {
+        return order;
+    }
+    let pos = |i: u32| {
+        let v = &mesh.vertices[i as usize];
+        [v.x, v.y, v.z]
+    };
+
+    // cut clusters where the cache has nothing useful left
+    let mut clusters: Vec<Vec<[u32; 3]>> = Vec::new();
+    let mut cache: std::collections::VecDeque<u32> = std::collections::VecDeque::new();
+    for t in &order {
+        let hits = t.iter().filter(|v| cache.contains(v)).count();
+        if hits == 0 || clusters.is_empty() {
+            clusters.push(Vec::new());
+        }
+        clusters.last_mut().unwrap().push(*t);
+        for &v in t {
+            if !cache.contains(&v) {
+                if cache.len() == opts.cache_size {
+                    cache.pop_front();
+                }
+                cache.push_back(v);
+            }
+        }
+    }
+    if clusters.len() < 2 {
+        return order;
+    }
+
+    let mut centre = [0.0f32; 3];
+    for t in &order {
+        for &v in t {
+            let p = pos(v);
+            centre = [centre[0] + p[0], centre[1] + p[1], centre[2] + p[2]];
+        }
+    }
+    let n = (order.len() * 3) as f32;
+    centre = [centre[0] / n, centre[1] / n, centre[2] / n];
+
+    // how far a cluster faces away from the centre
+    let mut keyed: Vec<(f32, Vec<[u32; 3]>)> = clusters
+        .into_iter()
+        .map(|c| {
+            let mut mid = [0.0f32; 3];
+            let mut normal = [0.0f32; 3];
+            for t in &c {
+                let p = [pos(t[0]), pos(t[1]), pos(t[2])];
+                let area_normal = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+                normal = [normal[0] + area_normal[0], normal[1] + area_normal[1], normal[2] + area_normal[2]];
+                for q in p {
+                    mid = [mid[0] + q[0], mid[1] + q[1], mid[2] + q[2]];
+                }
+            }
+            let k = (c.len() * 3) as f32;
+            mid = [mid[0] / k, mid[1] / k, mid[2] / k];
+            (dot(sub(mid, centre), normalize(normal)), c)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let sorted: Vec<[u32; 3]> = keyed.into_iter().flat_map(|(_, c)| c).collect();
+    let limit = misses(&order, opts.cache_size) as f32 * opts.overdraw_threshold;
+    if misses(&sorted, opts.cache_size) as f32 <= limit {
+        sorted
+    } else {
+        order
+    }
+}
+
+/// Renumbers vertices in order of first use; unused vertices go last.
+fn reorder_vertices(mesh: &mut NormalizedMesh) {
+    let mut new_index: Vec<Option<u32>> = vec![None; mesh.vertices.len()];
+    let mut order: Vec<usize> = Vec::with_capacity(mesh.vertices.len());
+
+    for face in &mut mesh.faces {
+        let idx = match face {
+            Face::Quad(idx, _) | Face::Poly(idx, _) => idx,
+        };
+        for i in idx.iter_mut() {
+            let old = *i as usize;
+            *i = *new_index[old].get_or_insert_with(|| {
+                order.push(old);
+                (order.len() - 1) as u32
+            });
+        }
+    }
+    for (old, slot) in new_index.iter().enumerate() {
+        if slot.is_none() {
+            order.push(old);
+        }
+    }
+
+    mesh.vertices = order.into_iter().map(|i| mesh.vertices[i].clone()).collect();
+}
@@ -0,0 +1,287 @@
+// Winding repair and hole detection.
+//
+// Runs on cleaned triangles, before normals are generated. Adjacency is
+// built on positions rather than vertex indices, so UV seams and material
+// borders do not split a surface into separate pieces.
+//
+// - Edges used by more than two triangles are non-manifold. They are
+//   reported and treated as borders: orientation does not spread across
+//   them.
+// - Each connected piece is made to agree with itself. A closed piece is
+//   then turned outward (positive volume); an open one keeps the winding of
+//   the larger part of its area, which is usually what was authored.
+// - Boundary loops of at most `max_hole_edges` edges that cover less than
+//   half the area of their piece are reported as holes, and filled when
+//   asked. Bigger openings are taken to be intentional, like the outline
+//   of a sign.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use hashbrown::HashMap;
+use rwx_parser::Face;
+
+use crate::math::{cross, dot, length, sub};
+use crate::triangulate::triangulate;
+use crate::NormalizedMesh;
+
+#[derive(Debug, Clone)]
+pub struct Hole {
+    /// Corners of the boundary loop, in the order a filling face would use
+    pub positions: Vec<[f32; 3]>,
+    pub filled: bool,
+}
+
+/// What the repair pass found and changed.
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    /// 0-based source faces whose winding was reversed
+    pub flipped_faces: Vec<usize>,
+    pub non_manifold_edges: Vec<[[f32; 3]; 2]>,
+    pub holes: Vec<Hole>,
+}
+
+impl RepairReport {
+    pub fn is_empty(&self) -> bool {
+        self.flipped_faces.is_empty() && self.non_manifold_edges.is_empty() && self.holes.is_empty()
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.flipped_faces.is_empty() {
+            let faces: Vec<String> = self.flipped_faces.iter().map(|f| (f + 1).to_string()).collect();
+            writeln!(f, "  flipped faces {}", faces.join(", "))?;
+        }
+        for [a, b] in &self.non_manifold_edges {
+            writeln!(f, "  non-manifold edge ({} {} {}) ({} {} {})", a[0], a[1], a[2], b[0], b[1], b[2])?;
+        }
+        for hole in &self.holes {
+            let p = hole.positions[0];
+            writeln!(
+                f,
+                "  hole of {} edges at ({} {} {}){}",
+                hole.positions.len(),
+                p[0], p[1], p[2],
+                if hole.filled { ", filled" } else { "" },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct EdgeUse {
+    face: usize,
+    /// The face runs the edge from the lower position id to the higher
+    forward: bool,
+}
+
+/// Orients `mesh` consistently and optionally fills small holes;
+/// `source_faces` runs parallel to `mesh.faces` and grows with fills.
+pub(crate) fn repair(
+    mesh: &mut NormalizedMesh,
+    source_faces: &mut Vec<usize>,
+    fill_holes: bool,
+    max_hole_edges: usize,
+) -> RepairReport {
+    let mut report = RepairReport::default();
+
+    let mut ids: HashMap<[u32; 3], usize> = HashMap::new();
+    let pid: Vec<usize> = mesh
+        .vertices
+        .iter()
+        .map(|v| {
+            let key = [v.x, v.y, v.z].map(|c| if c == 0.0 { 0 } else { c.to_bits() });
+            let next = ids.len();
+            *ids.entry(key).or_insert(next)
+        })
+        .collect();
+
+    let mut tris: Vec<[u32; 3]> = mesh.faces.iter().map(corners).collect();
+    let edges = edge_uses(&tris, &pid);
+
+    let mut keys: Vec<&(usize, usize)> = edges.keys().collect();
+    keys.sort_unstable();
+    for &(a, b) in keys {
+        let uses = &edges[&(a, b)];
+        if uses.len() > 2 {
+            let va = tris[uses[0].face].iter().find(|&&v| pid[v as usize] == a).unwrap();
+            let vb = tris[uses[0].face].iter().find(|&&v| pid[v as usize] == b).unwrap();
+            report.non_manifold_edges.push([position(mesh, *va), position(mesh, *vb)]);
+        }
+    }
+
+    // flood each component, recording which faces disagree with its first face
+    let mut component = vec![usize::MAX; tris.len()];
+    let mut flip = vec![false; tris.len()];
+    let mut components: Vec<Vec<usize>> = Vec::new();
+    for seed in 0..tris.len() {
+        if component[seed] != usize::MAX {
+            continue;
+        }
+        let c = components.len();
+        component[seed] = c;
+        let mut members = vec![seed];
+        let mut queue = VecDeque::from([seed]);
+        while let Some(fi) = queue.pop_front() {
+            for k in 0..3 {
+                let (a, b) = (pid[tris[fi][k] as usize], pid[tris[fi][(k + 1) % 3] as usize]);
+                let uses = &edges[&edge(a, b)];
+                if uses.len() != 2 {
+                    continue;
+                }
+                let (this, other) = if uses[0].face == fi { (&uses[0], &uses[1]) } else { (&uses[1], &uses[0]) };
+                if component[other.face] == usize::MAX {
+                    component[other.face] = c;
+                    flip[other.face] = flip[fi] ^ (this.forward == other.forward);
+                    members.push(other.face);
+                    queue.push_back(other.face);
+                }
+            }
+        }
+        components.push(members);
+    }
+
+    for members in &components {
+        let closed = members.iter().all(|&fi| {
+            (0..3).all(|k| edges[&edge(pid[tris[fi][k] as usize], pid[tris[fi][(k + 1) % 3] as usize])].len() == 2)
+        });
+
+        let mut volume = 0.0f64;
+        let (mut kept_area, mut flipped_area) = (0.0f32, 0.0f32);
+        for &fi in members {
+            let p = tris[fi].map(|v| position(mesh, v));
+            let area = length(cross(sub(p[1], p[0]), sub(p[2], p[0])));
+            let v = dot(p[0], cross(p[1], p[2])) as f64;
+            if flip[fi] {
+                flipped_area += area;
+                volume -= v;
+            } else {
+                kept_area += area;
+                volume += v;
+            }
+        }
+
+        // back-to-back sheets enclose nothing; leave those as authored
+        let scale = ((kept_area + flipped_area) as f64).powf(1.5);
+        let invert = if closed && volume.abs() > 1e-6 * scale {
+            volume < 0.0
+        } else {
+            flipped_area > kept_area
+        };
+        if invert {
+            for &fi in members {
+                flip[fi] = !flip[fi];
+            }
+        }
+    }
+
+    for (fi, face) in mesh.faces.iter_mut().enumerate() {
+        if flip[fi] {
+            let (Face::Quad(idx, _) | Face::Poly(idx, _)) = face;
+            idx.swap(1, 2);
+            tris[fi].swap(1, 2);
+            report.flipped_faces.push(source_faces[fi]);
+        }
+    }
+    report.flipped_faces.dedup();
+
+    // boundary half-edges, now consistently wound: start position -> (vertex, next position, face)
+    let mut outgoing: HashMap<usize, Vec<(u32, usize, usize)>> = HashMap::new();
+    for (fi, t) in tris.iter().enumerate() {
+        for k in 0..3 {
+            let (va, vb) = (t[k], t[(k + 1) % 3]);
+            if edges[&edge(pid[va as usize], pid[vb as usize])].len() == 1 {
+                outgoing.entry(pid[va as usize]).or_default().push((va, pid[vb as usize], fi));
+            }
+        }
+    }
+
+    let mut component_area = vec![0.0f32; components.len()];
+    for (fi, t) in tris.iter().enumerate() {
+        let p = t.map(|v| position(mesh, v));
+        component_area[component[fi]] += length(cross(sub(p[1], p[0]), sub(p[2], p[0]))) * 0.5;
+    }
+
+    let mut starts: Vec<usize> = outgoing.keys().copied().collect();
+    starts.sort_unstable();
+    let mut visited: HashMap<usize, ()> = HashMap::new();
+    for start in starts {
+        if visited.contains_key(&start) {
+            continue;
+        }
+
+        // walk the loop; give up where the boundary branches
+        let mut loop_vertices: Vec<u32> = Vec::new();
+        let mut face = usize::MAX;
+        let mut at = start;
+        let closed = loop {
+            let Some(out) = outgoing.get(&at) else { break false };
+            if out.len() != 1 || visited.contains_key(&at) {
+                break at == start && !loop_vertices.is_empty();
+            }
+            visited.insert(at, ());
+            let (v, next, fi) = out[0];
+            loop_vertices.push(v);
+            face = fi;
+            at = next;
+            if at == start {
+                break true;
+            }
+        };
+        if !closed || loop_vertices.len() < 3 || loop_vertices.len() > max_hole_edges {
+            continue;
+        }
+
+        // a filling face runs the loop backwards
+        loop_vertices.reverse();
+        let points: Vec<[f32; 3]> = loop_vertices.iter().map(|&v| position(mesh, v)).collect();
+        let fill = triangulate(&points);
+        let fill_area: f32 = fill
+            .iter()
+            .map(|t| length(cross(sub(points[t[1]], points[t[0]]), sub(points[t[2]], points[t[0]]))) * 0.5)
+            .sum();
+        if fill_area >= component_area[component[face]] * 0.5 {
+            continue;
+        }
+
+        if fill_holes {
+            let mat = match &mesh.faces[face] {
+                Face::Quad(_, mat) | Face::Poly(_, mat) => mat.clone(),
+            };
+            for t in fill {
+                mesh.faces.push(Face::Poly(t.iter().map(|&k| loop_vertices[k]).collect(), mat.clone()));
+                source_faces.push(source_faces[face]);
+            }
+        }
+        report.holes.push(Hole { positions: points, filled: fill_holes });
+    }
+
+    report
+}
+
+fn corners(face: &Face) -> [u32; 3] {
+    match face {
+        Face::Quad(idx, _) | Face::Poly(idx, _) => [idx[0], idx[1], idx[2]],
+    }
+}
+
+fn position(mesh: &NormalizedMesh, v: u32) -> [f32; 3] {
+    let v = &mesh.vertices[v as usize];
+    [v.x, v.y, v.z]
+}
+
+fn edge_uses(tris: &[[u32; 3]], pid: &[usize]) -> HashMap<(usize, usize), Vec<EdgeUse>> {
+    let mut edges: HashMap<(usize, usize), Vec<EdgeUse>> = HashMap::new();
+    for (fi, t) in tris.iter().enumerate() {
+        for k in 0..3 {
+            let (a, b) = (pid[t[k] as usize], pid[t[(k + 1) % 3] as usize]);
+            edges.entry(edge(a, b)).or_default().push(EdgeUse { face: fi, forward: a < b });
+        }
+    }
+    edges
+}
+
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    if a < b { (a, b) } else { (b, a) }
+}
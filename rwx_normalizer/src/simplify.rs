@@ -0,0 +1,564 @@
+// Quadric error mesh simplification for LOD generation.
+//
+// Garland-Heckbert quadrics with half-edge collapses: a vertex is merged
+// into one of its neighbours, which keeps all of that neighbour's
+// attributes, so no UVs or normals have to be interpolated.
+//
+// Collapses work on positions rather than vertices, so the vertices that
+// UV seams and hard normal edges split at one position move as a group,
+// each into the vertex at the target that it shares a triangle with.
+//
+// What has to survive simplification:
+//
+// - UV seams and hard normal edges: a vertex on one only collapses along
+//   it, and where seams meet or branch, such as the corners of a cube, it
+//   never moves.
+// - Material borders, where a vertex is used by more than one material:
+//   those vertices never move.
+// - Open edges (the silhouette of signs, leaves and other open shells):
+//   vertices on them only collapse along the open edge, and every open edge
+//   adds a constraint plane so the outline keeps its shape.
+//
+// Collapses that would flip a triangle or pinch the surface into a
+// non-manifold fold are rejected.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use hashbrown::HashMap;
+use rwx_parser::Face;
+
+use crate::math::{cross, dot, length, sub};
+use crate::NormalizedMesh;
+
+/// Weight of the constraint planes along open edges, relative to faces.
+const BORDER_WEIGHT: f64 = 10.0;
+
+#[derive(Debug, Clone)]
+pub struct LodLevel {
+    /// Fraction of the original triangle count this level aimed for
+    pub ratio: f32,
+    pub mesh: NormalizedMesh,
+    /// Largest distance from a vertex of LOD 0 to this level's surface, in
+    /// mesh units
+    pub error: f32,
+}
+
+/// Simplifies `mesh` towards `ratio` of its triangles.
+///
+/// Returns the simplified mesh and the largest distance from a vertex of
+/// `mesh` to its surface. The result may have more triangles than asked for
+/// when no further collapse is safe.
+pub fn simplify(mesh: &NormalizedMesh, ratio: f32) -> (NormalizedMesh, f32) {
+    let target = ((mesh.faces.len() as f32) * ratio.clamp(0.0, 1.0)).ceil() as usize;
+    let lod = Simplifier::new(mesh).run(target);
+    let error = deviation(mesh, &lod);
+    (lod, error)
+}
+
+/// Builds LOD 0 (the input) followed by one level per ratio, each simplified
+/// from the level before it.
+pub fn generate_lods(mesh: &NormalizedMesh, ratios: &[f32]) -> Vec<LodLevel> {
+    let mut levels = vec![LodLevel { ratio: 1.0, mesh: mesh.clone(), error: 0.0 }];
+    let original = mesh.faces.len() as f32;
+
+    for &ratio in ratios {
+        let prev = levels.last().unwrap();
+        let relative = if prev.mesh.faces.is_empty() { 1.0 } else { ratio * original / prev.mesh.faces.len() as f32 };
+        let (lod, _) = simplify(&prev.mesh, relative.min(1.0));
+        // measured against LOD 0, as the distances of the steps do not add up
+        let error = deviation(mesh, &lod);
+        levels.push(LodLevel { ratio, mesh: lod, error });
+    }
+
+    levels
+}
+
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane(n: [f64; 3], d: f64, w: f64) -> Self {
+        let [a, b, c] = n;
+        Quadric([
+            a * a * w, a * b * w, a * c * w, a * d * w,
+            b * b * w, b * c * w, b * d * w,
+            c * c * w, c * d * w,
+            d * d * w,
+        ])
+    }
+
+    fn add(&mut self, o: &Quadric) {
+        for k in 0..10 {
+            self.0[k] += o.0[k];
+        }
+    }
+
+    fn error(&self, p: [f32; 3]) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p[0] as f64, p[1] as f64, p[2] as f64);
+        let e = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9];
+        e.max(0.0)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Interior,
+    Border,
+    Seam,
+    Locked,
+}
+
+struct Candidate {
+    cost: f64,
+    from: u32,
+    to: u32,
+    stamp: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // cheapest first out of the max-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Collapses work on positions, each named by the first vertex there;
+/// vertex lists, kinds, quadrics and edges below are indexed by those.
+struct Simplifier<'a> {
+    mesh: &'a NormalizedMesh,
+    /// Triangles as indices into `mesh.vertices`
+    tris: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    /// Position of each vertex
+    position: Vec<u32>,
+    vert_tris: Vec<Vec<usize>>,
+    kind: Vec<Kind>,
+    border_edges: HashMap<(u32, u32), ()>,
+    seam_edges: HashMap<(u32, u32), ()>,
+    quadrics: Vec<Quadric>,
+    stamp: Vec<u32>,
+    removed: Vec<bool>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn new(mesh: &'a NormalizedMesh) -> Self {
+        let n = mesh.vertices.len();
+        let mut first: HashMap<[u32; 3], u32> = HashMap::new();
+        let position: Vec<u32> = mesh
+            .vertices
+            .iter()
+            .enumerate()
+            .map(|(i, v)| *first.entry([v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]).or_insert(i as u32))
+            .collect();
+
+        let mut tris = Vec::with_capacity(mesh.faces.len());
+        let mut vert_tris = vec![Vec::new(); n];
+        let mut materials: Vec<Option<&str>> = vec![None; n];
+        let mut wedges: Vec<Vec<u32>> = vec![Vec::new(); n];
+        let mut kind = vec![Kind::Interior; n];
+
+        for (ti, face) in mesh.faces.iter().enumerate() {
+            let (idx, mat) = match face {
+                Face::Quad(idx, mat) | Face::Poly(idx, mat) => (idx, mat),
+            };
+            tris.push([idx[0], idx[1], idx[2]]);
+            for &v in &idx[..3] {
+                let p = position[v as usize] as usize;
+                vert_tris[p].push(ti);
+                if !wedges[p].contains(&v) {
+                    wedges[p].push(v);
+                }
+                match materials[p] {
+                    None => materials[p] = Some(mat.as_str()),
+                    Some(m) if m != mat => kind[p] = Kind::Locked,
+                    _ => {}
+                }
+            }
+        }
+
+        // open, seam and non-manifold edges; along a seam the faces on
+        // either side use different vertices at one end or both
+        let mut edge_uses: HashMap<(u32, u32), Vec<[u32; 2]>> = HashMap::new();
+        for t in &tris {
+            for k in 0..3 {
+                let (a, b) = (t[k], t[(k + 1) % 3]);
+                let (pa, pb) = (position[a as usize], position[b as usize]);
+                let pair = if pa < pb { [a, b] } else { [b, a] };
+                edge_uses.entry(edge(pa, pb)).or_default().push(pair);
+            }
+        }
+        let mut border_edges = HashMap::new();
+        let mut seam_edges = HashMap::new();
+        let mut seam_count = vec![0usize; n];
+        for (&(a, b), uses) in &edge_uses {
+            match uses.len() {
+                1 => {
+                    border_edges.insert((a, b), ());
+                    for v in [a, b] {
+                        if kind[v as usize] == Kind::Interior {
+                            kind[v as usize] = Kind::Border;
+                        }
+                    }
+                }
+                2 => {
+                    if uses[0] != uses[1] {
+                        seam_edges.insert((a, b), ());
+                        seam_count[a as usize] += 1;
+                        seam_count[b as usize] += 1;
+                    }
+                }
+                _ => {
+                    kind[a as usize] = Kind::Locked;
+                    kind[b as usize] = Kind::Locked;
+                }
+            }
+        }
+
+        // a seam running through a position splits it into two vertices,
+        // which slide along the seam together; anything more tangled stays
+        for (p, w) in wedges.iter().enumerate() {
+            if w.len() > 1 {
+                let seam = kind[p] == Kind::Interior && w.len() == 2 && seam_count[p] == 2;
+                kind[p] = if seam { Kind::Seam } else { Kind::Locked };
+            }
+        }
+
+        let mut s = Simplifier {
+            mesh,
+            alive: vec![true; tris.len()],
+            tris,
+            position,
+            vert_tris,
+            kind,
+            border_edges,
+            seam_edges,
+            quadrics: vec![Quadric::default(); n],
+            stamp: vec![0; n],
+            removed: vec![false; n],
+        };
+        s.build_quadrics();
+        s
+    }
+
+    fn pos(&self, v: u32) -> [f32; 3] {
+        let p = &self.mesh.vertices[v as usize];
+        [p.x, p.y, p.z]
+    }
+
+    /// Positions of a triangle's corners.
+    fn corners(&self, ti: usize) -> [u32; 3] {
+        self.tris[ti].map(|v| self.position[v as usize])
+    }
+
+    fn build_quadrics(&mut self) {
+        for ti in 0..self.tris.len() {
+            let t = self.corners(ti);
+            let p = [self.pos(t[0]), self.pos(t[1]), self.pos(t[2])];
+            let n = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+            let double_area = length(n);
+            if double_area <= 0.0 {
+                continue;
+            }
+            let unit = [(n[0] / double_area) as f64, (n[1] / double_area) as f64, (n[2] / double_area) as f64];
+            let d = -(unit[0] * p[0][0] as f64 + unit[1] * p[0][1] as f64 + unit[2] * p[0][2] as f64);
+            let q = Quadric::plane(unit, d, double_area as f64 * 0.5);
+            for &v in &t {
+                self.quadrics[v as usize].add(&q);
+            }
+
+            // keep open edges in place with a plane through the edge, perpendicular to the face
+            for k in 0..3 {
+                let (a, b) = (t[k], t[(k + 1) % 3]);
+                if !self.border_edges.contains_key(&edge(a, b)) {
+                    continue;
+                }
+                let e = sub(self.pos(b), self.pos(a));
+                let len = length(e);
+                if len <= 0.0 {
+                    continue;
+                }
+                let c = cross(e, n);
+                let cl = length(c);
+                if cl <= 0.0 {
+                    continue;
+                }
+                let cn = [(c[0] / cl) as f64, (c[1] / cl) as f64, (c[2] / cl) as f64];
+                let pa = self.pos(a);
+                let cd = -(cn[0] * pa[0] as f64 + cn[1] * pa[1] as f64 + cn[2] * pa[2] as f64);
+                let bq = Quadric::plane(cn, cd, BORDER_WEIGHT * (len * len) as f64);
+                self.quadrics[a as usize].add(&bq);
+                self.quadrics[b as usize].add(&bq);
+            }
+        }
+    }
+
+    fn neighbours(&self, v: u32) -> Vec<u32> {
+        let mut out = Vec::new();
+        for &ti in &self.vert_tris[v as usize] {
+            if !self.alive[ti] {
+                continue;
+            }
+            for w in self.corners(ti) {
+                if w != v && !out.contains(&w) {
+                    out.push(w);
+                }
+            }
+        }
+        out
+    }
+
+    /// Which vertex at `to` each vertex at `from` becomes, or `None` if the
+    /// collapse is not allowed.
+    fn can_collapse(&self, from: u32, to: u32) -> Option<Vec<(u32, u32)>> {
+        match self.kind[from as usize] {
+            Kind::Locked => return None,
+            // border and seam vertices slide along their border or seam only
+            Kind::Border if !self.border_edges.contains_key(&edge(from, to)) => return None,
+            Kind::Seam if !self.seam_edges.contains_key(&edge(from, to)) => return None,
+            _ => {}
+        }
+
+        // each vertex takes the one it shares a triangle on the edge with
+        let mut remap: Vec<(u32, u32)> = Vec::new();
+        for &ti in &self.vert_tris[from as usize] {
+            let corners = self.corners(ti);
+            if !self.alive[ti] || !corners.contains(&to) {
+                continue;
+            }
+            let at = |p: u32| self.tris[ti][corners.iter().position(|&c| c == p).unwrap()];
+            let (a, b) = (at(from), at(to));
+            match remap.iter().find(|(x, _)| *x == a) {
+                Some(&(_, y)) if y != b => return None,
+                Some(_) => {}
+                None => remap.push((a, b)),
+            }
+        }
+        for &ti in &self.vert_tris[from as usize] {
+            if self.alive[ti] && !self.tris[ti].iter().any(|v| remap.iter().any(|(x, _)| x == v)) {
+                return None;
+            }
+        }
+
+        // link condition: shared neighbours must all come from triangles on the edge
+        let shared_tris = self.vert_tris[from as usize]
+            .iter()
+            .filter(|&&t| self.alive[t] && self.corners(t).contains(&to))
+            .count();
+        let from_n = self.neighbours(from);
+        let to_n = self.neighbours(to);
+        let common = from_n.iter().filter(|w| to_n.contains(w)).count();
+        if common > shared_tris {
+            return None;
+        }
+
+        // no triangle may flip or collapse to nothing
+        let target = self.pos(to);
+        for &ti in &self.vert_tris[from as usize] {
+            let t = self.corners(ti);
+            if !self.alive[ti] || t.contains(&to) {
+                continue;
+            }
+            let p = t.map(|v| self.pos(v));
+            let moved = t.map(|v| if v == from { target } else { self.pos(v) });
+            let before = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+            let after = cross(sub(moved[1], moved[0]), sub(moved[2], moved[0]));
+            let (lb, la) = (length(before), length(after));
+            if la <= 0.0 || (lb > 0.0 && dot(before, after) < 0.2 * lb * la) {
+                return None;
+            }
+        }
+        Some(remap)
+    }
+
+    fn best_candidate(&self, from: u32) -> Option<Candidate> {
+        if self.removed[from as usize] || self.kind[from as usize] == Kind::Locked {
+            return None;
+        }
+        self.neighbours(from)
+            .into_iter()
+            .filter(|&to| self.can_collapse(from, to).is_some())
+            .map(|to| Candidate {
+                cost: self.quadrics[from as usize].error(self.pos(to)),
+                from,
+                to,
+                stamp: self.stamp[from as usize],
+            })
+            .max()
+    }
+
+    fn run(mut self, target: usize) -> NormalizedMesh {
+        let mut heap = BinaryHeap::new();
+        for v in 0..self.mesh.vertices.len() as u32 {
+            if self.position[v as usize] != v {
+                continue;
+            }
+            if let Some(c) = self.best_candidate(v) {
+                heap.push(c);
+            }
+        }
+
+        let mut live = self.tris.len();
+
+        while live > target {
+            let Some(c) = heap.pop() else { break };
+            if self.removed[c.from as usize] || c.stamp != self.stamp[c.from as usize] {
+                continue;
+            }
+            let remap = if self.removed[c.to as usize] { None } else { self.can_collapse(c.from, c.to) };
+            let Some(remap) = remap else {
+                self.stamp[c.from as usize] += 1;
+                if let Some(next) = self.best_candidate(c.from) {
+                    heap.push(next);
+                }
+                continue;
+            };
+
+            // collapse: drop triangles on the edge, move the rest onto `to`
+            let from_tris = std::mem::take(&mut self.vert_tris[c.from as usize]);
+            for ti in from_tris {
+                if !self.alive[ti] {
+                    continue;
+                }
+                if self.corners(ti).contains(&c.to) {
+                    self.alive[ti] = false;
+                    live -= 1;
+                } else {
+                    for v in self.tris[ti].iter_mut() {
+                        if let Some(&(_, to)) = remap.iter().find(|(a, _)| a == v) {
+                            *v = to;
+                        }
+                    }
+                    self.vert_tris[c.to as usize].push(ti);
+                }
+            }
+            self.removed[c.from as usize] = true;
+
+            // the moved vertex inherits the open and seam edges it brought along
+            for edges in [&mut self.border_edges, &mut self.seam_edges] {
+                let moved: Vec<(u32, u32)> =
+                    edges.keys().filter(|&&(a, b)| a == c.from || b == c.from).copied().collect();
+                for (a, b) in moved {
+                    edges.remove(&(a, b));
+                    let other = if a == c.from { b } else { a };
+                    if other != c.to {
+                        edges.insert(edge(other, c.to), ());
+                    }
+                }
+            }
+
+            let q = self.quadrics[c.from as usize];
+            self.quadrics[c.to as usize].add(&q);
+
+            let mut affected = self.neighbours(c.to);
+            affected.push(c.to);
+            for v in affected {
+                self.stamp[v as usize] += 1;
+                if let Some(next) = self.best_candidate(v) {
+                    heap.push(next);
+                }
+            }
+        }
+
+        self.build_mesh()
+    }
+
+    fn build_mesh(&self) -> NormalizedMesh {
+        let mut new_index: Vec<Option<u32>> = vec![None; self.mesh.vertices.len()];
+        let mut vertices = Vec::new();
+        let mut faces = Vec::new();
+
+        for (ti, face) in self.mesh.faces.iter().enumerate() {
+            if !self.alive[ti] {
+                continue;
+            }
+            let mat = match face {
+                Face::Quad(_, mat) | Face::Poly(_, mat) => mat,
+            };
+            let idx = self.tris[ti].map(|v| {
+                *new_index[v as usize].get_or_insert_with(|| {
+                    vertices.push(self.mesh.vertices[v as usize].clone());
+                    (vertices.len() - 1) as u32
+                })
+            });
+            faces.push(Face::Poly(idx.to_vec(), mat.clone()));
+        }
+
+        NormalizedMesh {
+            vertices,
+            faces,
+            materials: self.mesh.materials.clone(),
+            report: self.mesh.report.clone(),
+        }
+    }
+}
+
+/// Largest distance from a vertex of `original` to the surface of
+/// `simplified`. Every vertex is tested against every triangle, which the
+/// sizes LOD levels are made for afford.
+fn deviation(original: &NormalizedMesh, simplified: &NormalizedMesh) -> f32 {
+    let position = |v: &rwx_parser::Vertex| [v.x, v.y, v.z];
+    let tris: Vec<[[f32; 3]; 3]> = simplified
+        .faces
+        .iter()
+        .map(|f| {
+            let (Face::Quad(idx, _) | Face::Poly(idx, _)) = f;
+            [0, 1, 2].map(|k| position(&simplified.vertices[idx[k] as usize]))
+        })
+        .collect();
+    if tris.is_empty() {
+        return 0.0;
+    }
+    original
+        .vertices
+        .iter()
+        .map(|v| tris.iter().map(|t| triangle_distance(position(v), t)).fold(f32::INFINITY, f32::min))
+        .fold(0.0, f32::max)
+}
+
+/// Distance from `p` to the closest point of triangle `t`.
+fn triangle_distance(p: [f32; 3], [a, b, c]: &[[f32; 3]; 3]) -> f32 {
+    let (ab, ac, ap) = (sub(*b, *a), sub(*c, *a), sub(p, *a));
+    let n = cross(ab, ac);
+    let area2 = dot(n, n);
+    if area2 > 0.0 {
+        // barycentric coordinates of p projected onto the plane
+        let v = dot(cross(ap, ac), n) / area2;
+        let w = dot(cross(ab, ap), n) / area2;
+        if v >= 0.0 && w >= 0.0 && v + w <= 1.0 {
+            return dot(ap, n).abs() / area2.sqrt();
+        }
+    }
+    // otherwise the closest point is on an edge
+    [(*a, *b), (*b, *c), (*c, *a)]
+        .into_iter()
+        .map(|(e0, e1)| {
+            let (d, q) = (sub(e1, e0), sub(p, e0));
+            let len2 = dot(d, d);
+            let t = if len2 > 0.0 { (dot(q, d) / len2).clamp(0.0, 1.0) } else { 0.0 };
+            length(sub(q, [d[0] * t, d[1] * t, d[2] * t]))
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+fn edge(a: u32, b: u32) -> (u32, u32) {
+    if a < b { (a, b) } else { (b, a) }
+}
@@ -0,0 +1,75 @@
+// MikkTSpace tangent generation.
+//
+// Tangents are only meaningful where a texture is mapped, so meshes without
+// UVs (every coordinate zero) or without normals are left untouched.
+// MikkTSpace produces one tangent per triangle corner; vertices shared by
+// corners with different tangents, such as along mirrored UV seams, are
+// split.
+
+use bevy_mikktspace::Geometry;
+use rwx_parser::Face;
+
+use crate::{assign_per_corner, NormalizedMesh};
+
+const TANGENT_EPSILON: f32 = 1e-3;
+
+struct MikkMesh<'a> {
+    mesh: &'a NormalizedMesh,
+    tangents: Vec<[[f32; 4]; 3]>,
+}
+
+impl MikkMesh<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> &rwx_parser::Vertex {
+        let idx = match &self.mesh.faces[face] {
+            Face::Quad(idx, _) | Face::Poly(idx, _) => idx,
+        };
+        &self.mesh.vertices[idx[vert] as usize]
+    }
+}
+
+impl Geometry for MikkMesh<'_> {
+    fn num_faces(&self) -> usize {
+        self.mesh.faces.len()
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let v = self.vertex(face, vert);
+        [v.x, v.y, v.z]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal.unwrap_or([0.0, 1.0, 0.0])
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let v = self.vertex(face, vert);
+        [v.u, v.v]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face][vert] = tangent;
+    }
+}
+
+pub(crate) fn generate(mesh: &mut NormalizedMesh) {
+    let has_uvs = mesh.vertices.iter().any(|v| v.u != 0.0 || v.v != 0.0);
+    let has_normals = mesh.vertices.iter().all(|v| v.normal.is_some());
+    if !has_uvs || !has_normals || mesh.faces.is_empty() {
+        return;
+    }
+
+    let mut mikk = MikkMesh { mesh, tangents: vec![[[1.0, 0.0, 0.0, 1.0]; 3]; mesh.faces.len()] };
+    if !bevy_mikktspace::generate_tangents(&mut mikk) {
+        return;
+    }
+    let tangents = mikk.tangents;
+
+    let same = |v: &rwx_parser::Vertex, t: [f32; 4]| {
+        v.tangent.is_some_and(|a| a[3] == t[3] && (0..3).all(|k| (a[k] - t[k]).abs() <= TANGENT_EPSILON))
+    };
+    assign_per_corner(mesh, &tangents, same, |v, t| v.tangent = Some(t));
+}
@@ -0,0 +1,166 @@
+// Polygon triangulation.
+//
+// Polygons are projected onto their best-fit (Newell) plane. Convex outlines
+// take the fan path; anything else is ear-clipped. Self-intersecting or
+// degenerate outlines, where no valid ear exists, fall back to clipping the
+// most convex corner so every polygon still yields n - 2 triangles.
+
+use crate::math::{cross, dot, normalize};
+
+const EPS: f32 = 1e-12;
+
+/// Triangulates a polygon given its corner positions in order.
+///
+/// Returns triangles as indices into `points`, wound the same way as the
+/// input outline.
+pub fn triangulate(points: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return vec![];
+    }
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let flat = project(points);
+
+    // work counter-clockwise, flip back at the end
+    let reversed = signed_area(&flat) < 0.0;
+    let mut order: Vec<usize> = (0..n).collect();
+    if reversed {
+        order.reverse();
+    }
+
+    let mut tris = if is_convex(&flat, &order) {
+        (1..n - 1).map(|k| [order[0], order[k], order[k + 1]]).collect()
+    } else {
+        ear_clip(&flat, order)
+    };
+
+    if reversed {
+        for t in &mut tris {
+            t.swap(1, 2);
+        }
+    }
+    tris
+}
+
+/// Projects the polygon onto the plane through it with the Newell normal.
+fn project(points: &[[f32; 3]]) -> Vec<[f32; 2]> {
+    let mut normal = [0.0f32; 3];
+    for i in 0..points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+
+    let len = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
+    if len <= EPS {
+        // collinear or zero-area outline: any projection will do
+        return points.iter().map(|p| [p[0], p[1]]).collect();
+    }
+    let n = [normal[0] / len, normal[1] / len, normal[2] / len];
+
+    // pick the world axis least aligned with the normal to build the basis
+    let axis = if n[0].abs() <= n[1].abs() && n[0].abs() <= n[2].abs() {
+        [1.0, 0.0, 0.0]
+    } else if n[1].abs() <= n[2].abs() {
+        [0.0, 1.0, 0.0]
+    } else {
+        [0.0, 0.0, 1.0]
+    };
+    let u = normalize(cross(axis, n));
+    let v = cross(n, u);
+
+    points.iter().map(|p| [dot(*p, u), dot(*p, v)]).collect()
+}
+
+fn signed_area(flat: &[[f32; 2]]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..flat.len() {
+        let a = flat[i];
+        let b = flat[(i + 1) % flat.len()];
+        area += a[0] * b[1] - b[0] * a[1];
+    }
+    area * 0.5
+}
+
+fn turn(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - b[1]) - (b[1] - a[1]) * (c[0] - b[0])
+}
+
+/// Counter-clockwise outline with no reflex corners that winds exactly once.
+fn is_convex(flat: &[[f32; 2]], order: &[usize]) -> bool {
+    let n = order.len();
+    let mut total_angle = 0.0f32;
+    for k in 0..n {
+        let a = flat[order[k]];
+        let b = flat[order[(k + 1) % n]];
+        let c = flat[order[(k + 2) % n]];
+        if turn(a, b, c) < -EPS {
+            return false;
+        }
+        let d1 = [b[0] - a[0], b[1] - a[1]];
+        let d2 = [c[0] - b[0], c[1] - b[1]];
+        total_angle += (d1[0] * d2[1] - d1[1] * d2[0]).atan2(d1[0] * d2[0] + d1[1] * d2[1]);
+    }
+    (total_angle - std::f32::consts::TAU).abs() < 0.1
+}
+
+fn ear_clip(flat: &[[f32; 2]], mut order: Vec<usize>) -> Vec<[usize; 3]> {
+    let mut tris = Vec::with_capacity(order.len() - 2);
+
+    while order.len() > 3 {
+        let n = order.len();
+        let ear = (0..n).find(|&k| is_ear(flat, &order, k)).unwrap_or_else(|| {
+            // no clean ear: self-intersecting input, take the sharpest convex corner
+            (0..n)
+                .max_by(|&x, &y| {
+                    corner_turn(flat, &order, x).total_cmp(&corner_turn(flat, &order, y))
+                })
+                .unwrap()
+        });
+
+        let prev = order[(ear + n - 1) % n];
+        let next = order[(ear + 1) % n];
+        tris.push([prev, order[ear], next]);
+        order.remove(ear);
+    }
+
+    tris.push([order[0], order[1], order[2]]);
+    tris
+}
+
+fn corner_turn(flat: &[[f32; 2]], order: &[usize], k: usize) -> f32 {
+    let n = order.len();
+    turn(flat[order[(k + n - 1) % n]], flat[order[k]], flat[order[(k + 1) % n]])
+}
+
+fn is_ear(flat: &[[f32; 2]], order: &[usize], k: usize) -> bool {
+    let n = order.len();
+    let a = flat[order[(k + n - 1) % n]];
+    let b = flat[order[k]];
+    let c = flat[order[(k + 1) % n]];
+
+    if turn(a, b, c) <= EPS {
+        return false;
+    }
+
+    order.iter().enumerate().all(|(j, &idx)| {
+        if j == k || j == (k + 1) % n || j == (k + n - 1) % n {
+            return true;
+        }
+        let p = flat[idx];
+        // corners repeated at the same position (bridged holes) don't block an ear
+        if p == a || p == b || p == c {
+            return true;
+        }
+        !inside_triangle(p, a, b, c)
+    })
+}
+
+fn inside_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    turn(a, b, p) >= 0.0 && turn(b, c, p) >= 0.0 && turn(c, a, p) >= 0.0
+}
@@ -0,0 +1,86 @@
+// Spatial-hash vertex welder.
+//
+// Positions are bucketed on a grid whose cell size is the position epsilon.
+// Two vertices within epsilon of each other are at most one cell apart on
+// each axis, so looking up the 27 surrounding cells finds every candidate
+// even when the values straddle a cell boundary.
+
+use hashbrown::HashMap;
+use rwx_parser::Vertex;
+
+use crate::NormalizeOptions;
+
+/// Smallest cell size, so an epsilon of zero still hashes sensibly.
+const MIN_CELL: f32 = 1e-6;
+
+pub(crate) struct Welder<'a> {
+    opts: &'a NormalizeOptions,
+    cell: f32,
+    grid: HashMap<(u32, i64, i64, i64), Vec<u32>>,
+    pub(crate) vertices: Vec<Vertex>,
+}
+
+impl<'a> Welder<'a> {
+    pub(crate) fn new(opts: &'a NormalizeOptions) -> Self {
+        Welder {
+            opts,
+            cell: opts.position_epsilon.max(MIN_CELL),
+            grid: HashMap::new(),
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Returns the output index for `v`, reusing an existing vertex of the
+    /// same `group` when every attribute is within tolerance.
+    pub(crate) fn insert(&mut self, v: &Vertex, group: u32) -> u32 {
+        let (cx, cy, cz) = self.cell_of(v);
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(bucket) = self.grid.get(&(group, cx + dx, cy + dy, cz + dz)) {
+                        if let Some(&found) = bucket.iter().find(|&&i| self.matches(&self.vertices[i as usize], v)) {
+                            return found;
+                        }
+                    }
+                }
+            }
+        }
+
+        let index = self.vertices.len() as u32;
+        self.vertices.push(v.clone());
+        self.grid.entry((group, cx, cy, cz)).or_default().push(index);
+        index
+    }
+
+    fn cell_of(&self, v: &Vertex) -> (i64, i64, i64) {
+        (
+            (v.x / self.cell).floor() as i64,
+            (v.y / self.cell).floor() as i64,
+            (v.z / self.cell).floor() as i64,
+        )
+    }
+
+    fn matches(&self, a: &Vertex, b: &Vertex) -> bool {
+        let o = self.opts;
+        close(a.x, b.x, o.position_epsilon)
+            && close(a.y, b.y, o.position_epsilon)
+            && close(a.z, b.z, o.position_epsilon)
+            && close(a.u, b.u, o.uv_epsilon)
+            && close(a.v, b.v, o.uv_epsilon)
+            && close_opt(a.normal, b.normal, o.normal_epsilon)
+            && close_opt(a.color, b.color, o.color_epsilon)
+    }
+}
+
+fn close(a: f32, b: f32, eps: f32) -> bool {
+    (a - b).abs() <= eps
+}
+
+fn close_opt(a: Option<[f32; 3]>, b: Option<[f32; 3]>, eps: f32) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (0..3).all(|k| close(a[k], b[k], eps)),
+        (None, None) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::{flat, vertex};
+use rwx_normalizer::{batch, BatchOptions, NormalizeOptions, Submesh};
+use rwx_parser::{Face, Material};
+use rwx_semantics::FlatMesh;
+
+/// A one-triangle clump at height `y` drawn with `material`.
+fn clump(name: &str, y: f32, material: &str) -> FlatMesh {
+    let mut mesh = flat(vec![vertex(0.0, y, 0.0), vertex(0.0, y, 1.0), vertex(1.0, y, 0.0)], vec![vec![1, 2, 3]]);
+    mesh.name = name.to_string();
+    mesh.faces = vec![Face::Poly(vec![1, 2, 3], material.to_string())];
+    mesh.materials = ["NULL", "wood"].map(|n| Material { name: n.to_string(), ..Default::default() }).to_vec();
+    mesh
+}
+
+#[test]
+fn clumps_merge_into_one_submesh_per_material() {
+    let clumps = [clump("seat", 0.0, "wood"), clump("cushion", 1.0, "NULL"), clump("back", 2.0, "wood")];
+    let batches = batch(&clumps, &NormalizeOptions::default(), &BatchOptions::default());
+
+    assert_eq!(batches.len(), 1);
+    let b = &batches[0];
+    assert_eq!(b.name, "seat");
+    assert!(b.collision);
+    assert_eq!(b.reports.len(), 3);
+    assert_eq!(
+        b.submeshes,
+        [
+            Submesh { material: "wood".to_string(), first_face: 0, face_count: 2 },
+            Submesh { material: "NULL".to_string(), first_face: 2, face_count: 1 },
+        ]
+    );
+}
+
+#[test]
+fn walk_through_clumps_stay_apart() {
+    let mut foliage = clump("leaves", 1.0, "wood");
+    foliage.collision = false;
+    let clumps = [clump("trunk", 0.0, "wood"), foliage, clump("branch", 2.0, "wood")];
+
+    // whatever else is split, solid and walk-through geometry never share a batch
+    for opts in [BatchOptions::default(), BatchOptions { split_tagged: true }] {
+        let batches = batch(&clumps, &NormalizeOptions::default(), &opts);
+        let summary: Vec<_> = batches.iter().map(|b| (b.name.as_str(), b.collision, b.mesh.faces.len())).collect();
+        assert_eq!(summary, [("trunk", true, 2), ("leaves", false, 1)]);
+    }
+}
+
+#[test]
+fn tagged_clumps_can_stay_apart() {
+    let mut sign = clump("sign", 1.0, "wood");
+    sign.tag = Some(100);
+    let clumps = [clump("post", 0.0, "wood"), sign, clump("base", 2.0, "wood")];
+
+    let merged = batch(&clumps, &NormalizeOptions::default(), &BatchOptions::default());
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].tag, None);
+
+    let split = batch(&clumps, &NormalizeOptions::default(), &BatchOptions { split_tagged: true });
+    let tags: Vec<_> = split.iter().map(|b| (b.name.as_str(), b.tag)).collect();
+    assert_eq!(tags, [("post", None), ("sign", Some(100))]);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::{flat, vertex};
+use rwx_normalizer::{normalize, RemovalReason};
+use rwx_parser::Material;
+use rwx_semantics::FlatMesh;
+
+fn faces() -> FlatMesh {
+    flat(
+        vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0), vertex(2.0, 0.0, 0.0)],
+        vec![
+            vec![1, 2, 3],
+            // a corner used twice
+            vec![1, 1, 2],
+            // three points on a line
+            vec![1, 2, 4],
+            // the first triangle again, starting elsewhere
+            vec![2, 3, 1],
+            // the first triangle's back
+            vec![1, 3, 2],
+        ],
+    )
+}
+
+#[test]
+fn degenerate_and_duplicate_faces_are_reported() {
+    let mesh = normalize(&faces());
+    let report = &mesh.report;
+    assert_eq!(mesh.faces.len(), 2);
+    assert_eq!(report.clump, "test");
+    assert_eq!(report.count(RemovalReason::RepeatedVertex), 1);
+    assert_eq!(report.count(RemovalReason::ZeroArea), 1);
+    assert_eq!(report.count(RemovalReason::Duplicate), 1);
+    assert_eq!(report.count(RemovalReason::BackToBack), 0);
+
+    let removed: Vec<_> = report.removed_faces.iter().map(|r| (r.source_face, r.reason)).collect();
+    assert_eq!(
+        removed,
+        [(1, RemovalReason::RepeatedVertex), (2, RemovalReason::ZeroArea), (3, RemovalReason::Duplicate)]
+    );
+    assert_eq!(report.removed_faces[2].positions, [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]]);
+    assert!(report.to_string().starts_with("test: 3 removed (1 repeated vertex, 1 zero area, 1 duplicate, 0 back-to-back)"));
+}
+
+#[test]
+fn back_to_back_faces_go_only_when_double_sided() {
+    let mut mesh = faces();
+    mesh.materials[0] = Material { double_sided: true, ..Default::default() };
+    let report = normalize(&mesh).report;
+    assert_eq!(report.count(RemovalReason::BackToBack), 1);
+    assert_eq!(report.removed_faces.last().unwrap().source_face, 4);
+}
+
+#[test]
+fn faces_with_bad_indices_are_reported() {
+    let vertices = vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(0.0, 1.0, 0.0)];
+    // past the last vertex, the 1-based zero, and too few corners
+    let mesh = normalize(&flat(vertices, vec![vec![1, 2, 3], vec![1, 2, 4], vec![0, 1, 2], vec![1, 2]]));
+    assert_eq!(mesh.faces.len(), 1);
+    assert_eq!(mesh.report.invalid_faces, [1, 2, 3]);
+    assert!(!mesh.report.is_empty());
+    assert!(mesh.report.to_string().contains("\n  face 2 skipped: invalid vertex indices\n"));
+}
@@ -0,0 +1,73 @@
+// Meshes shared by the normalizer tests.
+#![allow(dead_code)]
+
+use rwx_normalizer::NormalizedMesh;
+use rwx_parser::{Face, Material, Vertex};
+use rwx_semantics::FlatMesh;
+
+pub fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+    Vertex { x, y, z, u: 0.0, v: 0.0, normal: None, tangent: None, color: None }
+}
+
+pub fn uv_vertex(x: f32, y: f32, z: f32, u: f32, v: f32) -> Vertex {
+    Vertex { u, v, ..vertex(x, y, z) }
+}
+
+/// A clump with 1-based `faces` into `vertices`, all with the default
+/// material.
+pub fn flat(vertices: Vec<Vertex>, faces: Vec<Vec<u32>>) -> FlatMesh {
+    FlatMesh {
+        name: "test".to_string(),
+        vertices,
+        faces: faces.into_iter().map(|f| Face::Poly(f, "NULL".to_string())).collect(),
+        materials: vec![Material::default()],
+        tag: None,
+        collision: true,
+    }
+}
+
+/// Unit cube with one quad per side, wound outward.
+pub fn cube() -> FlatMesh {
+    let corners = (0..8).map(|i| vertex((i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32)).collect();
+    let faces = [[1, 3, 4, 2], [5, 6, 8, 7], [1, 2, 6, 5], [3, 7, 8, 4], [1, 5, 7, 3], [2, 4, 8, 6]];
+    flat(corners, faces.iter().map(|f| f.to_vec()).collect())
+}
+
+/// `n` by `n` grid of unit quads in the XZ plane, facing up, with UVs.
+pub fn grid(n: u32) -> FlatMesh {
+    let mut vertices = Vec::new();
+    for z in 0..=n {
+        for x in 0..=n {
+            vertices.push(uv_vertex(x as f32, 0.0, z as f32, x as f32 / n as f32, z as f32 / n as f32));
+        }
+    }
+    let at = |x: u32, z: u32| z * (n + 1) + x + 1;
+    let mut faces = Vec::new();
+    for z in 0..n {
+        for x in 0..n {
+            faces.push(vec![at(x, z), at(x, z + 1), at(x + 1, z + 1), at(x + 1, z)]);
+        }
+    }
+    flat(vertices, faces)
+}
+
+pub fn triangles(mesh: &NormalizedMesh) -> Vec<[u32; 3]> {
+    mesh.faces
+        .iter()
+        .map(|f| {
+            let (Face::Quad(idx, _) | Face::Poly(idx, _)) = f;
+            [idx[0], idx[1], idx[2]]
+        })
+        .collect()
+}
+
+pub fn position(v: &Vertex) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+/// Right-handed normal of a triangle, not normalized.
+pub fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+    let e1 = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let e2 = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    [e1[1] * e2[2] - e1[2] * e2[1], e1[2] * e2[0] - e1[0] * e2[2], e1[0] * e2[1] - e1[1] * e2[0]]
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{face_normal, grid, position, triangles};
+use rwx_normalizer::{normalize_with, CoordinateProfile, NormalizeOptions};
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+#[test]
+fn every_profile_keeps_winding_normals_and_tangents_consistent() {
+    for name in ["rwx", "gltf", "obj", "unity", "godot", "collada", "usd", "z_up"] {
+        let coordinates = CoordinateProfile::by_name(name).unwrap();
+        let mesh = normalize_with(&grid(1), &NormalizeOptions { coordinates, ..Default::default() });
+
+        for [a, b, c] in triangles(&mesh) {
+            let [a, b, c] = [a, b, c].map(|i| &mesh.vertices[i as usize]);
+            let n = face_normal(position(a), position(b), position(c));
+            assert!(dot(n, a.normal.unwrap()) > 0.0, "{name}: winding disagrees with the normals");
+        }
+
+        // the bitangent points the way V grows
+        let at = |u: f32, v: f32| mesh.vertices.iter().find(|x| x.u == u && x.v == v).unwrap();
+        let (start, end) = (position(at(0.0, 0.0)), position(at(0.0, 1.0)));
+        let along_v = [end[0] - start[0], end[1] - start[1], end[2] - start[2]];
+        for v in &mesh.vertices {
+            let t = v.tangent.unwrap();
+            let bitangent = cross(v.normal.unwrap(), [t[0], t[1], t[2]]).map(|x| x * t[3]);
+            assert!(dot(bitangent, along_v) > 0.0, "{name}: bitangent {bitangent:?} against V {along_v:?}");
+        }
+    }
+}
+
+#[test]
+fn unity_mirrors_x_scales_to_metres_and_flips_v() {
+    let opts = NormalizeOptions { coordinates: CoordinateProfile::unity(), ..Default::default() };
+    let mesh = normalize_with(&grid(1), &opts);
+    let corner = mesh.vertices.iter().find(|v| v.x != 0.0 && v.z != 0.0).unwrap();
+    assert_eq!((corner.x, corner.y, corner.z), (-10.0, 0.0, 10.0));
+    // the source corner has UV (1, 1)
+    assert_eq!((corner.u, corner.v), (1.0, 0.0));
+    assert_eq!(corner.normal, Some([0.0, 1.0, 0.0]));
+}
+
+#[test]
+fn z_up_turns_up_into_z() {
+    let opts = NormalizeOptions { coordinates: CoordinateProfile::z_up(), ..Default::default() };
+    let mesh = normalize_with(&grid(1), &opts);
+    assert!(mesh.vertices.iter().all(|v| v.normal == Some([0.0, 0.0, 1.0]) && v.z == 0.0));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{flat, triangles, vertex};
+use rwx_normalizer::{normalize_with, DoubleSidedMode, NormalizeOptions};
+use rwx_parser::{Face, Material};
+use rwx_semantics::FlatMesh;
+
+/// Two triangles side by side, the first with a double-sided material.
+fn leaves() -> FlatMesh {
+    let mut mesh = flat(
+        vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 0.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(2.0, 0.0, 0.0),
+            vertex(3.0, 0.0, 0.0),
+            vertex(2.0, 1.0, 0.0),
+        ],
+        vec![vec![1, 2, 3], vec![4, 5, 6]],
+    );
+    mesh.faces[0] = Face::Poly(vec![1, 2, 3], "leaves".to_string());
+    mesh.materials.push(Material { name: "leaves".to_string(), double_sided: true, ..Default::default() });
+    mesh
+}
+
+fn with(mode: DoubleSidedMode) -> NormalizeOptions {
+    NormalizeOptions { double_sided: mode, ..Default::default() }
+}
+
+#[test]
+fn marked_faces_keep_the_material_flag() {
+    let mesh = normalize_with(&leaves(), &with(DoubleSidedMode::Mark));
+    assert_eq!(mesh.faces.len(), 2);
+    assert!(mesh.materials.iter().find(|m| m.name == "leaves").unwrap().double_sided);
+}
+
+#[test]
+fn duplicated_faces_get_reversed_copies() {
+    let mesh = normalize_with(&leaves(), &with(DoubleSidedMode::Duplicate));
+    assert_eq!(mesh.faces.len(), 3);
+    assert!(mesh.materials.iter().all(|m| !m.double_sided));
+
+    let tris = triangles(&mesh);
+    let [front, back] = [tris[0], tris[2]];
+    let position = |i: u32| {
+        let v = &mesh.vertices[i as usize];
+        [v.x, v.y, v.z]
+    };
+    // same corners, opposite winding and normals
+    assert_eq!([front[0], front[2], front[1]].map(position), back.map(position));
+    for (f, b) in front.iter().zip([back[0], back[2], back[1]]) {
+        let (f, b) = (mesh.vertices[*f as usize].normal.unwrap(), mesh.vertices[b as usize].normal.unwrap());
+        assert_eq!(f, [0.0, 0.0, 1.0]);
+        assert_eq!(b, [0.0, 0.0, -1.0]);
+    }
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use common::{cube, face_normal, position, triangles};
+use rwx_normalizer::{normalize_with, NormalizeOptions};
+
+fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+    (0..3).all(|k| (a[k] - b[k]).abs() < 1e-4)
+}
+
+#[test]
+fn cube_edges_are_creased() {
+    // the cube's faces meet at 90 degrees, over the default 60 degree crease
+    let mesh = normalize_with(&cube(), &NormalizeOptions::default());
+    assert_eq!(mesh.vertices.len(), 24);
+
+    for [a, b, c] in triangles(&mesh) {
+        let [a, b, c] = [a, b, c].map(|i| &mesh.vertices[i as usize]);
+        let n = face_normal(position(a), position(b), position(c));
+        let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        let n = n.map(|x| x / len);
+        for v in [a, b, c] {
+            assert!(close(v.normal.unwrap(), n), "{:?} is not the face normal {n:?}", v.normal);
+        }
+    }
+}
+
+#[test]
+fn edges_below_the_crease_angle_are_smooth() {
+    let opts = NormalizeOptions { crease_angle: 120.0, ..Default::default() };
+    let mesh = normalize_with(&cube(), &opts);
+    assert_eq!(mesh.vertices.len(), 8);
+
+    // every corner normal points diagonally away from the centre
+    let d = 1.0 / 3f32.sqrt();
+    for v in &mesh.vertices {
+        let out = position(v).map(|x| if x > 0.5 { d } else { -d });
+        assert!(close(v.normal.unwrap(), out), "{:?} at {:?}", v.normal, position(v));
+    }
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{grid, position, triangles};
+use rwx_normalizer::{acmr, normalize, normalize_with, optimize, NormalizeOptions, OptimizeOptions, TargetFormat};
+use rwx_parser::Face;
+
+const CACHE: usize = 32;
+
+fn opts() -> OptimizeOptions {
+    OptimizeOptions { cache_size: CACHE, reorder_vertices: false, ..Default::default() }
+}
+
+#[test]
+fn shuffled_triangles_are_reordered() {
+    let mut mesh = normalize(&grid(10));
+    // a fixed permutation that scatters neighbouring triangles
+    let n = mesh.faces.len();
+    mesh.faces = (0..n).map(|i| mesh.faces[i * 67 % n].clone()).collect();
+    let mut expected = triangles(&mesh);
+
+    let report = optimize(&mut mesh, &opts());
+    assert!(report.acmr_after < report.acmr_before, "{report}");
+    assert_eq!(report.acmr_after, acmr(&mesh.faces, CACHE));
+
+    let mut tris = triangles(&mesh);
+    tris.sort();
+    expected.sort();
+    assert_eq!(tris, expected);
+}
+
+#[test]
+fn well_ordered_triangles_are_kept() {
+    let mut mesh = normalize(&grid(10));
+    let original = triangles(&mesh);
+
+    let report = optimize(&mut mesh, &opts());
+    assert_eq!(report.acmr_after, report.acmr_before);
+    assert_eq!(triangles(&mesh), original);
+}
+
+#[test]
+fn vertices_are_renumbered_in_order_of_use() {
+    let mut mesh = normalize(&grid(3));
+    mesh.faces.reverse();
+    let before: Vec<[[f32; 3]; 3]> =
+        triangles(&mesh).iter().map(|t| t.map(|i| position(&mesh.vertices[i as usize]))).collect();
+
+    optimize(&mut mesh, &OptimizeOptions::default());
+    let Face::Poly(first, _) = &mesh.faces[0] else { unreachable!() };
+    assert_eq!(first[..], [0, 1, 2]);
+    for t in triangles(&mesh) {
+        assert!(before.contains(&t.map(|i| position(&mesh.vertices[i as usize]))));
+    }
+}
+
+#[test]
+fn realtime_formats_optimize_during_normalize() {
+    let mesh = normalize_with(&grid(4), &NormalizeOptions::for_format(TargetFormat::Gltf));
+    let report = mesh.report.optimize.expect("glTF output should be optimized");
+    assert!(report.acmr_after <= report.acmr_before);
+    assert!(normalize_with(&grid(4), &NormalizeOptions::for_format(TargetFormat::Obj)).report.optimize.is_none());
+}
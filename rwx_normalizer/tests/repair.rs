@@ -0,0 +1,66 @@
+mod common;
+
+use common::{cube, face_normal, grid, position, triangles};
+use rwx_normalizer::{normalize, normalize_with, NormalizeOptions, NormalizedMesh};
+use rwx_parser::Face;
+
+/// Every triangle of a mesh around (0.5, 0.5, 0.5) faces away from it.
+fn faces_outward(mesh: &NormalizedMesh) -> bool {
+    triangles(mesh).iter().all(|t| {
+        let [a, b, c] = t.map(|i| position(&mesh.vertices[i as usize]));
+        let n = face_normal(a, b, c);
+        let out = [a[0] - 0.5, a[1] - 0.5, a[2] - 0.5];
+        n[0] * out[0] + n[1] * out[1] + n[2] * out[2] > 0.0
+    })
+}
+
+fn reverse(face: &mut Face) {
+    let (Face::Quad(idx, _) | Face::Poly(idx, _)) = face;
+    idx.reverse();
+}
+
+#[test]
+fn a_flipped_face_is_turned_back() {
+    let mut flat = cube();
+    reverse(&mut flat.faces[2]);
+    let mesh = normalize(&flat);
+    assert_eq!(mesh.report.repair.flipped_faces, [2]);
+    assert!(faces_outward(&mesh));
+    assert!(mesh.report.to_string().contains("flipped faces 3\n"));
+}
+
+#[test]
+fn an_inside_out_shell_is_turned_outward() {
+    let mut flat = cube();
+    flat.faces.iter_mut().for_each(reverse);
+    let mesh = normalize(&flat);
+    // reported once per source face, not per triangle
+    assert_eq!(mesh.report.repair.flipped_faces.len(), 6);
+    assert!(faces_outward(&mesh));
+}
+
+#[test]
+fn open_surfaces_follow_the_larger_part() {
+    let mut flat = grid(3);
+    reverse(&mut flat.faces[4]);
+    let mesh = normalize(&flat);
+    assert_eq!(mesh.report.repair.flipped_faces, [4]);
+    assert!(mesh.vertices.iter().all(|v| v.normal == Some([0.0, 1.0, 0.0])));
+}
+
+#[test]
+fn small_holes_are_found_and_filled() {
+    let mut flat = cube();
+    flat.faces.remove(1);
+
+    let found = normalize(&flat).report.repair;
+    assert_eq!(found.holes.len(), 1);
+    assert_eq!(found.holes[0].positions.len(), 4);
+    assert!(!found.holes[0].filled);
+
+    let opts = NormalizeOptions { fill_holes: true, ..Default::default() };
+    let mesh = normalize_with(&flat, &opts);
+    assert!(mesh.report.repair.holes[0].filled);
+    assert_eq!(mesh.faces.len(), 12);
+    assert!(faces_outward(&mesh));
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{flat, grid, position, triangles, vertex};
+use rwx_normalizer::{generate_lods, normalize, simplify};
+use rwx_semantics::FlatMesh;
+
+/// Unit cube with each side an `n` by `n` grid of quads, wound outward.
+fn subdivided_cube(n: u32) -> FlatMesh {
+    // corner, and the two edges of each side whose cross product points out
+    let sides: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        ([0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+        ([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ];
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    for (o, u, v) in sides {
+        let base = vertices.len() as u32 + 1;
+        for j in 0..=n {
+            for i in 0..=n {
+                let (s, t) = (i as f32 / n as f32, j as f32 / n as f32);
+                vertices.push(vertex(o[0] + u[0] * s + v[0] * t, o[1] + u[1] * s + v[1] * t, o[2] + u[2] * s + v[2] * t));
+            }
+        }
+        let at = |i: u32, j: u32| base + j * (n + 1) + i;
+        for j in 0..n {
+            for i in 0..n {
+                faces.push(vec![at(i, j), at(i + 1, j), at(i + 1, j + 1), at(i, j + 1)]);
+            }
+        }
+    }
+    flat(vertices, faces)
+}
+
+#[test]
+fn creased_meshes_simplify() {
+    // hard edges split every vertex along the cube's edges
+    let mesh = normalize(&subdivided_cube(4));
+    assert_eq!(mesh.faces.len(), 192);
+
+    let (lod, _) = simplify(&mesh, 0.1);
+    assert!(lod.faces.len() <= 24, "{} triangles left", lod.faces.len());
+
+    // still a cube: every vertex on its surface, flat-shaded per side, and
+    // every corner kept
+    for v in &lod.vertices {
+        let p = position(v);
+        let n = v.normal.unwrap();
+        let axis = (0..3).find(|&k| n[k].abs() == 1.0).expect("normals stay axis-aligned");
+        assert_eq!(p[axis], if n[axis] > 0.0 { 1.0 } else { 0.0 });
+    }
+    for corner in 0..8 {
+        let p = [corner & 1, corner >> 1 & 1, corner >> 2 & 1].map(|c| c as f32);
+        assert!(lod.vertices.iter().any(|v| position(v) == p), "corner {p:?} was lost");
+    }
+}
+
+#[test]
+fn uv_seams_move_together() {
+    // a grid whose left and right halves are mapped separately
+    let mut flat = grid(8);
+    let seam: Vec<usize> = (0..flat.vertices.len()).filter(|&i| flat.vertices[i].x == 4.0).collect();
+    for &i in &seam {
+        let mut copy = flat.vertices[i].clone();
+        copy.u += 0.5;
+        flat.vertices.push(copy);
+    }
+    let first_copy = flat.vertices.len() - seam.len();
+    for face in &mut flat.faces {
+        let rwx_parser::Face::Poly(idx, _) = face else { unreachable!() };
+        if idx.iter().any(|&i| flat.vertices[i as usize - 1].x > 4.0) {
+            for i in idx.iter_mut() {
+                if let Some(k) = seam.iter().position(|&s| s == *i as usize - 1) {
+                    *i = (first_copy + k + 1) as u32;
+                }
+            }
+        }
+    }
+
+    let mesh = normalize(&flat);
+    let (lod, _) = simplify(&mesh, 0.25);
+    assert!(lod.faces.len() < mesh.faces.len() / 2, "{} triangles left", lod.faces.len());
+    // each side of the seam keeps its own mapping
+    for t in triangles(&lod) {
+        let corners = t.map(|i| &lod.vertices[i as usize]);
+        let right = corners.iter().any(|v| v.x > 4.0);
+        for v in corners {
+            let offset = if v.x == 4.0 && right { 0.5 } else { 0.0 };
+            assert_eq!(v.u, v.x / 8.0 + offset, "corner at x = {}", v.x);
+        }
+    }
+}
+
+#[test]
+fn lod_levels_reach_their_triangle_counts() {
+    let mesh = normalize(&grid(10));
+    let levels = generate_lods(&mesh, &[0.5, 0.25]);
+
+    let counts: Vec<usize> = levels.iter().map(|l| l.mesh.faces.len()).collect();
+    assert_eq!(counts, [200, 100, 50]);
+    // a flat grid simplifies without error and keeps its outline
+    for level in &levels {
+        assert!(level.error < 1e-3);
+        for corner in [[0.0, 0.0, 0.0], [10.0, 0.0, 0.0], [0.0, 0.0, 10.0], [10.0, 0.0, 10.0]] {
+            assert!(level.mesh.vertices.iter().any(|v| position(v) == corner));
+        }
+    }
+}
+
+#[test]
+fn error_is_a_distance() {
+    // a bumpy grid, and the same grid ten times larger
+    let mut bumpy = grid(8);
+    for v in &mut bumpy.vertices {
+        v.y = (v.x * v.z * 0.37).sin() * 0.3;
+    }
+    let mut large = bumpy.clone();
+    for v in &mut large.vertices {
+        (v.x, v.y, v.z) = (v.x * 10.0, v.y * 10.0, v.z * 10.0);
+    }
+
+    let (_, error) = simplify(&normalize(&bumpy), 0.25);
+    let (_, large_error) = simplify(&normalize(&large), 0.25);
+    assert!(error > 0.0 && error < 0.6, "error {error}");
+    // a quadric cost would grow a hundredfold
+    assert!((large_error / error - 10.0).abs() < 0.01, "{large_error} is not ten times {error}");
+}
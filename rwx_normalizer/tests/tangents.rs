@@ -0,0 +1,43 @@
+mod common;
+
+use common::{grid, vertex};
+use rwx_normalizer::normalize;
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn close(a: [f32; 3], b: [f32; 3]) -> bool {
+    (0..3).all(|k| (a[k] - b[k]).abs() < 1e-3)
+}
+
+#[test]
+fn tangents_follow_u_and_handedness_follows_v() {
+    let plain = grid(2);
+    let mut mirrored = grid(2);
+    for v in &mut mirrored.vertices {
+        v.u = 1.0 - v.u;
+    }
+
+    // u runs along +x, then along -x; v runs along +z in both
+    for (flat, along_u, sign) in [(plain, [1.0, 0.0, 0.0], -1.0), (mirrored, [-1.0, 0.0, 0.0], 1.0)] {
+        let mesh = normalize(&flat);
+        for v in &mesh.vertices {
+            let t = v.tangent.expect("every vertex has a tangent");
+            assert!(close([t[0], t[1], t[2]], along_u), "tangent {t:?}");
+            assert_eq!(t[3], sign);
+            let n = v.normal.unwrap();
+            let bitangent = cross(n, [t[0], t[1], t[2]]).map(|x| x * t[3]);
+            assert!(close(bitangent, [0.0, 0.0, 1.0]), "bitangent {bitangent:?}");
+        }
+    }
+}
+
+#[test]
+fn meshes_without_uvs_get_no_tangents() {
+    let flat = common::flat(
+        vec![vertex(0.0, 0.0, 0.0), vertex(0.0, 0.0, 1.0), vertex(1.0, 0.0, 0.0)],
+        vec![vec![1, 2, 3]],
+    );
+    assert!(normalize(&flat).vertices.iter().all(|v| v.tangent.is_none()));
+}
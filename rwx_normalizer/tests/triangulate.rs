@@ -0,0 +1,44 @@
+use rwx_normalizer::{normalize, triangulate};
+
+mod common;
+use common::{face_normal, flat, position, triangles, vertex};
+
+/// 2 by 2 square with its top right quarter cut out, counter-clockwise
+/// in the XY plane; the corner at (1, 1) is reflex.
+const L: [[f32; 3]; 6] = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [2.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 2.0, 0.0], [0.0, 2.0, 0.0]];
+
+fn area(points: &[[f32; 3]], t: [usize; 3]) -> f32 {
+    face_normal(points[t[0]], points[t[1]], points[t[2]])[2] / 2.0
+}
+
+#[test]
+fn concave_polygon_is_ear_clipped() {
+    let tris = triangulate(&L);
+    assert_eq!(tris.len(), 4);
+    // every triangle keeps the outline's winding and together they cover
+    // exactly the L, so none spans the notch
+    assert!(tris.iter().all(|&t| area(&L, t) > 0.0), "{tris:?}");
+    let total: f32 = tris.iter().map(|&t| area(&L, t)).sum();
+    assert!((total - 3.0).abs() < 1e-5);
+}
+
+#[test]
+fn clockwise_polygon_keeps_its_winding() {
+    let reversed: Vec<[f32; 3]> = L.iter().rev().copied().collect();
+    let tris = triangulate(&reversed);
+    assert_eq!(tris.len(), 4);
+    assert!(tris.iter().all(|&t| area(&reversed, t) < 0.0));
+}
+
+#[test]
+fn tilted_polygon_is_projected_to_its_plane() {
+    // the L standing in the YZ plane
+    let tilted: Vec<[f32; 3]> = L.iter().map(|p| [0.0, p[0], p[1]]).collect();
+    let mesh = normalize(&flat(tilted.iter().map(|p| vertex(p[0], p[1], p[2])).collect(), vec![(1..=6).collect()]));
+    let tris = triangles(&mesh);
+    assert_eq!(tris.len(), 4);
+    for t in tris {
+        let [a, b, c] = t.map(|i| position(&mesh.vertices[i as usize]));
+        assert!(face_normal(a, b, c)[0] > 0.0);
+    }
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use common::{flat, uv_vertex, vertex};
+use rwx_normalizer::{normalize_with, NormalizeOptions};
+use rwx_parser::Face;
+
+fn weld_only() -> NormalizeOptions {
+    NormalizeOptions { generate_normals: false, generate_tangents: false, ..Default::default() }
+}
+
+#[test]
+fn positions_across_a_cell_boundary_are_welded() {
+    // 0.49999 and 0.50003 hash to neighbouring cells but are within epsilon
+    let vertices = vec![
+        vertex(0.0, 0.0, 0.0),
+        vertex(0.49999, 0.0, 0.49999),
+        vertex(0.0, 0.0, 1.0),
+        vertex(0.0, 0.0, 0.0),
+        vertex(1.0, 0.0, 0.0),
+        vertex(0.50003, 0.0, 0.50003),
+    ];
+    let mesh = normalize_with(&flat(vertices, vec![vec![1, 2, 3], vec![4, 5, 6]]), &weld_only());
+    assert_eq!(mesh.vertices.len(), 4);
+    assert_eq!(mesh.faces.len(), 2);
+}
+
+#[test]
+fn distant_positions_and_uv_seams_are_kept_apart() {
+    let vertices = vec![
+        uv_vertex(0.0, 0.0, 0.0, 0.0, 0.0),
+        uv_vertex(1.0, 0.0, 0.0, 1.0, 0.0),
+        uv_vertex(0.0, 0.0, 1.0, 0.0, 1.0),
+        // a UV seam: same position, different texture coordinates
+        uv_vertex(1.0, 0.0, 0.0, 0.0, 0.0),
+        uv_vertex(1.0, 0.0, 1.0, 1.0, 1.0),
+        // two epsilons away from the first triangle's corner
+        uv_vertex(0.0002, 0.0, 1.0, 0.0, 1.0),
+    ];
+    let mesh = normalize_with(&flat(vertices, vec![vec![1, 3, 2], vec![4, 6, 5]]), &weld_only());
+    assert_eq!(mesh.vertices.len(), 6);
+}
+
+#[test]
+fn material_boundaries_can_stay_unwelded() {
+    let mut quad = flat(
+        vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(1.0, 0.0, 1.0), vertex(0.0, 0.0, 1.0)],
+        vec![vec![1, 4, 3], vec![1, 3, 2]],
+    );
+    quad.faces[1] = Face::Poly(vec![1, 3, 2], "other".to_string());
+
+    assert_eq!(normalize_with(&quad, &weld_only()).vertices.len(), 4);
+    let split = NormalizeOptions { weld_across_materials: false, ..weld_only() };
+    // the diagonal is shared by both materials
+    assert_eq!(normalize_with(&quad, &split).vertices.len(), 6);
+}
@@ -1,81 +1,441 @@
+use std::fmt;
+
 use rwx_lexer::Token;
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone)]
+/// Vertex position (RWX units) and texture coordinate, with optional
+/// shading attributes.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Vertex {
     pub x: f32,
     pub y: f32,
     pub z: f32,
     pub u: f32,
     pub v: f32,
+    #[serde(default)]
+    pub normal: Option<[f32; 3]>,
+    /// Tangent with the bitangent sign in `w`
+    #[serde(default)]
+    pub tangent: Option<[f32; 4]>,
+    /// Prelight colour
+    #[serde(default)]
+    pub color: Option<[f32; 3]>,
 }
 
-#[derive(Debug, Clone)]
+/// Polygon as vertex indices plus the name of its material. Indices are
+/// 1-based in parsed and scene data and 0-based in normalized meshes.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum Face {
     Quad(Vec<u32>, String),
     Poly(Vec<u32>, String),
 }
 
-#[derive(Debug, Clone)]
-pub struct Material { pub name: String }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum LightSampling {
+    Facet,
+    Vertex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum GeometrySampling {
+    PointCloud,
+    Wireframe,
+    Solid,
+}
 
+/// Clump `AxisAlignment`: how the clump turns to face the viewer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum AxisAlignment {
+    #[default]
+    None,
+    /// Turns about its Y axis so its Z axis points at the viewer
+    ZOrientX,
+    /// Tilts about its X axis so its Z axis points at the viewer
+    ZOrientY,
+    /// Turns freely so its Z axis points at the viewer
+    Xyz,
+}
+
+/// Material state in effect when a face was declared.
+///
+/// Faces refer to materials by `name`, which is the texture name (or `NULL`
+/// when untextured), suffixed with `_2`, `_3`, ... when the same texture is
+/// used with different surface settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Material {
+    pub name: String,
+    pub color: [f32; 3],
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub specular: f32,
+    pub opacity: f32,
+    pub texture: Option<String>,
+    pub mask: Option<String>,
+    pub bump: Option<String>,
+    pub light_sampling: LightSampling,
+    pub geometry_sampling: GeometrySampling,
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: "NULL".to_string(),
+            color: [1.0, 1.0, 1.0],
+            ambient: 0.0,
+            diffuse: 1.0,
+            specular: 0.0,
+            opacity: 1.0,
+            texture: None,
+            mask: None,
+            bump: None,
+            light_sampling: LightSampling::Vertex,
+            geometry_sampling: GeometrySampling::Solid,
+            double_sided: false,
+        }
+    }
+}
+
+impl Material {
+    /// True when both materials render the same, ignoring their names.
+    fn same_surface(&self, other: &Material) -> bool {
+        Material { name: String::new(), ..self.clone() } == Material { name: String::new(), ..other.clone() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RwxMesh {
+    pub vertices: Vec<Vertex>,
+    pub faces: Vec<Face>,
+}
+
+/// A clump (or prototype) with its own vertex list; face indices are
+/// 1-based into that list.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RwxObject {
+    pub name: String,
+    pub mesh: Option<RwxMesh>,
+    pub children: Vec<RwxObject>,
+    /// `Tag n`, used by worlds to find signs, pictures and the like
+    #[serde(default)]
+    pub tag: Option<u32>,
+    /// False after `Collision off`; inherited by child clumps
+    #[serde(default = "collision_default")]
+    pub collision: bool,
+    /// Set on the copy of a prototype placed by `ProtoInstance name`
+    #[serde(default)]
+    pub proto: Option<String>,
+    /// `AxisAlignment`; not inherited by child clumps
+    #[serde(default)]
+    pub axis_alignment: AxisAlignment,
+}
+
+fn collision_default() -> bool {
+    true
+}
+
+/// Parsed model.
+///
+/// `vertices`/`faces` hold every clump merged into one list with 1-based
+/// indices across the whole file; `root` holds the same geometry as a clump
+/// tree with clump-local indices. Prototypes are kept out of both until
+/// `ProtoInstance` places a copy of one in the tree.
 #[derive(Debug, Clone)]
 pub struct RwxModel {
     pub vertices: Vec<Vertex>,
     pub faces: Vec<Face>,
     pub materials: Vec<Material>,
+    pub root: RwxObject,
+    /// `ProtoBegin name` ... `ProtoEnd` definitions, in file order
+    pub prototypes: Vec<RwxObject>,
 }
 
-pub fn parse(tokens: &[Token]) -> RwxModel {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// A `Quad` or `Polygon` ended before all of its vertex indices
+    TruncatedFace { clump: String, command: &'static str, expected: usize, found: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::TruncatedFace { clump, command, expected, found } => {
+                write!(f, "{command} in {clump} has {found} of {expected} vertex indices")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Clump being built, plus the global index of each of its vertices.
+struct OpenClump {
+    object: RwxObject,
+    vertices: Vec<Vertex>,
+    faces: Vec<Face>,
+    global: Vec<u32>,
+    proto: bool,
+}
+
+impl OpenClump {
+    fn new(name: String, collision: bool, proto: bool) -> Self {
+        OpenClump {
+            object: RwxObject { name, mesh: None, children: Vec::new(), tag: None, collision, proto: None, axis_alignment: AxisAlignment::None },
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            global: Vec::new(),
+            proto,
+        }
+    }
+
+    fn finish(mut self) -> RwxObject {
+        if !self.vertices.is_empty() || !self.faces.is_empty() {
+            self.object.mesh = Some(RwxMesh { vertices: self.vertices, faces: self.faces });
+        }
+        self.object
+    }
+
+    fn global_index(&self, local: u32) -> u32 {
+        match self.global.get((local as usize).wrapping_sub(1)) {
+            Some(g) => g + 1,
+            None => local,
+        }
+    }
+}
+
+pub fn parse(tokens: &[Token]) -> Result<RwxModel, ParseError> {
     let mut vertices = Vec::new();
     let mut faces = Vec::new();
-    let mut materials = Vec::new();
-    let mut cur_mat = "NULL".to_string();
+    let mut materials: Vec<Material> = Vec::new();
+
+    let mut state = Material::default();
+    let mut state_stack: Vec<Material> = Vec::new();
+    let mut prototypes: Vec<RwxObject> = Vec::new();
+    let mut clumps = vec![OpenClump::new("model".to_string(), true, false)];
+    let mut clump_count = 0;
 
     let mut i = 0;
     while i < tokens.len() {
         match &tokens[i] {
             Token::Ident(name) => {
-                // texture switching
-                if name == "Texture" {
-                    if let Token::Ident(tex) = &tokens[i+1] {
-                        cur_mat = tex.clone();
-                        materials.push(Material { name: cur_mat.clone() });
-                        i += 2;
-                        continue;
+                let cmd = name.to_ascii_lowercase();
+                i += 1;
+                match cmd.as_str() {
+                    "clumpbegin" | "protobegin" => {
+                        let clump_name = if cmd == "protobegin" {
+                            let n = ident_at(tokens, i).unwrap_or("proto").to_string();
+                            i += 1;
+                            n
+                        } else {
+                            clump_count += 1;
+                            format!("clump_{}", clump_count)
+                        };
+                        state_stack.push(state.clone());
+                        let parent = clumps.last().unwrap();
+                        let proto = cmd == "protobegin" || parent.proto;
+                        clumps.push(OpenClump::new(clump_name, parent.object.collision, proto));
+                    }
+
+                    "clumpend" | "protoend" => {
+                        if let Some(s) = state_stack.pop() {
+                            state = s;
+                        }
+                        if clumps.len() > 1 {
+                            let done = clumps.pop().unwrap().finish();
+                            if cmd == "protoend" {
+                                prototypes.retain(|p| !p.name.eq_ignore_ascii_case(&done.name));
+                                prototypes.push(done);
+                            } else {
+                                clumps.last_mut().unwrap().object.children.push(done);
+                            }
+                        }
                     }
+
+                    // the copy gets its own global vertices so the merged
+                    // lists include every placement
+                    "protoinstance" => {
+                        if let Some(name) = ident_at(tokens, i) {
+                            if let Some(p) = prototypes.iter().find(|p| p.name.eq_ignore_ascii_case(name)) {
+                                let mut copy = p.clone();
+                                copy.proto = Some(p.name.clone());
+                                let clump = clumps.last_mut().unwrap();
+                                if !clump.proto {
+                                    append_global(&copy, &mut vertices, &mut faces);
+                                }
+                                clump.object.children.push(copy);
+                            }
+                            i += 1;
+                        }
+                    }
+
+                    "tag" => {
+                        if let Some(n) = num_at(tokens, i) {
+                            clumps.last_mut().unwrap().object.tag = Some(n as u32);
+                            i += 1;
+                        }
+                    }
+
+                    "axisalignment" => {
+                        let alignment = match ident_at(tokens, i).map(|s| s.to_ascii_lowercase()).as_deref() {
+                            Some("none") => Some(AxisAlignment::None),
+                            Some("zorientx") => Some(AxisAlignment::ZOrientX),
+                            Some("zorienty") => Some(AxisAlignment::ZOrientY),
+                            Some("xyz") => Some(AxisAlignment::Xyz),
+                            _ => None,
+                        };
+                        if let Some(a) = alignment {
+                            clumps.last_mut().unwrap().object.axis_alignment = a;
+                            i += 1;
+                        }
+                    }
+
+                    "collision" => {
+                        match ident_at(tokens, i).map(|s| s.to_ascii_lowercase()).as_deref() {
+                            Some("on") => clumps.last_mut().unwrap().object.collision = true,
+                            Some("off") => clumps.last_mut().unwrap().object.collision = false,
+                            _ => {}
+                        }
+                    }
+
+                    "color" => {
+                        for (k, c) in state.color.iter_mut().enumerate() {
+                            *c = num_at(tokens, i + k).unwrap_or(*c);
+                        }
+                        i = skip_numbers(tokens, i);
+                    }
+
+                    "surface" => {
+                        state.ambient = num_at(tokens, i).unwrap_or(state.ambient);
+                        state.diffuse = num_at(tokens, i + 1).unwrap_or(state.diffuse);
+                        state.specular = num_at(tokens, i + 2).unwrap_or(state.specular);
+                        i = skip_numbers(tokens, i);
+                    }
+
+                    "ambient" | "diffuse" | "specular" | "opacity" => {
+                        if let Some(n) = num_at(tokens, i) {
+                            match cmd.as_str() {
+                                "ambient" => state.ambient = n,
+                                "diffuse" => state.diffuse = n,
+                                "specular" => state.specular = n,
+                                _ => state.opacity = n,
+                            }
+                        }
+                        i = skip_numbers(tokens, i);
+                    }
+
+                    // Texture name [mask name] [bump name]
+                    "texture" => {
+                        if let Some(tex) = ident_at(tokens, i) {
+                            state.texture = if tex.eq_ignore_ascii_case("null") { None } else { Some(tex.to_string()) };
+                            state.mask = None;
+                            state.bump = None;
+                            i += 1;
+                        }
+                        while let (Some(kind), Some(file)) = (ident_at(tokens, i), ident_at(tokens, i + 1)) {
+                            if kind.eq_ignore_ascii_case("mask") {
+                                state.mask = Some(file.to_string());
+                            } else if kind.eq_ignore_ascii_case("bump") {
+                                state.bump = Some(file.to_string());
+                            } else {
+                                break;
+                            }
+                            i += 2;
+                        }
+                    }
+
+                    "lightsampling" => {
+                        match ident_at(tokens, i).map(|s| s.to_ascii_lowercase()).as_deref() {
+                            Some("facet") => state.light_sampling = LightSampling::Facet,
+                            Some("vertex") => state.light_sampling = LightSampling::Vertex,
+                            _ => {}
+                        }
+                    }
+
+                    "geometrysampling" => {
+                        match ident_at(tokens, i).map(|s| s.to_ascii_lowercase()).as_deref() {
+                            Some("pointcloud") => state.geometry_sampling = GeometrySampling::PointCloud,
+                            Some("wireframe") => state.geometry_sampling = GeometrySampling::Wireframe,
+                            Some("solid") => state.geometry_sampling = GeometrySampling::Solid,
+                            _ => {}
+                        }
+                    }
+
+                    "materialmode" | "materialmodes" => {
+                        state.double_sided = false;
+                        while let Some(mode) = ident_at(tokens, i) {
+                            if mode.eq_ignore_ascii_case("double") {
+                                state.double_sided = true;
+                            } else if !mode.eq_ignore_ascii_case("null") && !mode.eq_ignore_ascii_case("none") {
+                                break;
+                            }
+                            i += 1;
+                        }
+                    }
+
+                    "addmaterialmode" | "removematerialmode"
+                        if ident_at(tokens, i).is_some_and(|m| m.eq_ignore_ascii_case("double")) =>
+                    {
+                        state.double_sided = cmd == "addmaterialmode";
+                        i += 1;
+                    }
+
+                    _ => {}
                 }
-                i += 1;
             }
 
-            Token::VertexExt => {
-                let x = get_num(tokens, i+1);
-                let y = get_num(tokens, i+2);
-                let z = get_num(tokens, i+3);
+            // Vertex|VertexExt x y z [UV u v] [Prelight r g b]
+            Token::VertexExt | Token::Vertex => {
+                let x = num_at(tokens, i + 1).unwrap_or(0.0);
+                let y = num_at(tokens, i + 2).unwrap_or(0.0);
+                let z = num_at(tokens, i + 3).unwrap_or(0.0);
+                i = skip_numbers(tokens, i + 1);
 
-                // expect UV token
-                let u = get_num(tokens, i+5);
-                let v = get_num(tokens, i+6);
+                let (mut u, mut v) = (0.0, 0.0);
+                if matches!(tokens.get(i), Some(Token::UV)) {
+                    u = num_at(tokens, i + 1).unwrap_or(0.0);
+                    v = num_at(tokens, i + 2).unwrap_or(0.0);
+                    i = skip_numbers(tokens, i + 1);
+                }
+
+                let mut color = None;
+                if ident_at(tokens, i).is_some_and(|s| s.eq_ignore_ascii_case("prelight")) {
+                    let c = [i + 1, i + 2, i + 3].map(|k| num_at(tokens, k).unwrap_or(0.0));
+                    color = Some(c);
+                    i = skip_numbers(tokens, i + 1);
+                }
 
-                vertices.push(Vertex { x, y, z, u, v });
-                i += 7;
+                let clump = clumps.last_mut().unwrap();
+                let vertex = Vertex { x, y, z, u, v, normal: None, tangent: None, color };
+                if !clump.proto {
+                    clump.global.push(vertices.len() as u32);
+                    vertices.push(vertex.clone());
+                }
+                clump.vertices.push(vertex);
             }
 
             Token::Quad => {
-                let a = get_num(tokens, i+1) as u32;
-                let b = get_num(tokens, i+2) as u32;
-                let c = get_num(tokens, i+3) as u32;
-                let d = get_num(tokens, i+4) as u32;
-                faces.push(Face::Quad(vec![a,b,c,d], cur_mat.clone()));
+                let idx = face_indices(tokens, i + 1, 4).map_err(|found| truncated(&clumps, "Quad", 4, found))?;
+                let mat = intern_material(&mut materials, &state);
+                let clump = clumps.last_mut().unwrap();
+                if !clump.proto {
+                    let global = idx.iter().map(|&ix| clump.global_index(ix)).collect();
+                    faces.push(Face::Quad(global, mat.clone()));
+                }
+                clump.faces.push(Face::Quad(idx, mat));
                 i += 5;
             }
 
             Token::Polygon => {
-                let count = get_num(tokens, i+1) as usize;
-                let mut idx = Vec::new();
-                for k in 0..count {
-                    idx.push(get_num(tokens, i+2+k) as u32);
+                let count = num_at(tokens, i + 1).ok_or_else(|| truncated(&clumps, "Polygon", 1, 0))? as usize;
+                let idx = face_indices(tokens, i + 2, count).map_err(|found| truncated(&clumps, "Polygon", count, found))?;
+                let mat = intern_material(&mut materials, &state);
+                let clump = clumps.last_mut().unwrap();
+                if !clump.proto {
+                    let global = idx.iter().map(|&ix| clump.global_index(ix)).collect();
+                    faces.push(Face::Poly(global, mat.clone()));
                 }
-                faces.push(Face::Poly(idx, cur_mat.clone()));
+                clump.faces.push(Face::Poly(idx, mat));
                 i += 2 + count;
             }
 
@@ -83,9 +443,80 @@ pub fn parse(tokens: &[Token]) -> RwxModel {
         }
     }
 
-    RwxModel { vertices, faces, materials }
+    // close anything left open at end of file
+    while clumps.len() > 1 {
+        let done = clumps.pop().unwrap().finish();
+        clumps.last_mut().unwrap().object.children.push(done);
+    }
+    let root = clumps.pop().unwrap().finish();
+
+    Ok(RwxModel { vertices, faces, materials, root, prototypes })
+}
+
+/// The `count` vertex indices starting at `idx`, or how many there are if
+/// the face ends early.
+fn face_indices(tokens: &[Token], idx: usize, count: usize) -> Result<Vec<u32>, usize> {
+    (0..count).map(|k| num_at(tokens, idx + k).map(|n| n as u32).ok_or(k)).collect()
+}
+
+fn truncated(clumps: &[OpenClump], command: &'static str, expected: usize, found: usize) -> ParseError {
+    let clump = clumps.last().map(|c| c.object.name.clone()).unwrap_or_default();
+    ParseError::TruncatedFace { clump, command, expected, found }
+}
+
+/// Appends the geometry of `obj` and its children to the merged lists.
+fn append_global(obj: &RwxObject, vertices: &mut Vec<Vertex>, faces: &mut Vec<Face>) {
+    if let Some(mesh) = &obj.mesh {
+        let offset = vertices.len() as u32;
+        vertices.extend(mesh.vertices.iter().cloned());
+        for face in &mesh.faces {
+            faces.push(match face {
+                Face::Quad(idx, mat) => Face::Quad(idx.iter().map(|i| i + offset).collect(), mat.clone()),
+                Face::Poly(idx, mat) => Face::Poly(idx.iter().map(|i| i + offset).collect(), mat.clone()),
+            });
+        }
+    }
+    for child in &obj.children {
+        append_global(child, vertices, faces);
+    }
+}
+
+/// Returns the name of the material matching `state`, adding it to
+/// `materials` if it has not been used by a face yet.
+fn intern_material(materials: &mut Vec<Material>, state: &Material) -> String {
+    if let Some(m) = materials.iter().find(|m| m.same_surface(state)) {
+        return m.name.clone();
+    }
+
+    let base = state.texture.clone().unwrap_or_else(|| "NULL".to_string());
+    let mut name = base.clone();
+    let mut n = 1;
+    while materials.iter().any(|m| m.name == name) {
+        n += 1;
+        name = format!("{}_{}", base, n);
+    }
+
+    materials.push(Material { name: name.clone(), ..state.clone() });
+    name
 }
 
-fn get_num(tokens: &[Token], idx: usize) -> f32 {
-    if let Token::Number(n) = tokens[idx] { n } else { 0.0 }
+fn num_at(tokens: &[Token], idx: usize) -> Option<f32> {
+    match tokens.get(idx) {
+        Some(Token::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn ident_at(tokens: &[Token], idx: usize) -> Option<&str> {
+    match tokens.get(idx) {
+        Some(Token::Ident(s)) => Some(s.as_str()),
+        _ => None,
+    }
+}
+
+fn skip_numbers(tokens: &[Token], mut idx: usize) -> usize {
+    while num_at(tokens, idx).is_some() {
+        idx += 1;
+    }
+    idx
 }
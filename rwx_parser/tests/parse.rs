@@ -0,0 +1,88 @@
+use rwx_lexer::lex;
+use rwx_parser::{parse, Face, ParseError, RwxObject};
+
+const LAMP: &str = "
+ModelBegin
+Color 1 0 0
+Surface 0.2 0.6 0.1
+ClumpBegin
+Vertex 0 0 0
+Vertex 1 0 0
+Vertex 1 1 0
+Vertex 0 1 0
+Quad 1 2 3 4
+ClumpBegin
+Texture shade Mask shadem
+Opacity 0.5
+Vertex 0 0 1
+Vertex 1 0 1
+Vertex 1 1 1
+Polygon 3 1 2 3
+ClumpEnd
+Polygon 3 1 2 3
+ClumpBegin
+Texture shade
+Vertex 0 0 2
+Vertex 1 0 2
+Vertex 1 1 2
+Polygon 3 1 2 3
+ClumpEnd
+ClumpEnd
+ModelEnd
+";
+
+fn material(face: &Face) -> &str {
+    match face {
+        Face::Quad(_, m) | Face::Poly(_, m) => m,
+    }
+}
+
+fn faces(obj: &RwxObject) -> Vec<&str> {
+    obj.mesh.as_ref().map_or(vec![], |m| m.faces.iter().map(material).collect())
+}
+
+#[test]
+fn material_state_is_inherited_and_restored() {
+    let model = parse(&lex(LAMP)).unwrap();
+    let lamp = &model.root.children[0];
+    let [shade, plain] = &lamp.children[..] else { panic!("expected two child clumps") };
+
+    // the parent's faces before and after the child use the same material
+    assert_eq!(faces(lamp), ["NULL", "NULL"]);
+    // a texture used with different surface settings gets a suffixed name
+    assert_eq!(faces(shade), ["shade"]);
+    assert_eq!(faces(plain), ["shade_2"]);
+
+    let get = |name: &str| model.materials.iter().find(|m| m.name == name).unwrap();
+    let null = get("NULL");
+    assert_eq!(null.color, [1.0, 0.0, 0.0]);
+    assert_eq!((null.ambient, null.diffuse, null.specular), (0.2, 0.6, 0.1));
+
+    // colour and surface come down from the parent; the child's own state
+    // does not leak into its sibling
+    let masked = get("shade");
+    assert_eq!(masked.color, [1.0, 0.0, 0.0]);
+    assert_eq!(masked.diffuse, 0.6);
+    assert_eq!(masked.mask.as_deref(), Some("shadem"));
+    assert_eq!(masked.opacity, 0.5);
+    let unmasked = get("shade_2");
+    assert_eq!(unmasked.mask, None);
+    assert_eq!(unmasked.opacity, 1.0);
+    assert_eq!(model.materials.len(), 3);
+}
+
+#[test]
+fn truncated_faces_are_errors() {
+    let quad = parse(&lex("ClumpBegin\nVertex 0 0 0\nQuad 1 2\nClumpEnd"));
+    assert_eq!(
+        quad.unwrap_err(),
+        ParseError::TruncatedFace { clump: "clump_1".to_string(), command: "Quad", expected: 4, found: 2 }
+    );
+
+    let polygon = parse(&lex("Vertex 0 0 0\nPolygon 5 1 2 3"));
+    let err = polygon.unwrap_err();
+    assert_eq!(err.to_string(), "Polygon in model has 3 of 5 vertex indices");
+
+    assert!(parse(&lex("Polygon")).is_err());
+    assert!(parse(&lex("Quad 1 2 3 4")).is_ok());
+}
@@ -1,9 +1,16 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
-use rwx_parser::{RwxObject, RwxVertex, RwxFace, RwxMesh};
+use rwx_parser::{AxisAlignment, RwxModel, RwxObject, RwxMesh, Vertex, Face, Material};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub mod material;
+
+pub use material::{AlphaMode, PbrMaterial};
+
+/// Clump transform, applied as scale, then rotate, then translate.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RwxTransform {
     pub translate: Option<[f32; 3]>,
+    /// `[angle_degrees, axis_x, axis_y, axis_z]`
     pub rotate: Option<[f32; 4]>,
     pub scale: Option<[f32; 3]>,
 }
@@ -17,7 +24,7 @@ impl RwxTransform {
         }
     }
 
-    pub fn apply(&self, v: &RwxVertex) -> RwxVertex {
+    pub fn apply(&self, v: &Vertex) -> Vertex {
         let mut x = v.x;
         let mut y = v.y;
         let mut z = v.z;
@@ -31,35 +38,7 @@ impl RwxTransform {
 
         // Rotate (angle-axis)
         if let Some(r) = self.rotate {
-            let angle = r[0].to_radians();
-            let ax = r[1];
-            let ay = r[2];
-            let az = r[3];
-
-            let len = (ax*ax + ay*ay + az*az).sqrt();
-            let (ax, ay, az) = if len != 0.0 {
-                (ax/len, ay/len, az/len)
-            } else {
-                (0.0, 0.0, 0.0)
-            };
-
-            let (sin_a, cos_a) = angle.sin_cos();
-
-            let rx = ax*(ax*x + ay*y + az*z)*(1.0 - cos_a)
-                + x*cos_a
-                + (-az*y + ay*z)*sin_a;
-
-            let ry = ay*(ax*x + ay*y + az*z)*(1.0 - cos_a)
-                + y*cos_a
-                + (az*x - ax*z)*sin_a;
-
-            let rz = az*(ax*x + ay*y + az*z)*(1.0 - cos_a)
-                + z*cos_a
-                + (-ay*x + ax*y)*sin_a;
-
-            x = rx;
-            y = ry;
-            z = rz;
+            (x, y, z) = rotate_axis_angle(r, x, y, z);
         }
 
         // Translate
@@ -69,32 +48,135 @@ impl RwxTransform {
             z += t[2];
         }
 
-        RwxVertex {
+        // Normals follow rotation and the inverse of the scale
+        let normal = v.normal.map(|n| {
+            let (mut nx, mut ny, mut nz) = (n[0], n[1], n[2]);
+            if let Some(s) = self.scale {
+                nx /= s[0];
+                ny /= s[1];
+                nz /= s[2];
+            }
+            if let Some(r) = self.rotate {
+                (nx, ny, nz) = rotate_axis_angle(r, nx, ny, nz);
+            }
+            let len = (nx*nx + ny*ny + nz*nz).sqrt();
+            if len > 0.0 { [nx/len, ny/len, nz/len] } else { n }
+        });
+
+        // Tangents follow rotation; a mirroring scale flips the bitangent
+        let tangent = v.tangent.map(|t| {
+            let (mut tx, mut ty, mut tz) = (t[0], t[1], t[2]);
+            let mut w = t[3];
+            if let Some(s) = self.scale {
+                tx *= s[0];
+                ty *= s[1];
+                tz *= s[2];
+                if s[0] * s[1] * s[2] < 0.0 {
+                    w = -w;
+                }
+            }
+            if let Some(r) = self.rotate {
+                (tx, ty, tz) = rotate_axis_angle(r, tx, ty, tz);
+            }
+            let len = (tx*tx + ty*ty + tz*tz).sqrt();
+            if len > 0.0 { [tx/len, ty/len, tz/len, w] } else { t }
+        });
+
+        Vertex {
             x, y, z,
             u: v.u,
             v: v.v,
+            normal,
+            tangent,
+            color: v.color,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Rotates a point by `[angle_degrees, axis_x, axis_y, axis_z]`.
+fn rotate_axis_angle(r: [f32; 4], x: f32, y: f32, z: f32) -> (f32, f32, f32) {
+    let angle = r[0].to_radians();
+    let ax = r[1];
+    let ay = r[2];
+    let az = r[3];
+
+    let len = (ax*ax + ay*ay + az*az).sqrt();
+    let (ax, ay, az) = if len != 0.0 {
+        (ax/len, ay/len, az/len)
+    } else {
+        (0.0, 0.0, 0.0)
+    };
+
+    let (sin_a, cos_a) = angle.sin_cos();
+
+    let rx = ax*(ax*x + ay*y + az*z)*(1.0 - cos_a)
+        + x*cos_a
+        + (-az*y + ay*z)*sin_a;
+
+    let ry = ay*(ax*x + ay*y + az*z)*(1.0 - cos_a)
+        + y*cos_a
+        + (az*x - ax*z)*sin_a;
+
+    let rz = az*(ax*x + ay*y + az*z)*(1.0 - cos_a)
+        + z*cos_a
+        + (-ay*x + ax*y)*sin_a;
+
+    (rx, ry, rz)
+}
+
+/// A clump: its own vertices, faces indexing them (1-based), a transform
+/// relative to the parent and child clumps.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RwxNode {
     pub name: String,
-    pub vertices: Vec<RwxVertex>,
-    pub faces: Vec<RwxFace>,
+    pub vertices: Vec<Vertex>,
+    pub faces: Vec<Face>,
     pub transform: RwxTransform,
     pub children: Vec<RwxNode>,
+    /// Clump `Tag`, if any
+    #[serde(default)]
+    pub tag: Option<u32>,
+    /// False for clumps with `Collision off`
+    #[serde(default = "collision_default")]
+    pub collision: bool,
+    /// Prototype this node is a `ProtoInstance` of; its geometry and
+    /// children are a copy of the prototype's
+    #[serde(default)]
+    pub proto: Option<String>,
+    /// Clump `AxisAlignment`
+    #[serde(default)]
+    pub axis_alignment: AxisAlignment,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn collision_default() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RwxScene {
     pub root: RwxNode,
+    /// Every material referenced by a face, looked up by `Face` material name
+    pub materials: Vec<Material>,
 }
 
 impl RwxScene {
     pub fn from_object(obj: &RwxObject) -> Self {
         let root = Self::convert_node(obj);
-        RwxScene { root }
+        RwxScene { root, materials: vec![] }
+    }
+
+    pub fn from_model(model: &RwxModel) -> Self {
+        let root = Self::convert_node(&model.root);
+        RwxScene { root, materials: model.materials.clone() }
+    }
+
+    pub fn material(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().find(|m| m.name == name)
+    }
+
+    /// PBR interpretation of every material, in the same order as `materials`
+    pub fn pbr_materials(&self) -> Vec<PbrMaterial> {
+        self.materials.iter().map(PbrMaterial::from_rwx).collect()
     }
 
     fn convert_node(obj: &RwxObject) -> RwxNode {
@@ -104,7 +186,7 @@ impl RwxScene {
         };
 
         let children = obj.children.iter()
-            .map(|c| Self::convert_node(c))
+            .map(Self::convert_node)
             .collect();
 
         RwxNode {
@@ -113,6 +195,10 @@ impl RwxScene {
             faces,
             transform: RwxTransform::identity(), // all nodes use identity
             children,
+            tag: obj.tag,
+            collision: obj.collision,
+            proto: obj.proto.clone(),
+            axis_alignment: obj.axis_alignment,
         }
     }
 
@@ -139,6 +225,10 @@ impl RwxScene {
             faces: node.faces.clone(),
             transform: world_tf.clone(),
             children: vec![],
+            tag: node.tag,
+            collision: node.collision,
+            proto: node.proto.clone(),
+            axis_alignment: node.axis_alignment,
         });
 
         for child in &node.children {
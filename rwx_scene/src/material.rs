@@ -0,0 +1,86 @@
+//! Metallic-roughness interpretation of RWX materials.
+//!
+//! RWX surfaces are Phong-lit: a face is shaded as
+//! `color * (ambient * Ia + diffuse * Id) + specular * Is`. Every exporter
+//! goes through [`PbrMaterial::from_rwx`] so glTF, Unity and Godot agree on
+//! what a surface looks like:
+//!
+//! - **Base colour** is `Color` scaled by `ambient + diffuse` (clamped to 1),
+//!   the brightness a face reaches under full ambient and direct light. A
+//!   textured face ignores `Color`, as the RWX renderer does, and uses the
+//!   texture scaled by the same factor.
+//! - **Alpha** is `Opacity`. Opacity below 1 gives [`AlphaMode::Blend`]; a
+//!   fully opaque surface with a texture mask gives [`AlphaMode::Mask`] with
+//!   the mask as the alpha source; anything else is [`AlphaMode::Opaque`].
+//! - **Metallic** is always 0; RWX has no notion of metals.
+//! - **Roughness** is `1 - 0.6 * specular`. RWX specular is a highlight on a
+//!   diffuse surface rather than a reflection, so even a fully specular
+//!   surface stays at 0.4 instead of becoming a mirror.
+//! - **Normal texture** is the `bump` map. It is a height map, so exporters
+//!   convert it to a normal map with `rwx_texture::height_to_normal`.
+//! - **Double sided** follows `MaterialModes double`.
+//!
+//! `LightSampling` and `GeometrySampling` have no PBR equivalent; faceted
+//! shading is produced through normals and non-solid sampling is exported
+//! as solid.
+
+use schemars::JsonSchema;
+use serde::{Serialize, Deserialize};
+use rwx_parser::Material;
+
+/// Alpha cutoff used for masked textures.
+pub const MASK_CUTOFF: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum AlphaMode {
+    Opaque,
+    Mask { cutoff: f32 },
+    Blend,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PbrMaterial {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<String>,
+    /// Texture whose luminance provides alpha when `alpha_mode` is `Mask`
+    pub alpha_texture: Option<String>,
+    pub normal_texture: Option<String>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl PbrMaterial {
+    pub fn from_rwx(mat: &Material) -> Self {
+        let light = (mat.ambient + mat.diffuse).clamp(0.0, 1.0);
+        let color = if mat.texture.is_some() { [1.0, 1.0, 1.0] } else { mat.color };
+        let opacity = mat.opacity.clamp(0.0, 1.0);
+
+        let alpha_mode = if opacity < 1.0 {
+            AlphaMode::Blend
+        } else if mat.texture.is_some() && mat.mask.is_some() {
+            AlphaMode::Mask { cutoff: MASK_CUTOFF }
+        } else {
+            AlphaMode::Opaque
+        };
+
+        PbrMaterial {
+            name: mat.name.clone(),
+            base_color: [
+                (color[0] * light).clamp(0.0, 1.0),
+                (color[1] * light).clamp(0.0, 1.0),
+                (color[2] * light).clamp(0.0, 1.0),
+                opacity,
+            ],
+            base_color_texture: mat.texture.clone(),
+            alpha_texture: if mat.texture.is_some() { mat.mask.clone() } else { None },
+            normal_texture: if mat.texture.is_some() { mat.bump.clone() } else { None },
+            metallic: 0.0,
+            roughness: 1.0 - 0.6 * mat.specular.clamp(0.0, 1.0),
+            alpha_mode,
+            double_sided: mat.double_sided,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use rwx_parser::Material;
+use rwx_scene::material::MASK_CUTOFF;
+use rwx_scene::{AlphaMode, PbrMaterial};
+
+fn textured(name: &str) -> Material {
+    Material { name: name.to_string(), texture: Some(name.to_string()), ..Default::default() }
+}
+
+#[test]
+fn colour_is_scaled_by_lighting() {
+    let mat = Material { color: [1.0, 0.5, 0.0], ambient: 0.2, diffuse: 0.3, specular: 0.5, ..Default::default() };
+    let pbr = PbrMaterial::from_rwx(&mat);
+    assert_eq!(pbr.base_color, [0.5, 0.25, 0.0, 1.0]);
+    assert_eq!(pbr.metallic, 0.0);
+    assert!((pbr.roughness - 0.7).abs() < 1e-6);
+    assert_eq!(pbr.alpha_mode, AlphaMode::Opaque);
+
+    // ambient and diffuse add up to at most full brightness
+    let bright = Material { ambient: 1.0, diffuse: 1.0, ..Default::default() };
+    assert_eq!(PbrMaterial::from_rwx(&bright).base_color, [1.0; 4]);
+}
+
+#[test]
+fn textures_replace_colour() {
+    let mat = Material { color: [1.0, 0.0, 0.0], diffuse: 0.5, bump: Some("bumps".to_string()), ..textured("wood") };
+    let pbr = PbrMaterial::from_rwx(&mat);
+    assert_eq!(pbr.base_color, [0.5, 0.5, 0.5, 1.0]);
+    assert_eq!(pbr.base_color_texture.as_deref(), Some("wood"));
+    assert_eq!(pbr.normal_texture.as_deref(), Some("bumps"));
+
+    // maps only apply on top of a texture
+    let untextured = Material { mask: Some("m".to_string()), bump: Some("b".to_string()), ..Default::default() };
+    let pbr = PbrMaterial::from_rwx(&untextured);
+    assert_eq!((pbr.alpha_texture, pbr.normal_texture), (None, None));
+    assert_eq!(pbr.alpha_mode, AlphaMode::Opaque);
+}
+
+#[test]
+fn alpha_mode_follows_opacity_and_mask() {
+    let masked = Material { mask: Some("leavesm".to_string()), double_sided: true, ..textured("leaves") };
+    let pbr = PbrMaterial::from_rwx(&masked);
+    assert_eq!(pbr.alpha_mode, AlphaMode::Mask { cutoff: MASK_CUTOFF });
+    assert_eq!(pbr.alpha_texture.as_deref(), Some("leavesm"));
+    assert!(pbr.double_sided);
+
+    // partial opacity blends, even with a mask
+    let faded = Material { opacity: 0.5, ..masked };
+    let pbr = PbrMaterial::from_rwx(&faded);
+    assert_eq!(pbr.alpha_mode, AlphaMode::Blend);
+    assert_eq!(pbr.base_color[3], 0.5);
+}
@@ -1,45 +1,75 @@
-use rwx_parser::{RwxModel, Node, Vertex, Face};
+use rwx_parser::{RwxModel, RwxObject, Vertex, Face, Material};
 
 #[derive(Debug, Clone)]
 pub struct FlatMesh {
+    /// Clump (or model) the geometry came from
+    pub name: String,
     pub vertices: Vec<Vertex>,
     pub faces: Vec<Face>,
+    pub materials: Vec<Material>,
+    /// Clump tag, if any
+    pub tag: Option<u32>,
+    /// Whether the clump takes part in collision
+    pub collision: bool,
 }
 
+/// Merges every clump of the model into one mesh.
+///
+/// Face indices are 1-based into `vertices`, as in the RWX source.
 pub fn flatten(model: &RwxModel) -> FlatMesh {
     let mut out_vertices = Vec::new();
     let mut out_faces = Vec::new();
 
-    flatten_nodes(&model.nodes, &mut out_vertices, &mut out_faces);
+    flatten_object(&model.root, &mut out_vertices, &mut out_faces);
 
     FlatMesh {
+        name: model.root.name.clone(),
         vertices: out_vertices,
         faces: out_faces,
+        materials: model.materials.clone(),
+        tag: model.root.tag,
+        collision: model.root.collision,
     }
 }
 
-fn flatten_nodes(nodes: &[Node], verts: &mut Vec<Vertex>, faces: &mut Vec<Face>) {
-    for n in nodes {
-        match n {
-            Node::Vertex(v) => {
-                verts.push(v.clone());
-            }
+/// One mesh per clump that has geometry, without its children's geometry.
+pub fn flatten_clumps(model: &RwxModel) -> Vec<FlatMesh> {
+    let mut out = Vec::new();
+    collect_clumps(&model.root, &model.materials, &mut out);
+    out
+}
 
-            Node::Face(f) => {
-                faces.push(f.clone());
-            }
+fn collect_clumps(obj: &RwxObject, materials: &[Material], out: &mut Vec<FlatMesh>) {
+    if let Some(mesh) = &obj.mesh {
+        out.push(FlatMesh {
+            name: obj.name.clone(),
+            vertices: mesh.vertices.clone(),
+            faces: mesh.faces.clone(),
+            materials: materials.to_vec(),
+            tag: obj.tag,
+            collision: obj.collision,
+        });
+    }
 
-            Node::Block(b) => {
-                flatten_nodes(b, verts, faces);
-            }
+    for child in &obj.children {
+        collect_clumps(child, materials, out);
+    }
+}
 
-            Node::Transform(_) => {
-                continue;
-            }
+fn flatten_object(obj: &RwxObject, verts: &mut Vec<Vertex>, faces: &mut Vec<Face>) {
+    if let Some(mesh) = &obj.mesh {
+        let offset = verts.len() as u32;
+        verts.extend(mesh.vertices.iter().cloned());
 
-            Node::Material(_) => {
-                continue;
-            }
+        for f in &mesh.faces {
+            faces.push(match f {
+                Face::Quad(idx, mat) => Face::Quad(idx.iter().map(|i| i + offset).collect(), mat.clone()),
+                Face::Poly(idx, mat) => Face::Poly(idx.iter().map(|i| i + offset).collect(), mat.clone()),
+            });
         }
     }
+
+    for child in &obj.children {
+        flatten_object(child, verts, faces);
+    }
 }
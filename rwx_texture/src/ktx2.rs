@@ -0,0 +1,73 @@
+// KTX2 writing, for one uncompressed sRGB RGBA8 image with no mipmaps.
+//
+// Layout: identifier, header, index, one level index entry, the data
+// format descriptor, then the pixels, rows from the top as KTX2 expects by
+// default. There is no key/value data and no supercompression.
+
+use image::RgbaImage;
+
+const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+
+// identifier, header (9 u32), index (4 u32 + 2 u64), one level (3 u64)
+const DFD_OFFSET: u32 = 12 + 9 * 4 + 4 * 4 + 2 * 8 + 3 * 8;
+
+// basic descriptor block: 6 words plus 4 words per channel
+const DFD_BLOCK_SIZE: u32 = 24 + 4 * 16;
+
+/// `image` as a KTX2 file.
+pub fn encode_ktx2(image: &RgbaImage) -> Vec<u8> {
+    let pixels = image.as_raw();
+    let dfd = data_format_descriptor();
+    let level_offset = (DFD_OFFSET as usize + dfd.len()).next_multiple_of(4);
+
+    let mut out = Vec::with_capacity(level_offset + pixels.len());
+    out.extend_from_slice(&IDENTIFIER);
+    for word in [
+        VK_FORMAT_R8G8B8A8_SRGB,
+        1, // typeSize
+        image.width(),
+        image.height(),
+        0, // pixelDepth
+        0, // layerCount
+        1, // faceCount
+        1, // levelCount
+        0, // supercompressionScheme
+    ] {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+
+    // dfd offset and length, kvd offset and length
+    for word in [DFD_OFFSET, dfd.len() as u32, 0, 0] {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+    // sgd offset and length
+    out.extend_from_slice(&[0; 16]);
+    // level 0: offset, length, uncompressed length
+    for word in [level_offset as u64, pixels.len() as u64, pixels.len() as u64] {
+        out.extend_from_slice(&word.to_le_bytes());
+    }
+
+    out.extend_from_slice(&dfd);
+    out.resize(level_offset, 0);
+    out.extend_from_slice(pixels);
+    out
+}
+
+fn data_format_descriptor() -> Vec<u8> {
+    let mut words = vec![
+        4 + DFD_BLOCK_SIZE,       // dfdTotalSize
+        0,                        // vendor 0 (Khronos), descriptor type 0 (basic)
+        2 | DFD_BLOCK_SIZE << 16, // version 1.3, block size
+        1 | 1 << 8 | 2 << 16,     // RGBSDA model, BT.709 primaries, sRGB transfer, straight alpha
+        0,                        // 1x1x1 texel blocks
+        4,                        // 4 bytes per texel in plane 0
+        0,
+    ];
+    // R, G, B, then A, which stays linear under the sRGB transfer
+    for (channel, offset) in [(0u32, 0u32), (1, 8), (2, 16), (15 | 0x10, 24)] {
+        words.extend([offset | 7 << 16 | channel << 24, 0, 0, 255]);
+    }
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
@@ -0,0 +1,201 @@
+// Texture conversion for exporters.
+//
+// RWX worlds ship textures as JPG or BMP with the transparency in a
+// separate mask, usually a black and white BMP: white is opaque, black is
+// cut out. Engines expect one image with an alpha channel, so a masked
+// texture is merged into a single RGBA image whose alpha is the mask's
+// luminance, then written as PNG or as uncompressed KTX2.
+//
+// A mask of a different size than its texture is scaled to fit. Power of
+// two resizing picks the nearest power of two on each side, for engines
+// and GPUs that need it for mipmaps or wrapping.
+//
+// Bump maps are greyscale height maps, brighter being higher, while glTF
+// and engines want tangent-space normal maps, so a bump map is turned into
+// one from the slopes of its luminance.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor};
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgba, RgbaImage};
+
+mod ktx2;
+
+pub use ktx2::encode_ktx2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextureFormat {
+    #[default]
+    Png,
+    /// KTX2, uncompressed sRGB RGBA8
+    Ktx2,
+}
+
+impl TextureFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TextureFormat::Png => "png",
+            TextureFormat::Ktx2 => "ktx2",
+        }
+    }
+
+    /// The format a file name's extension asks for.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "png" => Some(TextureFormat::Png),
+            "ktx2" => Some(TextureFormat::Ktx2),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TextureOptions {
+    pub format: TextureFormat,
+    /// Resize each side to the nearest power of two
+    pub power_of_two: bool,
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io(io::Error),
+    /// The data is not an image in a supported format
+    Image(image::ImageError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Io(e) => write!(f, "{e}"),
+            TextureError::Image(e) => write!(f, "cannot decode image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Io(e) => Some(e),
+            TextureError::Image(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for TextureError {
+    fn from(e: io::Error) -> Self {
+        TextureError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for TextureError {
+    fn from(e: image::ImageError) -> Self {
+        TextureError::Image(e)
+    }
+}
+
+/// Name of the file a texture converts to, e.g. `leaves_leavesm.png` for
+/// texture `leaves` with mask `leavesm`.
+pub fn converted_name(texture: &str, mask: Option<&str>, format: TextureFormat) -> String {
+    match mask {
+        Some(mask) => format!("{texture}_{mask}.{}", format.extension()),
+        None => format!("{texture}.{}", format.extension()),
+    }
+}
+
+/// Decodes a PNG, JPEG or BMP image.
+pub fn load(data: &[u8]) -> Result<RgbaImage, TextureError> {
+    Ok(image::load_from_memory(data)?.to_rgba8())
+}
+
+/// Replaces `base`'s alpha with the luminance of `mask`, scaled to fit.
+pub fn merge_mask(base: &mut RgbaImage, mask: &RgbaImage) {
+    let scaled;
+    let mask = if mask.dimensions() == base.dimensions() {
+        mask
+    } else {
+        scaled = imageops::resize(mask, base.width(), base.height(), FilterType::Triangle);
+        &scaled
+    };
+    for (pixel, m) in base.pixels_mut().zip(mask.pixels()) {
+        pixel.0[3] = luma(m.0);
+    }
+}
+
+/// Tangent-space normal map of the height map `height`: X to the right, Y
+/// up the image and Z out of the surface, as glTF expects. `strength`
+/// scales the slopes, 0 giving a flat map. The edges wrap around, as the
+/// texture tiles.
+pub fn height_to_normal(height: &RgbaImage, strength: f32) -> RgbaImage {
+    let (w, h) = (height.width() as i64, height.height() as i64);
+    let heights: Vec<f32> = height.pixels().map(|p| luma(p.0) as f32 / 255.0).collect();
+    let at = |x: i64, y: i64| heights[(y.rem_euclid(h) * w + x.rem_euclid(w)) as usize];
+
+    RgbaImage::from_fn(height.width(), height.height(), |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        // central differences; rows run down the image, Y runs up
+        let dx = (at(x + 1, y) - at(x - 1, y)) * 0.5 * strength;
+        let dy = (at(x, y - 1) - at(x, y + 1)) * 0.5 * strength;
+        let len = (dx * dx + dy * dy + 1.0).sqrt();
+        let encode = |c: f32| ((c / len * 0.5 + 0.5) * 255.0).round() as u8;
+        Rgba([encode(-dx), encode(-dy), encode(1.0), 255])
+    })
+}
+
+/// Rec. 601 luma, as masks and bump maps are greyscale in practice.
+fn luma([r, g, b, _]: [u8; 4]) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8
+}
+
+/// `image` resized so each side is the nearest power of two.
+pub fn resize_power_of_two(image: &RgbaImage) -> RgbaImage {
+    let (w, h) = (nearest_power_of_two(image.width()), nearest_power_of_two(image.height()));
+    if (w, h) == image.dimensions() {
+        return image.clone();
+    }
+    imageops::resize(image, w, h, FilterType::CatmullRom)
+}
+
+/// The power of two closest to `n`, rounding up on a tie.
+pub fn nearest_power_of_two(n: u32) -> u32 {
+    if n <= 1 {
+        return 1;
+    }
+    let upper = n.next_power_of_two();
+    let lower = upper / 2;
+    if n - lower < upper - n { lower } else { upper }
+}
+
+pub fn encode(image: &RgbaImage, format: TextureFormat) -> Result<Vec<u8>, TextureError> {
+    match format {
+        TextureFormat::Png => {
+            let mut out = Cursor::new(Vec::new());
+            image.write_to(&mut out, ImageFormat::Png)?;
+            Ok(out.into_inner())
+        }
+        TextureFormat::Ktx2 => Ok(encode_ktx2(image)),
+    }
+}
+
+/// Decodes `base`, merges `mask` into its alpha if given, and encodes the
+/// result as `opts` asks.
+pub fn convert(base: &[u8], mask: Option<&[u8]>, opts: &TextureOptions) -> Result<Vec<u8>, TextureError> {
+    let mut image = load(base)?;
+    if let Some(mask) = mask {
+        merge_mask(&mut image, &load(mask)?);
+    }
+    if opts.power_of_two {
+        image = resize_power_of_two(&image);
+    }
+    encode(&image, opts.format)
+}
+
+/// [`convert`] from and to files.
+pub fn convert_file(base: &Path, mask: Option<&Path>, out: &Path, opts: &TextureOptions) -> Result<(), TextureError> {
+    let mask = mask.map(fs::read).transpose()?;
+    let data = convert(&fs::read(base)?, mask.as_deref(), opts)?;
+    fs::write(out, data)?;
+    Ok(())
+}
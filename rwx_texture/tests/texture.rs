@@ -0,0 +1,122 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Rgba, RgbaImage};
+use rwx_texture::{
+    convert, converted_name, encode_ktx2, height_to_normal, load, merge_mask, nearest_power_of_two,
+    resize_power_of_two, TextureFormat, TextureOptions,
+};
+
+fn encoded(image: &RgbaImage, format: ImageFormat) -> Vec<u8> {
+    let mut out = Cursor::new(Vec::new());
+    if format == ImageFormat::Png {
+        image.write_to(&mut out, format).unwrap();
+    } else {
+        // JPEG and BMP as RWX ships them, without alpha
+        image::DynamicImage::ImageRgba8(image.clone()).to_rgb8().write_to(&mut out, format).unwrap();
+    }
+    out.into_inner()
+}
+
+/// 4x2 texture, left half red and right half blue.
+fn base() -> RgbaImage {
+    RgbaImage::from_fn(4, 2, |x, _| if x < 2 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) })
+}
+
+/// 2x1 mask: white (opaque) on the left, black (cut out) on the right.
+fn mask() -> RgbaImage {
+    RgbaImage::from_fn(2, 1, |x, _| if x == 0 { Rgba([255; 4]) } else { Rgba([0, 0, 0, 255]) })
+}
+
+#[test]
+fn mask_becomes_alpha() {
+    let mut image = base();
+    merge_mask(&mut image, &mask());
+
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(0, 1).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(3, 0).0, [0, 0, 255, 0]);
+    assert_eq!(image.get_pixel(3, 1).0, [0, 0, 255, 0]);
+}
+
+#[test]
+fn height_becomes_normal() {
+    let flat = height_to_normal(&RgbaImage::from_pixel(3, 3, Rgba([90, 90, 90, 255])), 4.0);
+    assert!(flat.pixels().all(|p| p.0 == [128, 128, 255, 255]));
+
+    // rising to the right, then rising up the image
+    let ramp = RgbaImage::from_fn(3, 3, |x, _| Rgba([[0, 100, 200][x as usize]; 4]));
+    let [r, g, b, _] = height_to_normal(&ramp, 4.0).get_pixel(1, 1).0;
+    assert!(r < 128 && g == 128 && b > 128 && b < 255);
+    let ramp = RgbaImage::from_fn(3, 3, |_, y| Rgba([[200, 100, 0][y as usize]; 4]));
+    let [r, g, _, _] = height_to_normal(&ramp, 4.0).get_pixel(1, 1).0;
+    assert!(r == 128 && g < 128);
+}
+
+#[test]
+fn converts_bmp_with_mask_to_png() {
+    let data = convert(
+        &encoded(&base(), ImageFormat::Bmp),
+        Some(&encoded(&mask(), ImageFormat::Bmp)),
+        &TextureOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::Png);
+    let image = load(&data).unwrap();
+    assert_eq!(image.dimensions(), (4, 2));
+    assert_eq!(image.get_pixel(0, 0).0[3], 255);
+    assert_eq!(image.get_pixel(3, 1).0[3], 0);
+}
+
+#[test]
+fn resizes_to_nearest_power_of_two() {
+    assert_eq!(nearest_power_of_two(1), 1);
+    assert_eq!(nearest_power_of_two(5), 4);
+    assert_eq!(nearest_power_of_two(6), 8);
+    assert_eq!(nearest_power_of_two(200), 256);
+    assert_eq!(nearest_power_of_two(256), 256);
+
+    let image = RgbaImage::from_pixel(5, 3, Rgba([10, 20, 30, 255]));
+    let resized = resize_power_of_two(&image);
+    assert_eq!(resized.dimensions(), (4, 4));
+    assert_eq!(resized.get_pixel(2, 2).0, [10, 20, 30, 255]);
+
+    let opts = TextureOptions { power_of_two: true, ..Default::default() };
+    let data = convert(&encoded(&image, ImageFormat::Png), None, &opts).unwrap();
+    assert_eq!(load(&data).unwrap().dimensions(), (4, 4));
+}
+
+#[test]
+fn ktx2_is_readable() {
+    let mut image = base();
+    merge_mask(&mut image, &mask());
+    let data = encode_ktx2(&image);
+
+    let reader = ktx2::Reader::new(&data).unwrap();
+    let header = reader.header();
+    assert_eq!(header.format, Some(ktx2::Format::R8G8B8A8_SRGB));
+    assert_eq!((header.pixel_width, header.pixel_height), (4, 2));
+    assert_eq!(header.level_count, 1);
+
+    let levels: Vec<_> = reader.levels().collect();
+    assert_eq!(levels.len(), 1);
+    assert_eq!(levels[0].data, image.as_raw().as_slice());
+
+    let block = reader.dfd_blocks().next().unwrap();
+    let basic = ktx2::DfdBlockBasic::parse(block.data).unwrap();
+    assert_eq!(basic.header.transfer_function, Some(ktx2::TransferFunction::SRGB));
+    let samples: Vec<_> = basic.sample_information().collect();
+    assert_eq!(samples.len(), 4);
+    assert_eq!(samples[3].channel_type, 15);
+    assert!(samples[3].channel_type_qualifiers.contains(ktx2::ChannelTypeQualifiers::LINEAR));
+    assert!(samples.iter().all(|s| s.bit_length.get() == 8));
+
+    let opts = TextureOptions { format: TextureFormat::Ktx2, ..Default::default() };
+    assert_eq!(convert(&encoded(&image, ImageFormat::Png), None, &opts).unwrap(), data);
+}
+
+#[test]
+fn converted_names() {
+    assert_eq!(converted_name("leaves", Some("leavesm"), TextureFormat::Png), "leaves_leavesm.png");
+    assert_eq!(converted_name("wood", None, TextureFormat::Ktx2), "wood.ktx2");
+}
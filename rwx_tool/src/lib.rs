@@ -1 +0,0 @@
-pub mod export_obj;
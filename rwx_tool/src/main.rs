@@ -1,41 +1,545 @@
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
 
+use rwx_archive::{read_rwx, ObjectLibrary};
 use rwx_lexer::lex;
 use rwx_parser::parse;
+use rwx_normalizer::{
+    batch, duplicate_back_faces, generate_lods, BatchOptions, normalize, normalize_with, DoubleSidedMode, NormalizeOptions, TargetFormat,
+};
+use rwx_export::{
+    export_collada, export_godot, export_gltf, export_mesh_obj, export_obj, export_ply, export_stl, export_usd, scene_schema,
+    write_scene_json, ColladaOptions, GltfOptions, GodotOptions, ObjOptions, PlyOptions, StlOptions, UsdOptions,
+};
+use rwx_scene::RwxScene;
+use rwx_semantics::{flatten, flatten_clumps};
+use rwx_texture::{convert_file, TextureFormat, TextureOptions};
+use rwx_unity::{package_prefab, write_prefab, LodGroup, PrefabOptions};
 
-// import the function from the module
-use crate::export_obj::export_obj;
-
-mod export_obj;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    match args.get(1).map(String::as_str) {
+        Some("lod") => return lod(&args[2..]),
+        Some("to-gltf") => return to_gltf(&args[2..]),
+        Some("to-json") => return to_json(&args[2..]),
+        Some("to-dae") => return to_dae(&args[2..]),
+        Some("to-usd") => return to_usd(&args[2..]),
+        Some("to-godot") => return to_godot(&args[2..]),
+        Some("to-stl") => return to_stl(&args[2..]),
+        Some("to-ply") => return to_ply(&args[2..]),
+        Some("to-unity") => return to_unity(&args[2..]),
+        Some("texture") => return texture(&args[2..]),
+        Some("library") => return library(&args[2..]),
+        _ => {}
+    }
+
     if args.len() < 3 {
-        eprintln!("Usage: rwx_tool <input.rwx> <output_base> [--debug]");
+        eprintln!("Usage: rwx_tool <input.rwx> <output_base> [--debug] [--report] [--batch] [--textures dir]");
+        eprintln!("       rwx_tool lod --levels 0.5,0.25 <input.rwx> <output_base>");
+        eprintln!("       rwx_tool to-gltf <input.rwx> <output.gltf|output.glb> [--embed] [--textures dir] [--lod 0.5,0.25]");
+        eprintln!("       rwx_tool to-json <input.rwx> <output.json>");
+        eprintln!("       rwx_tool to-json --schema <output.schema.json>");
+        eprintln!("       rwx_tool to-dae <input.rwx> <output.dae> [--textures dir]");
+        eprintln!("       rwx_tool to-usd <input.rwx> <output.usda> [--textures dir]");
+        eprintln!("       rwx_tool to-godot <input.rwx> <output.tscn> [--textures dir] [--res res://dir]");
+        eprintln!("       rwx_tool to-stl <input.rwx> <output.stl> [--ascii] [--scale 1000]");
+        eprintln!("       rwx_tool to-ply <input.rwx> <output.ply> [--ascii]");
+        eprintln!("       rwx_tool to-unity <input.rwx> <project_dir|output.unitypackage> [--textures dir]");
+        eprintln!("       rwx_tool texture <input> <output.png|output.ktx2> [--mask mask] [--pot]");
+        eprintln!("       rwx_tool library <object_dir> <output_dir> [--format obj|gltf|glb|dae|usda|tscn|json] [--textures dir]");
         std::process::exit(1);
     }
 
     let input = &args[1];
     let output = &args[2];
     let debug = args.iter().any(|a| a == "--debug");
+    let report = args.iter().any(|a| a == "--report");
+    let batches = args.iter().any(|a| a == "--batch");
 
-    let text = fs::read_to_string(input)
+    let text = read_rwx(Path::new(input))
         .expect("Failed to read RWX file");
 
     let tokens = lex(&text);
-    let model = parse(&tokens);
+    let model = parse(&tokens).expect("Failed to parse RWX file");
 
     if debug {
         println!("{:#?}", model);
     }
 
-    // this now works
-    if let Err(e) = export_obj(&model, output) {
+    // list geometry the normalizer would drop, so it can be fixed at the source
+    if report {
+        for clump in flatten_clumps(&model) {
+            let mesh = normalize(&clump);
+            if !mesh.report.is_empty() {
+                print!("{}", mesh.report);
+            }
+        }
+    }
+
+    // draw calls after merging clumps by material; tagged clumps stay apart
+    let batch_opts = BatchOptions { split_tagged: true };
+    if batches {
+        let clumps = flatten_clumps(&model);
+        let batched = batch(&clumps, &NormalizeOptions::for_format(TargetFormat::Obj), &batch_opts);
+        let before: usize = clumps
+            .iter()
+            .map(|c| {
+                let mut mats: Vec<&str> = c.faces.iter().map(face_material).collect();
+                mats.sort();
+                mats.dedup();
+                mats.len()
+            })
+            .sum();
+        let after: usize = batched.iter().map(|b| b.submeshes.len()).sum();
+        println!("{} clumps -> {} batches, {} -> {} draw calls", clumps.len(), batched.len(), before, after);
+        for b in &batched {
+            let tag = b.tag.map_or(String::new(), |t| format!(" tag {t}"));
+            let collision = if b.collision { "" } else { " no collision" };
+            println!("  {}{}{}: {} submeshes", b.name, tag, collision, b.submeshes.len());
+        }
+    }
+
+    // textures usually sit next to the object
+    let mut obj_opts = ObjOptions { batch: batches.then_some(batch_opts), ..Default::default() };
+    obj_opts.texture_dirs.extend(args.windows(2).filter(|w| w[0] == "--textures").map(|w| PathBuf::from(&w[1])));
+    if obj_opts.texture_dirs.is_empty() {
+        obj_opts.texture_dirs.push(Path::new(input).parent().unwrap_or(Path::new(".")).to_path_buf());
+    }
+
+    let scene = RwxScene::from_model(&model);
+    if let Err(e) = export_obj(&scene, Path::new(&format!("{output}.obj")), &obj_opts) {
         eprintln!("Error exporting OBJ: {}", e);
         std::process::exit(1);
     }
 
     println!("Exported OBJ + MTL to {output}.obj and {output}.mtl");
 }
+
+// rwx_tool lod --levels 0.5,0.25 <input.rwx> <output_base>
+//
+// Writes <output_base>_LOD0.obj (the full mesh) and one simplified OBJ per
+// level, plus <output_base>.lodgroup.json for Unity's LODGroup.
+fn lod(args: &[String]) {
+    let mut levels = vec![0.5, 0.25];
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        if a == "--levels" {
+            levels = parse_levels(it.next());
+        } else {
+            paths.push(a);
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool lod --levels 0.5,0.25 <input.rwx> <output_base>");
+        std::process::exit(1);
+    };
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let model = parse(&lex(&text)).expect("Failed to parse RWX file");
+    let obj_opts = ObjOptions {
+        texture_dirs: vec![Path::new(input).parent().unwrap_or(Path::new(".")).to_path_buf()],
+        ..Default::default()
+    };
+
+    // simplify single-sided geometry; back faces are added per level afterwards
+    let opts = NormalizeOptions {
+        double_sided: DoubleSidedMode::Mark,
+        ..NormalizeOptions::for_format(TargetFormat::Obj)
+    };
+    let mesh = normalize_with(&flatten(&model), &opts);
+
+    let name = Path::new(output).file_name().map_or(output.to_string(), |n| n.to_string_lossy().into_owned());
+    let mut entries = Vec::new();
+    for (i, mut level) in generate_lods(&mesh, &levels).into_iter().enumerate() {
+        let base = format!("{output}_LOD{i}");
+        entries.push((format!("{name}_LOD{i}.obj"), level.ratio, level.mesh.faces.len()));
+        println!("LOD{i}: {} triangles, error {:.4}", level.mesh.faces.len(), level.error);
+
+        duplicate_back_faces(&mut level.mesh);
+        if let Err(e) = export_mesh_obj(&level.mesh, Path::new(&format!("{base}.obj")), &obj_opts) {
+            eprintln!("Error exporting OBJ: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Err(e) = LodGroup::new(&name, &entries).write(Path::new(&format!("{output}.lodgroup.json"))) {
+        eprintln!("Error writing LOD group: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// rwx_tool to-gltf <input.rwx> <output.gltf|output.glb> [--embed] [--textures dir] [--lod 0.5,0.25]
+fn to_gltf(args: &[String]) {
+    let mut opts = GltfOptions::default();
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--embed" => opts.embed_textures = true,
+            "--textures" => opts.texture_dirs.extend(it.next().map(PathBuf::from)),
+            "--lod" => opts.lod_levels = parse_levels(it.next()),
+            _ => paths.push(a),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool to-gltf <input.rwx> <output.gltf|output.glb> [--embed] [--textures dir] [--lod 0.5,0.25]");
+        std::process::exit(1);
+    };
+
+    let output = Path::new(output);
+    opts.binary = output.extension().is_some_and(|e| e.eq_ignore_ascii_case("glb"));
+    // textures usually sit next to the object
+    if opts.texture_dirs.is_empty() {
+        opts.texture_dirs.push(Path::new(input).parent().unwrap_or(Path::new(".")).to_path_buf());
+    }
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+
+    if let Err(e) = export_gltf(&scene, output, &opts) {
+        eprintln!("Error exporting glTF: {}", e);
+        std::process::exit(1);
+    }
+    println!("Exported glTF to {}", output.display());
+}
+
+// rwx_tool to-json <input.rwx> <output.json>
+// rwx_tool to-json --schema <output.schema.json>
+fn to_json(args: &[String]) {
+    match args {
+        [flag, output] if flag == "--schema" => {
+            let schema = serde_json::to_string_pretty(&scene_schema()).unwrap();
+            if let Err(e) = fs::write(output, schema + "\n") {
+                eprintln!("Error writing schema: {}", e);
+                std::process::exit(1);
+            }
+            println!("Wrote scene schema to {output}");
+        }
+        [input, output] => {
+            let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+            let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+            let source = Path::new(input).file_name().and_then(|n| n.to_str());
+            if let Err(e) = write_scene_json(&scene, source, Path::new(output)) {
+                eprintln!("Error exporting JSON: {}", e);
+                std::process::exit(1);
+            }
+            println!("Exported scene JSON to {output}");
+        }
+        _ => {
+            eprintln!("Usage: rwx_tool to-json <input.rwx> <output.json>");
+            eprintln!("       rwx_tool to-json --schema <output.schema.json>");
+            std::process::exit(1);
+        }
+    }
+}
+
+// rwx_tool to-dae <input.rwx> <output.dae> [--textures dir]
+fn to_dae(args: &[String]) {
+    let mut opts = ColladaOptions::default();
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--textures" => opts.texture_dirs.extend(it.next().map(PathBuf::from)),
+            _ => paths.push(a),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool to-dae <input.rwx> <output.dae> [--textures dir]");
+        std::process::exit(1);
+    };
+
+    if opts.texture_dirs.is_empty() {
+        opts.texture_dirs.push(Path::new(input).parent().unwrap_or(Path::new(".")).to_path_buf());
+    }
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+
+    if let Err(e) = export_collada(&scene, Path::new(output), &opts) {
+        eprintln!("Error exporting COLLADA: {}", e);
+        std::process::exit(1);
+    }
+    println!("Exported COLLADA to {output}");
+}
+
+// rwx_tool to-usd <input.rwx> <output.usda> [--textures dir]
+fn to_usd(args: &[String]) {
+    let mut opts = UsdOptions::default();
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--textures" => opts.texture_dirs.extend(it.next().map(PathBuf::from)),
+            _ => paths.push(a),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool to-usd <input.rwx> <output.usda> [--textures dir]");
+        std::process::exit(1);
+    };
+
+    if opts.texture_dirs.is_empty() {
+        opts.texture_dirs.push(Path::new(input).parent().unwrap_or(Path::new(".")).to_path_buf());
+    }
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+
+    if let Err(e) = export_usd(&scene, Path::new(output), &opts) {
+        eprintln!("Error exporting USD: {}", e);
+        std::process::exit(1);
+    }
+    println!("Exported USD to {output}");
+}
+
+// rwx_tool to-godot <input.rwx> <output.tscn> [--textures dir] [--res res://dir]
+fn to_godot(args: &[String]) {
+    let mut opts = GodotOptions::default();
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--textures" => opts.texture_dirs.extend(it.next().map(PathBuf::from)),
+            "--res" => opts.resource_dir = it.next().cloned(),
+            _ => paths.push(a),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool to-godot <input.rwx> <output.tscn> [--textures dir] [--res res://dir]");
+        std::process::exit(1);
+    };
+
+    if opts.texture_dirs.is_empty() {
+        opts.texture_dirs.push(Path::new(input).parent().unwrap_or(Path::new(".")).to_path_buf());
+    }
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+
+    if let Err(e) = export_godot(&scene, Path::new(output), &opts) {
+        eprintln!("Error exporting Godot scene: {}", e);
+        std::process::exit(1);
+    }
+    println!("Exported Godot scene to {output}");
+}
+
+// rwx_tool to-stl <input.rwx> <output.stl> [--ascii] [--scale 1000]
+fn to_stl(args: &[String]) {
+    let mut opts = StlOptions::default();
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--ascii" => opts.binary = false,
+            "--scale" => {
+                opts.scale = it.next().and_then(|s| s.parse().ok()).filter(|s: &f32| *s > 0.0).unwrap_or_else(|| {
+                    eprintln!("--scale needs a positive number, e.g. 1000 for millimetres");
+                    std::process::exit(1);
+                })
+            }
+            _ => paths.push(a),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool to-stl <input.rwx> <output.stl> [--ascii] [--scale 1000]");
+        std::process::exit(1);
+    };
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+
+    match export_stl(&scene, Path::new(output), &opts) {
+        Ok(report) => println!("Exported STL to {output} ({report})"),
+        Err(e) => {
+            eprintln!("Error exporting STL: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// rwx_tool to-ply <input.rwx> <output.ply> [--ascii]
+fn to_ply(args: &[String]) {
+    let mut opts = PlyOptions::default();
+    let mut paths = Vec::new();
+    for a in args {
+        match a.as_str() {
+            "--ascii" => opts.binary = false,
+            _ => paths.push(a),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool to-ply <input.rwx> <output.ply> [--ascii]");
+        std::process::exit(1);
+    };
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+
+    if let Err(e) = export_ply(&scene, Path::new(output), &opts) {
+        eprintln!("Error exporting PLY: {}", e);
+        std::process::exit(1);
+    }
+    println!("Exported PLY to {output}");
+}
+
+// rwx_tool to-unity <input.rwx> <project_dir|output.unitypackage> [--textures dir]
+fn to_unity(args: &[String]) {
+    let mut opts = PrefabOptions::default();
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--textures" => opts.texture_dirs.extend(it.next().map(PathBuf::from)),
+            _ => paths.push(a),
+        }
+    }
+    let [input, output] = paths[..] else {
+        eprintln!("Usage: rwx_tool to-unity <input.rwx> <project_dir|output.unitypackage> [--textures dir]");
+        std::process::exit(1);
+    };
+
+    if opts.texture_dirs.is_empty() {
+        opts.texture_dirs.push(Path::new(input).parent().unwrap_or(Path::new(".")).to_path_buf());
+    }
+
+    let text = read_rwx(Path::new(input)).expect("Failed to read RWX file");
+    let scene = RwxScene::from_model(&parse(&lex(&text)).expect("Failed to parse RWX file"));
+    let name = Path::new(input).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+
+    // a .unitypackage file, or straight into a project folder
+    let result = if output.ends_with(".unitypackage") {
+        package_prefab(&scene, &name, Path::new(output), &opts)
+    } else {
+        write_prefab(&scene, &name, Path::new(output), &opts)
+    };
+    if let Err(e) = result {
+        eprintln!("Error exporting Unity prefab: {}", e);
+        std::process::exit(1);
+    }
+    println!("Exported Unity prefab {name} to {output}");
+}
+
+// rwx_tool texture <input> <output.png|output.ktx2> [--mask mask] [--pot]
+fn texture(args: &[String]) {
+    let mut opts = TextureOptions::default();
+    let mut mask = None;
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--mask" => mask = it.next().map(PathBuf::from),
+            "--pot" => opts.power_of_two = true,
+            _ => paths.push(a),
+        }
+    }
+    let ([input, output], Some(format)) = (&paths[..], paths.get(1).and_then(|o| TextureFormat::from_path(Path::new(o)))) else {
+        eprintln!("Usage: rwx_tool texture <input> <output.png|output.ktx2> [--mask mask] [--pot]");
+        std::process::exit(1);
+    };
+    opts.format = format;
+
+    if let Err(e) = convert_file(Path::new(input), mask.as_deref(), Path::new(output), &opts) {
+        eprintln!("Error converting texture: {}", e);
+        std::process::exit(1);
+    }
+    println!("Converted {input} to {output}");
+}
+
+// rwx_tool library <object_dir> <output_dir> [--format obj|gltf|glb|dae|usda|tscn|json] [--textures dir]
+//
+// Converts every object in a directory of `.rwx` files or zipped objects,
+// as an object path serves them, to <output_dir>/<name>.<format>. Without
+// --textures, textures are looked up in the object directory and in a
+// `textures` directory beside it.
+fn library(args: &[String]) {
+    let mut format = "obj".to_string();
+    let mut texture_dirs = Vec::new();
+    let mut paths = Vec::new();
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--format" => format = it.next().map(|f| f.to_ascii_lowercase()).unwrap_or_default(),
+            "--textures" => texture_dirs.extend(it.next().map(PathBuf::from)),
+            _ => paths.push(a),
+        }
+    }
+    let formats = ["obj", "gltf", "glb", "dae", "usda", "tscn", "json"];
+    let ([input, output], true) = (&paths[..], formats.contains(&format.as_str())) else {
+        eprintln!("Usage: rwx_tool library <object_dir> <output_dir> [--format obj|gltf|glb|dae|usda|tscn|json] [--textures dir]");
+        std::process::exit(1);
+    };
+
+    let input = Path::new(input);
+    if texture_dirs.is_empty() {
+        texture_dirs.push(input.to_path_buf());
+        texture_dirs.extend(input.parent().map(|p| p.join("textures")).filter(|p| p.is_dir()));
+    }
+    let library = ObjectLibrary::new(vec![input.to_path_buf()]);
+    let names = library.objects().unwrap_or_else(|e| {
+        eprintln!("Error reading object directory {}: {}", input.display(), e);
+        std::process::exit(1);
+    });
+    if let Err(e) = fs::create_dir_all(output) {
+        eprintln!("Error creating {output}: {}", e);
+        std::process::exit(1);
+    }
+
+    let mut failed = 0;
+    for name in &names {
+        let path = Path::new(output).join(format!("{name}.{format}"));
+        let result = library.read(name).and_then(|text| {
+            let model = parse(&lex(&text)).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            let scene = RwxScene::from_model(&model);
+            export_as(&scene, &format, &path, &texture_dirs)
+        });
+        match result {
+            Ok(()) => println!("{name} -> {}", path.display()),
+            Err(e) => {
+                eprintln!("Error converting {name}: {}", e);
+                failed += 1;
+            }
+        }
+    }
+    println!("Converted {} of {} objects", names.len() - failed, names.len());
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn export_as(scene: &RwxScene, format: &str, path: &Path, texture_dirs: &[PathBuf]) -> std::io::Result<()> {
+    let texture_dirs = texture_dirs.to_vec();
+    match format {
+        "gltf" | "glb" => {
+            let opts = GltfOptions { binary: format == "glb", texture_dirs, ..Default::default() };
+            export_gltf(scene, path, &opts)
+        }
+        "dae" => export_collada(scene, path, &ColladaOptions { texture_dirs }),
+        "usda" => export_usd(scene, path, &UsdOptions { texture_dirs }),
+        "tscn" => export_godot(scene, path, &GodotOptions { texture_dirs, ..Default::default() }),
+        "json" => write_scene_json(scene, None, path),
+        _ => export_obj(scene, path, &ObjOptions { texture_dirs, ..Default::default() }),
+    }
+}
+
+fn parse_levels(list: Option<&String>) -> Vec<f32> {
+    list.map(String::as_str)
+        .unwrap_or("")
+        .split(',')
+        .map(|r| r.trim().parse::<f32>().ok().filter(|r| *r > 0.0 && *r <= 1.0))
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_else(|| {
+            eprintln!("LOD levels must be ratios between 0 and 1, e.g. 0.5,0.25");
+            std::process::exit(1);
+        })
+}
+
+fn face_material(face: &rwx_parser::Face) -> &str {
+    match face {
+        rwx_parser::Face::Quad(_, mat) | rwx_parser::Face::Poly(_, mat) => mat,
+    }
+}
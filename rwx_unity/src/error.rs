@@ -0,0 +1,54 @@
+//! Errors from packaging.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum UnityError {
+    /// An input file could not be read
+    Read { path: PathBuf, source: io::Error },
+    /// An output file could not be written
+    Write { path: PathBuf, source: io::Error },
+    /// A texture archive could not be opened or has no image in it
+    Archive { path: PathBuf, source: zip::result::ZipError },
+    /// A texture or its mask could not be decoded for merging
+    Texture { path: PathBuf, source: rwx_texture::TextureError },
+    /// The manifest could not be serialized
+    Manifest(serde_json::Error),
+}
+
+impl UnityError {
+    pub(crate) fn read(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| UnityError::Read { path, source }
+    }
+
+    pub(crate) fn write(path: impl Into<PathBuf>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.into();
+        move |source| UnityError::Write { path, source }
+    }
+}
+
+impl fmt::Display for UnityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnityError::Read { path, source } => write!(f, "cannot read {}: {source}", path.display()),
+            UnityError::Write { path, source } => write!(f, "cannot write {}: {source}", path.display()),
+            UnityError::Archive { path, source } => write!(f, "cannot read texture archive {}: {source}", path.display()),
+            UnityError::Texture { path, source } => write!(f, "cannot convert texture {}: {source}", path.display()),
+            UnityError::Manifest(e) => write!(f, "cannot serialize package manifest: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for UnityError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UnityError::Read { source, .. } | UnityError::Write { source, .. } => Some(source),
+            UnityError::Archive { source, .. } => Some(source),
+            UnityError::Texture { source, .. } => Some(source),
+            UnityError::Manifest(e) => Some(e),
+        }
+    }
+}
@@ -1,58 +1,167 @@
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use rwx_parser::Material;
 use serde::{Serialize, Deserialize};
 
+pub mod error;
+pub mod package;
+pub mod prefab;
+pub mod textures;
+
+pub use error::UnityError;
+pub use package::{asset_guid, write_unitypackage, ImportSettings, NormalsMode, PackageAsset, WrapMode};
+pub use prefab::{package_prefab, prefab_assets, write_prefab, PrefabOptions};
+pub use textures::{find_texture, merge_mask, read_texture, referenced_textures};
+
+/// Where packaged objects are imported, under the project folder.
+pub const ASSET_ROOT: &str = "Assets/RWX";
+
+/// Summary of a package, written next to it as `<name>.unitypack.json`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UnityPackage {
     pub name: String,
+    /// Package file name
+    pub package: String,
+    /// Project paths of the packaged model files
     pub obj: String,
     pub mtl: String,
     pub textures: Vec<String>,
+    /// Textures the materials use that no search directory has
+    #[serde(default)]
+    pub missing_textures: Vec<String>,
 }
 
+/// Writes `<out_dir>/<base_name>.unitypackage` holding the OBJ, its MTL and
+/// the textures `materials` use, all under `Assets/RWX/<base_name>/`.
+/// Textures are looked up with [`find_texture`] in `texture_dirs`; the
+/// ones not found are listed in the manifest rather than failing. A mask
+/// found along with its texture is packaged merged into it by
+/// [`merge_mask`]; neither is packaged as a file of its own unless another
+/// material uses it without a mask.
 pub fn package_unity(
     base_name: &str,
-    obj_path: &str,
-    mtl_path: &str,
-    texture_dir: &str,
-    out_dir: &str
-) {
-    fs::create_dir_all(out_dir).unwrap();
+    obj_path: &Path,
+    mtl_path: &Path,
+    materials: &[Material],
+    texture_dirs: &[PathBuf],
+    out_dir: &Path,
+    settings: &ImportSettings,
+) -> Result<UnityPackage, UnityError> {
+    fs::create_dir_all(out_dir).map_err(UnityError::write(out_dir))?;
 
-    let obj_target = format!("{}/{}.obj", out_dir, base_name);
-    let mtl_target = format!("{}/{}.mtl", out_dir, base_name);
+    let folder = format!("{}/{}", ASSET_ROOT, base_name);
+    let obj_target = format!("{}/{}.obj", folder, base_name);
+    let mtl_target = format!("{}/{}.mtl", folder, base_name);
 
-    fs::copy(obj_path, &obj_target).unwrap();
-    fs::copy(mtl_path, &mtl_target).unwrap();
+    let obj = fs::read(obj_path).map_err(UnityError::read(obj_path))?;
+    let mtl = fs::read(mtl_path).map_err(UnityError::read(mtl_path))?;
+    let mut assets = vec![
+        PackageAsset::folder("", ASSET_ROOT),
+        PackageAsset::folder(base_name, &folder),
+        PackageAsset::file(base_name, &obj_target, obj, settings),
+        PackageAsset::file(base_name, &mtl_target, mtl, settings),
+    ];
 
     let mut tex_list = Vec::new();
-
-    if Path::new(texture_dir).exists() {
-        for entry in fs::read_dir(texture_dir).unwrap() {
-            let e = entry.unwrap();
-            let p = e.path();
-            if let Some(ext) = p.extension() {
-                if ext == "png" || ext == "jpg" {
-                    let fname = p.file_name().unwrap().to_string_lossy().to_string();
-                    let target = format!("{}/{}", out_dir, fname);
-                    fs::copy(&p, &target).unwrap();
-                    tex_list.push(fname);
-                }
-            }
+    let mut missing = Vec::new();
+    let mut merged = Vec::new();
+    let mut add = |file: String, data: Vec<u8>, assets: &mut Vec<PackageAsset>| {
+        let target = format!("{}/{}", folder, file);
+        // two names can resolve to the same file
+        if !tex_list.contains(&target) {
+            assets.push(PackageAsset::file(base_name, &target, data, settings));
+            tex_list.push(target);
         }
+    };
+    for mat in materials {
+        let (Some(texture), Some(mask)) = (&mat.texture, &mat.mask) else { continue };
+        let (Some(base), Some(mask_path)) = (find_texture(texture, texture_dirs), find_texture(mask, texture_dirs)) else {
+            continue;
+        };
+        let (file, data) = merge_mask(texture, mask, &base, &mask_path)?;
+        add(file, data, &mut assets);
+        merged.extend([texture.clone(), mask.clone()]);
     }
+    // the MTL only refers to the merged image, so a merged texture or mask
+    // is packaged on its own only if some material also uses it unmasked
+    let used_unmerged = |name: &str| {
+        materials.iter().any(|m| {
+            let is = |n: &Option<String>| n.as_deref().is_some_and(|n| n.eq_ignore_ascii_case(name));
+            (m.mask.is_none() && is(&m.texture)) || is(&m.bump)
+        })
+    };
+    for name in referenced_textures(materials) {
+        if merged.iter().any(|m| m.eq_ignore_ascii_case(&name)) && !used_unmerged(&name) {
+            continue;
+        }
+        let Some(path) = find_texture(&name, texture_dirs) else {
+            missing.push(name);
+            continue;
+        };
+        let (file, data) = read_texture(&path)?;
+        add(file, data, &mut assets);
+    }
+
+    let package = format!("{}.unitypackage", base_name);
+    let package_path = out_dir.join(&package);
+    let file = File::create(&package_path).map_err(UnityError::write(&package_path))?;
+    write_unitypackage(file, &assets).map_err(UnityError::write(&package_path))?;
 
     let pack = UnityPackage {
         name: base_name.to_string(),
-        obj: format!("{}.obj", base_name),
-        mtl: format!("{}.mtl", base_name),
+        package,
+        obj: obj_target,
+        mtl: mtl_target,
         textures: tex_list,
+        missing_textures: missing,
     };
 
-    let json_path = format!("{}/{}.unitypack.json", out_dir, base_name);
-    let mut jf = File::create(json_path).unwrap();
-    jf.write_all(serde_json::to_string_pretty(&pack).unwrap().as_bytes())
-        .unwrap();
+    let json_path = out_dir.join(format!("{}.unitypack.json", base_name));
+    let json = serde_json::to_string_pretty(&pack).map_err(UnityError::Manifest)?;
+    fs::write(&json_path, json).map_err(UnityError::write(&json_path))?;
+    Ok(pack)
+}
+
+/// One renderer entry of a Unity `LODGroup`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LodLevelInfo {
+    pub mesh: String,
+    /// Fraction of the LOD 0 triangle count
+    pub ratio: f32,
+    pub triangles: usize,
+    /// Screen height fraction below which Unity switches to the next level
+    pub screen_relative_height: f32,
+}
+
+/// Metadata for building a `LODGroup` on import.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LodGroup {
+    pub name: String,
+    pub levels: Vec<LodLevelInfo>,
+}
+
+impl LodGroup {
+    /// Levels as `(mesh file, ratio, triangle count)`, LOD 0 first. Each
+    /// level hands over at half its ratio, and the last one culls at 1% of
+    /// the screen.
+    pub fn new(name: &str, levels: &[(String, f32, usize)]) -> Self {
+        let count = levels.len();
+        let levels = levels
+            .iter()
+            .enumerate()
+            .map(|(i, (mesh, ratio, triangles))| LodLevelInfo {
+                mesh: mesh.clone(),
+                ratio: *ratio,
+                triangles: *triangles,
+                screen_relative_height: if i + 1 == count { 0.01 } else { ratio * 0.5 },
+            })
+            .collect();
+        LodGroup { name: name.to_string(), levels }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), UnityError> {
+        let json = serde_json::to_string_pretty(self).map_err(UnityError::Manifest)?;
+        fs::write(path, json).map_err(UnityError::write(path))
+    }
 }
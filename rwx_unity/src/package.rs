@@ -0,0 +1,245 @@
+//! `.unitypackage` writing.
+//!
+//! A package is a gzipped tar with one folder per asset, named after the
+//! asset's GUID and holding `asset` (the file itself, absent for folders),
+//! `asset.meta` (the importer settings Unity would otherwise generate) and
+//! `pathname` (where the asset goes under `Assets/`).
+//!
+//! GUIDs are derived from the object name and asset path, so re-exporting
+//! an object replaces the previous import instead of duplicating it, and
+//! other assets (prefabs, materials) can refer to it before Unity has seen
+//! it. Tar headers carry no timestamps or owners, so the same input always
+//! gives the same package bytes.
+
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use md5::{Digest, Md5};
+
+/// How the model importer fills in normals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalsMode {
+    /// Use the normals in the file
+    Import,
+    /// Recalculate from the smoothing angle
+    Calculate,
+    None,
+}
+
+/// Texture wrap mode, for both U and V.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+/// Settings written into the `.meta` files.
+#[derive(Debug, Clone)]
+pub struct ImportSettings {
+    /// Model importer `globalScale`; 1 when the model is already in metres
+    pub scale_factor: f32,
+    pub normals: NormalsMode,
+    /// Smoothing angle used when `normals` is `Calculate`
+    pub smoothing_angle: f32,
+    pub texture_wrap: WrapMode,
+}
+
+impl Default for ImportSettings {
+    fn default() -> Self {
+        ImportSettings {
+            scale_factor: 1.0,
+            normals: NormalsMode::Import,
+            smoothing_angle: 60.0,
+            texture_wrap: WrapMode::Repeat,
+        }
+    }
+}
+
+/// One entry of a package.
+#[derive(Debug, Clone)]
+pub struct PackageAsset {
+    /// Path under the project, e.g. `Assets/RWX/chair/chair.obj`
+    pub pathname: String,
+    pub guid: String,
+    /// File contents; `None` for folders
+    pub data: Option<Vec<u8>>,
+    pub meta: String,
+}
+
+impl PackageAsset {
+    /// A folder entry, so the package creates it with a stable GUID.
+    pub fn folder(object: &str, pathname: &str) -> Self {
+        let guid = asset_guid(object, pathname);
+        let meta = format!("fileFormatVersion: 2\nguid: {guid}\nfolderAsset: yes\n{}", default_importer());
+        PackageAsset { pathname: pathname.to_string(), guid, data: None, meta }
+    }
+
+    /// A file entry with the importer its extension calls for.
+    pub fn file(object: &str, pathname: &str, data: Vec<u8>, settings: &ImportSettings) -> Self {
+        let guid = asset_guid(object, pathname);
+        let ext = pathname.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).unwrap_or_default();
+        let importer = match ext.as_str() {
+            "obj" | "fbx" | "dae" => model_importer(settings),
+            "png" | "jpg" | "jpeg" | "bmp" | "tga" => texture_importer(settings),
+            // the only `.asset` files written here are meshes
+            "asset" => native_format_importer(4300000),
+            "mat" => native_format_importer(2100000),
+            "prefab" => "PrefabImporter:\n  externalObjects: {}\n  userData:\n  assetBundleName:\n  assetBundleVariant:\n".to_string(),
+            "cs" => mono_importer(),
+            _ => default_importer(),
+        };
+        let meta = format!("fileFormatVersion: 2\nguid: {guid}\n{importer}");
+        PackageAsset { pathname: pathname.to_string(), guid, data: Some(data), meta }
+    }
+}
+
+/// Stable 32-digit hex GUID for `pathname` within `object`'s package.
+/// Assets shared by every package, like the `Assets/RWX` folder, pass an
+/// empty `object` so all packages agree on them.
+pub fn asset_guid(object: &str, pathname: &str) -> String {
+    let digest = Md5::digest(format!("rwx:{object}:{pathname}").as_bytes());
+    digest.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Writes `assets` as a gzipped tar in Unity's package layout.
+pub fn write_unitypackage(out: impl Write, assets: &[PackageAsset]) -> io::Result<()> {
+    let mut tar = tar::Builder::new(GzEncoder::new(out, Compression::default()));
+    for asset in assets {
+        if let Some(data) = &asset.data {
+            append(&mut tar, &format!("{}/asset", asset.guid), data)?;
+        }
+        append(&mut tar, &format!("{}/asset.meta", asset.guid), asset.meta.as_bytes())?;
+        append(&mut tar, &format!("{}/pathname", asset.guid), asset.pathname.as_bytes())?;
+    }
+    tar.into_inner()?.finish()?.flush()
+}
+
+fn append<W: Write>(tar: &mut tar::Builder<W>, path: &str, data: &[u8]) -> io::Result<()> {
+    let mut header = tar::Header::new_ustar();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(0);
+    header.set_entry_type(tar::EntryType::Regular);
+    tar.append_data(&mut header, path, data)
+}
+
+fn model_importer(settings: &ImportSettings) -> String {
+    let normals = match settings.normals {
+        NormalsMode::Import => 0,
+        NormalsMode::Calculate => 1,
+        NormalsMode::None => 2,
+    };
+    format!(
+        "ModelImporter:
+  serializedVersion: 21300
+  internalIDToNameTable: []
+  externalObjects: {{}}
+  materials:
+    materialImportMode: 1
+    materialName: 0
+    materialSearch: 1
+    materialLocation: 1
+  animations:
+    importAnimation: 0
+  meshes:
+    globalScale: {}
+    meshCompression: 0
+    addColliders: 0
+    importVisibility: 0
+    importBlendShapes: 0
+    importCameras: 0
+    importLights: 0
+    swapUVChannels: 0
+    generateSecondaryUV: 0
+    useFileUnits: 1
+    keepQuads: 0
+    weldVertices: 1
+    isReadable: 0
+    useFileScale: 0
+  tangentSpace:
+    normalSmoothAngle: {}
+    normalImportMode: {normals}
+    tangentImportMode: 3
+    normalCalculationMode: 4
+  importAnimation: 0
+  animationType: 0
+  userData:
+  assetBundleName:
+  assetBundleVariant:
+",
+        settings.scale_factor, settings.smoothing_angle,
+    )
+}
+
+fn texture_importer(settings: &ImportSettings) -> String {
+    let wrap = match settings.texture_wrap {
+        WrapMode::Repeat => 0,
+        WrapMode::Clamp => 1,
+        WrapMode::Mirror => 2,
+    };
+    format!(
+        "TextureImporter:
+  internalIDToNameTable: []
+  externalObjects: {{}}
+  serializedVersion: 12
+  mipmaps:
+    mipMapMode: 0
+    enableMipMap: 1
+    sRGBTexture: 1
+  isReadable: 0
+  textureSettings:
+    serializedVersion: 2
+    filterMode: 1
+    aniso: 1
+    mipBias: 0
+    wrapU: {wrap}
+    wrapV: {wrap}
+    wrapW: {wrap}
+  alphaUsage: 1
+  alphaIsTransparency: 0
+  textureType: 0
+  textureShape: 1
+  userData:
+  assetBundleName:
+  assetBundleVariant:
+"
+    )
+}
+
+fn native_format_importer(main_object: u64) -> String {
+    format!(
+        "NativeFormatImporter:
+  externalObjects: {{}}
+  mainObjectFileID: {main_object}
+  userData:
+  assetBundleName:
+  assetBundleVariant:
+"
+    )
+}
+
+fn mono_importer() -> String {
+    "MonoImporter:
+  externalObjects: {}
+  serializedVersion: 2
+  defaultReferences: []
+  executionOrder: 0
+  icon: {instanceID: 0}
+  userData:
+  assetBundleName:
+  assetBundleVariant:
+"
+    .to_string()
+}
+
+fn default_importer() -> String {
+    "DefaultImporter:
+  externalObjects: {}
+  userData:
+  assetBundleName:
+  assetBundleVariant:
+"
+    .to_string()
+}
@@ -0,0 +1,561 @@
+//! Unity prefab export.
+//!
+//! An object becomes a folder `Assets/RWX/<name>/` holding:
+//!
+//! - `<name>.prefab`: one GameObject per clump, nested as the clumps are,
+//!   with a MeshFilter and MeshRenderer for clumps with geometry and a
+//!   MeshCollider for those that also take part in collision.
+//! - One `.asset` Mesh per clump, in Unity space (metres, X mirrored, V
+//!   from the bottom) with one submesh per material. Double-sided faces
+//!   are duplicated, as Unity's built-in shaders cull back faces.
+//! - One `.mat` per material on the Standard shader, set up from
+//!   [`PbrMaterial::from_rwx`]: opacity below 1 uses the Fade mode and a
+//!   masked texture the Cutout mode.
+//! - The texture files the materials use, found in `texture_dirs` by
+//!   [`find_texture`]. A masked texture is merged with its mask into one
+//!   `<texture>_<mask>.png` whose alpha the Cutout mode reads.
+//!
+//! Clumps with a `Tag`, an `AxisAlignment` or a prototype get an
+//! `RwxObjectInfo` component holding them. Its script lives in
+//! `Assets/RWX/Scripts/` with the same GUID in every package, so importing
+//! several objects does not duplicate the class.
+//!
+//! File IDs inside a prefab are `class id * 100000 + clump index`, as
+//! Unity's own legacy scheme, so they stay the same between exports.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rwx_normalizer::{normalize_with, NormalizeOptions, NormalizedMesh, TargetFormat};
+use rwx_parser::{AxisAlignment, Face, Vertex};
+use rwx_scene::{AlphaMode, PbrMaterial, RwxNode, RwxScene};
+use rwx_semantics::FlatMesh;
+
+use crate::{asset_guid, find_texture, merge_mask, read_texture, write_unitypackage, ImportSettings, PackageAsset, UnityError, ASSET_ROOT};
+
+/// Project path of the `RwxObjectInfo` component script.
+pub const OBJECT_INFO_PATH: &str = "Assets/RWX/Scripts/RwxObjectInfo.cs";
+
+/// Source of the `RwxObjectInfo` component. Besides holding the clump
+/// properties it turns axis-aligned clumps towards the main camera.
+pub const OBJECT_INFO_SCRIPT: &str = r#"using UnityEngine;
+
+// RWX clump properties with no Unity equivalent.
+public class RwxObjectInfo : MonoBehaviour
+{
+    public enum Alignment { None, ZOrientX, ZOrientY, XYZ }
+
+    [Tooltip("RWX Tag, or -1 when the clump has none")]
+    public int rwxTag = -1;
+
+    [Tooltip("Turns the clump so its Z axis points at the main camera")]
+    public Alignment axisAlignment;
+
+    [Tooltip("Prototype this clump is an instance of")]
+    public string prototype = "";
+
+    void LateUpdate()
+    {
+        var cam = Camera.main;
+        if (axisAlignment == Alignment.None || cam == null)
+            return;
+
+        var parent = transform.parent;
+        var toCamera = cam.transform.position - transform.position;
+        if (parent != null)
+            toCamera = parent.InverseTransformDirection(toCamera);
+
+        switch (axisAlignment)
+        {
+            case Alignment.ZOrientX:
+                toCamera.y = 0;
+                break;
+            case Alignment.ZOrientY:
+                toCamera.x = 0;
+                break;
+        }
+        if (toCamera.sqrMagnitude > 0)
+            transform.localRotation = Quaternion.LookRotation(toCamera, axisAlignment == Alignment.ZOrientY ? Vector3.Cross(toCamera, Vector3.right) : Vector3.up);
+    }
+}
+"#;
+
+// Unity class ids of the objects written
+const GAME_OBJECT: u64 = 1;
+const TRANSFORM: u64 = 4;
+const MESH_RENDERER: u64 = 23;
+const MESH_FILTER: u64 = 33;
+const MESH_COLLIDER: u64 = 64;
+const MONO_BEHAVIOUR: u64 = 114;
+
+// main object file IDs of native assets and imported textures and scripts
+const MESH_ID: u64 = 4300000;
+const MATERIAL_ID: u64 = 2100000;
+const TEXTURE_ID: u64 = 2800000;
+const SCRIPT_ID: u64 = 11500000;
+
+#[derive(Debug, Clone, Default)]
+pub struct PrefabOptions {
+    /// Directories searched for texture files
+    pub texture_dirs: Vec<PathBuf>,
+    /// Importer settings for the textures' `.meta` files
+    pub settings: ImportSettings,
+}
+
+/// Every asset of `scene`'s prefab, as [`write_prefab`] and
+/// [`package_prefab`] write them: the folders, the component script,
+/// textures, materials, meshes and the prefab itself.
+pub fn prefab_assets(scene: &RwxScene, name: &str, opts: &PrefabOptions) -> Result<Vec<PackageAsset>, UnityError> {
+    let folder = format!("{ASSET_ROOT}/{name}");
+    let scripts = OBJECT_INFO_PATH.rsplit_once('/').map_or(ASSET_ROOT, |(dir, _)| dir);
+
+    // shared between packages, so not tied to the object
+    let mut assets = vec![
+        PackageAsset::folder("", ASSET_ROOT),
+        PackageAsset::folder("", scripts),
+        PackageAsset::file("", OBJECT_INFO_PATH, OBJECT_INFO_SCRIPT.as_bytes().to_vec(), &opts.settings),
+        PackageAsset::folder(name, &folder),
+    ];
+
+    let mut taken = Vec::new();
+    let mut textures: HashMap<(PathBuf, Option<PathBuf>), String> = HashMap::new();
+    let mut materials = HashMap::new();
+    for mat in &scene.materials {
+        let pbr = PbrMaterial::from_rwx(mat);
+        let mask = pbr.alpha_texture.as_deref().and_then(|m| find_texture(m, &opts.texture_dirs));
+        let texture = match pbr.base_color_texture.as_deref().and_then(|t| find_texture(t, &opts.texture_dirs)) {
+            Some(file) => match textures.get(&(file.clone(), mask.clone())) {
+                Some(guid) => Some(guid.clone()),
+                None => {
+                    let (file_name, data) = match (&pbr.base_color_texture, &pbr.alpha_texture, &mask) {
+                        (Some(texture), Some(mask_name), Some(mask)) => merge_mask(texture, mask_name, &file, mask)?,
+                        _ => read_texture(&file)?,
+                    };
+                    let target = format!("{folder}/{}", unique(&file_name, &mut taken));
+                    let asset = PackageAsset::file(name, &target, data, &opts.settings);
+                    textures.insert((file, mask), asset.guid.clone());
+                    let guid = asset.guid.clone();
+                    assets.push(asset);
+                    Some(guid)
+                }
+            },
+            None => None,
+        };
+
+        let target = format!("{folder}/{}", unique(&format!("{}.mat", safe_name(&mat.name)), &mut taken));
+        let yaml = material_yaml(&pbr, texture.as_deref());
+        let asset = PackageAsset::file(name, &target, yaml.into_bytes(), &opts.settings);
+        materials.insert(mat.name.clone(), asset.guid.clone());
+        assets.push(asset);
+    }
+
+    let mut prefab = Prefab {
+        object: name,
+        folder: &folder,
+        scene,
+        materials: &materials,
+        normalize: NormalizeOptions::for_format(TargetFormat::Unity),
+        settings: &opts.settings,
+        script: asset_guid("", OBJECT_INFO_PATH),
+        out: String::from(YAML_HEADER),
+        meshes: Vec::new(),
+        taken,
+    };
+    prefab.node(&scene.root, name, 0, 0, 0);
+
+    let target = format!("{folder}/{}", unique(&format!("{}.prefab", safe_name(name)), &mut prefab.taken));
+    assets.append(&mut prefab.meshes);
+    assets.push(PackageAsset::file(name, &target, prefab.out.into_bytes(), &opts.settings));
+    Ok(assets)
+}
+
+/// Writes `scene`'s prefab and its assets into the Unity project at
+/// `project_dir`, each next to its `.meta` file.
+pub fn write_prefab(scene: &RwxScene, name: &str, project_dir: &Path, opts: &PrefabOptions) -> Result<(), UnityError> {
+    for asset in prefab_assets(scene, name, opts)? {
+        let path = project_dir.join(&asset.pathname);
+        match &asset.data {
+            Some(data) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).map_err(UnityError::write(parent))?;
+                }
+                fs::write(&path, data).map_err(UnityError::write(&path))?;
+            }
+            None => fs::create_dir_all(&path).map_err(UnityError::write(&path))?,
+        }
+        let meta = PathBuf::from(format!("{}.meta", path.display()));
+        fs::write(&meta, &asset.meta).map_err(UnityError::write(&meta))?;
+    }
+    Ok(())
+}
+
+/// Writes `scene`'s prefab and its assets to `path` as a `.unitypackage`.
+pub fn package_prefab(scene: &RwxScene, name: &str, path: &Path, opts: &PrefabOptions) -> Result<(), UnityError> {
+    let assets = prefab_assets(scene, name, opts)?;
+    let file = fs::File::create(path).map_err(UnityError::write(path))?;
+    write_unitypackage(file, &assets).map_err(UnityError::write(path))
+}
+
+const YAML_HEADER: &str = "%YAML 1.1\n%TAG !u! tag:unity3d.com,2011:\n";
+
+struct Prefab<'a> {
+    object: &'a str,
+    folder: &'a str,
+    scene: &'a RwxScene,
+    /// Material GUIDs by RWX material name
+    materials: &'a HashMap<String, String>,
+    normalize: NormalizeOptions,
+    settings: &'a ImportSettings,
+    script: String,
+    out: String,
+    meshes: Vec<PackageAsset>,
+    taken: Vec<String>,
+}
+
+impl Prefab<'_> {
+    /// Writes `node` as clump `index` and then its children, returning the
+    /// number of clumps written.
+    fn node(&mut self, node: &RwxNode, name: &str, index: u64, parent: u64, order: usize) -> u64 {
+        let id = |class: u64| class * 100000 + index;
+
+        let mut child_ids = Vec::new();
+        let mut next = index + 1;
+        for child in &node.children {
+            child_ids.push(TRANSFORM * 100000 + next);
+            next += count(child);
+        }
+
+        let mesh = node_mesh(node, self.scene, &self.normalize);
+        let info = node.tag.is_some() || node.axis_alignment != AxisAlignment::None || node.proto.is_some();
+
+        let mut components = vec![id(TRANSFORM)];
+        if mesh.is_some() {
+            components.extend([id(MESH_FILTER), id(MESH_RENDERER)]);
+            if node.collision {
+                components.push(id(MESH_COLLIDER));
+            }
+        }
+        if info {
+            components.push(id(MONO_BEHAVIOUR));
+        }
+
+        self.header(GAME_OBJECT, id(GAME_OBJECT), "GameObject");
+        let _ = writeln!(self.out, "  serializedVersion: 6\n  m_Component:");
+        for c in &components {
+            let _ = writeln!(self.out, "  - component: {{fileID: {c}}}");
+        }
+        let _ = writeln!(self.out, "  m_Layer: 0\n  m_Name: {}", yaml_str(name));
+        let _ = writeln!(self.out, "  m_TagString: Untagged\n  m_Icon: {{fileID: 0}}\n  m_NavMeshLayer: 0");
+        let _ = writeln!(self.out, "  m_StaticEditorFlags: 0\n  m_IsActive: 1");
+
+        self.header(TRANSFORM, id(TRANSFORM), "Transform");
+        let _ = writeln!(self.out, "  m_GameObject: {{fileID: {}}}", id(GAME_OBJECT));
+        self.transform(node);
+        if child_ids.is_empty() {
+            let _ = writeln!(self.out, "  m_Children: []");
+        } else {
+            let _ = writeln!(self.out, "  m_Children:");
+            for c in &child_ids {
+                let _ = writeln!(self.out, "  - {{fileID: {c}}}");
+            }
+        }
+        let _ = writeln!(self.out, "  m_Father: {{fileID: {parent}}}\n  m_RootOrder: {order}");
+
+        if let Some(mesh) = mesh {
+            let target = format!("{}/{}", self.folder, unique(&format!("{}.asset", safe_name(&node.name)), &mut self.taken));
+            let (yaml, used) = mesh_yaml(&node.name, &mesh);
+            let asset = PackageAsset::file(self.object, &target, yaml.into_bytes(), self.settings);
+            let mesh_ref = format!("{{fileID: {MESH_ID}, guid: {}, type: 2}}", asset.guid);
+            self.meshes.push(asset);
+
+            self.header(MESH_FILTER, id(MESH_FILTER), "MeshFilter");
+            let _ = writeln!(self.out, "  m_GameObject: {{fileID: {}}}\n  m_Mesh: {mesh_ref}", id(GAME_OBJECT));
+
+            self.header(MESH_RENDERER, id(MESH_RENDERER), "MeshRenderer");
+            let _ = writeln!(self.out, "  m_GameObject: {{fileID: {}}}", id(GAME_OBJECT));
+            let _ = writeln!(self.out, "  m_Enabled: 1\n  m_CastShadows: 1\n  m_ReceiveShadows: 1\n  m_DynamicOccludee: 1");
+            let _ = writeln!(self.out, "  m_LightProbeUsage: 1\n  m_ReflectionProbeUsage: 1\n  m_RenderingLayerMask: 1");
+            let _ = writeln!(self.out, "  m_Materials:");
+            for mat in used {
+                match self.materials.get(&mat) {
+                    Some(guid) => {
+                        let _ = writeln!(self.out, "  - {{fileID: {MATERIAL_ID}, guid: {guid}, type: 2}}");
+                    }
+                    None => {
+                        let _ = writeln!(self.out, "  - {{fileID: 0}}");
+                    }
+                }
+            }
+
+            if node.collision {
+                self.header(MESH_COLLIDER, id(MESH_COLLIDER), "MeshCollider");
+                let _ = writeln!(self.out, "  m_GameObject: {{fileID: {}}}", id(GAME_OBJECT));
+                let _ = writeln!(self.out, "  m_Material: {{fileID: 0}}\n  m_IsTrigger: 0\n  m_Enabled: 1");
+                let _ = writeln!(self.out, "  serializedVersion: 4\n  m_Convex: 0\n  m_CookingOptions: 30");
+                let _ = writeln!(self.out, "  m_Mesh: {mesh_ref}");
+            }
+        }
+
+        if info {
+            let alignment = match node.axis_alignment {
+                AxisAlignment::None => 0,
+                AxisAlignment::ZOrientX => 1,
+                AxisAlignment::ZOrientY => 2,
+                AxisAlignment::Xyz => 3,
+            };
+            self.header(MONO_BEHAVIOUR, id(MONO_BEHAVIOUR), "MonoBehaviour");
+            let _ = writeln!(self.out, "  m_GameObject: {{fileID: {}}}", id(GAME_OBJECT));
+            let _ = writeln!(self.out, "  m_Enabled: 1\n  m_EditorHideFlags: 0");
+            let _ = writeln!(self.out, "  m_Script: {{fileID: {SCRIPT_ID}, guid: {}, type: 3}}", self.script);
+            let _ = writeln!(self.out, "  m_Name: \n  m_EditorClassIdentifier: ");
+            let _ = writeln!(self.out, "  rwxTag: {}", node.tag.map_or(-1, i64::from));
+            let _ = writeln!(self.out, "  axisAlignment: {alignment}");
+            let _ = writeln!(self.out, "  prototype: {}", yaml_str(node.proto.as_deref().unwrap_or("")));
+        }
+
+        let mut written = 1;
+        for (order, child) in node.children.iter().enumerate() {
+            written += self.node(child, &child.name, index + written, id(TRANSFORM), order);
+        }
+        written
+    }
+
+    fn header(&mut self, class: u64, id: u64, kind: &str) {
+        let _ = writeln!(self.out, "--- !u!{class} &{id}\n{kind}:");
+        let _ = writeln!(self.out, "  m_ObjectHideFlags: 0\n  m_CorrespondingSourceObject: {{fileID: 0}}");
+        let _ = writeln!(self.out, "  m_PrefabInstance: {{fileID: 0}}\n  m_PrefabAsset: {{fileID: 0}}");
+    }
+
+    /// Local position, rotation and scale, converted like the vertices.
+    fn transform(&mut self, node: &RwxNode) {
+        let profile = &self.normalize.coordinates;
+        let tf = &node.transform;
+        let point = |p: [f32; 3]| {
+            profile.apply(&Vertex { x: p[0], y: p[1], z: p[2], u: 0.0, v: 0.0, normal: Some(p), tangent: None, color: None })
+        };
+
+        let position = tf.translate.map_or([0.0; 3], |t| {
+            let v = point(t);
+            [v.x, v.y, v.z]
+        });
+        // a mirror reverses the sense of rotation about the mapped axis
+        let rotation = tf.rotate.map_or([0.0, 0.0, 0.0, 1.0], |r| {
+            let axis = point([r[1], r[2], r[3]]).normal.unwrap_or([0.0; 3]);
+            let angle = if profile.flips_handedness() { -r[0] } else { r[0] };
+            let len = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+            if len == 0.0 {
+                return [0.0, 0.0, 0.0, 1.0];
+            }
+            let (s, c) = (angle.to_radians() * 0.5).sin_cos();
+            [axis[0] / len * s, axis[1] / len * s, axis[2] / len * s, c]
+        });
+        let scale = tf.scale.map_or([1.0; 3], |s| profile.axes.map(|(axis, _)| s[axis]));
+
+        let _ = writeln!(self.out, "  m_LocalRotation: {}", xyzw(rotation));
+        let _ = writeln!(self.out, "  m_LocalPosition: {}", xyz(position));
+        let _ = writeln!(self.out, "  m_LocalScale: {}", xyz(scale));
+    }
+}
+
+/// Number of clumps in `node`'s subtree, itself included.
+fn count(node: &RwxNode) -> u64 {
+    1 + node.children.iter().map(count).sum::<u64>()
+}
+
+fn node_mesh(node: &RwxNode, scene: &RwxScene, opts: &NormalizeOptions) -> Option<NormalizedMesh> {
+    if node.faces.is_empty() {
+        return None;
+    }
+    let flat = FlatMesh {
+        name: node.name.clone(),
+        vertices: node.vertices.clone(),
+        faces: node.faces.clone(),
+        materials: scene.materials.clone(),
+        tag: node.tag,
+        collision: node.collision,
+    };
+    let mesh = normalize_with(&flat, opts);
+    if mesh.faces.is_empty() { None } else { Some(mesh) }
+}
+
+/// Mesh asset YAML, plus the material of each submesh in order.
+fn mesh_yaml(name: &str, mesh: &NormalizedMesh) -> (String, Vec<String>) {
+    // triangle indices grouped by material, in order of first use
+    let mut groups: Vec<(String, Vec<u32>)> = Vec::new();
+    for face in &mesh.faces {
+        let (Face::Quad(idx, mat) | Face::Poly(idx, mat)) = face;
+        match groups.iter_mut().find(|(m, _)| m == mat) {
+            Some((_, list)) => list.extend_from_slice(&idx[..3]),
+            None => groups.push((mat.clone(), idx[..3].to_vec())),
+        }
+    }
+
+    let wide = mesh.vertices.len() > u16::MAX as usize;
+    let mut indices = Vec::new();
+    let mut out = String::from(YAML_HEADER);
+    let _ = writeln!(out, "--- !u!43 &{MESH_ID}\nMesh:");
+    let _ = writeln!(out, "  m_ObjectHideFlags: 0\n  m_CorrespondingSourceObject: {{fileID: 0}}");
+    let _ = writeln!(out, "  m_PrefabInstance: {{fileID: 0}}\n  m_PrefabAsset: {{fileID: 0}}");
+    let _ = writeln!(out, "  m_Name: {}\n  serializedVersion: 10\n  m_SubMeshes:", yaml_str(name));
+    for (_, list) in &groups {
+        let first = list.iter().min().copied().unwrap_or(0);
+        let last = list.iter().max().copied().unwrap_or(0);
+        let (center, extent) = bounds(list.iter().map(|&i| &mesh.vertices[i as usize]));
+        let _ = writeln!(out, "  - serializedVersion: 2\n    firstByte: {}", indices.len());
+        let _ = writeln!(out, "    indexCount: {}\n    topology: 0\n    baseVertex: 0", list.len());
+        let _ = writeln!(out, "    firstVertex: {first}\n    vertexCount: {}", last - first + 1);
+        let _ = writeln!(out, "    localAABB:\n      m_Center: {}\n      m_Extent: {}", xyz(center), xyz(extent));
+        for &i in list {
+            if wide {
+                indices.extend_from_slice(&i.to_le_bytes());
+            } else {
+                indices.extend_from_slice(&(i as u16).to_le_bytes());
+            }
+        }
+    }
+    let _ = writeln!(out, "  m_BindPose: []\n  m_BoneNameHashes: \n  m_RootBoneNameHash: 0");
+    let _ = writeln!(out, "  m_MeshCompression: 0\n  m_IsReadable: 1\n  m_KeepVertices: 1\n  m_KeepIndices: 1");
+    let _ = writeln!(out, "  m_IndexFormat: {}\n  m_IndexBuffer: {}", u8::from(wide), hex(&indices));
+
+    // one interleaved stream of float channels: position, normal,
+    // tangent, colour, uv0
+    let tangents = mesh.vertices.iter().all(|v| v.tangent.is_some());
+    let colors = mesh.vertices.iter().any(|v| v.color.is_some());
+    let dimensions = [3, 3, if tangents { 4 } else { 0 }, if colors { 4 } else { 0 }, 2];
+    let _ = writeln!(out, "  m_VertexData:\n    serializedVersion: 3\n    m_VertexCount: {}", mesh.vertices.len());
+    let _ = writeln!(out, "    m_Channels:");
+    let mut offset = 0;
+    for channel in 0..14 {
+        let dimension = dimensions.get(channel).copied().unwrap_or(0);
+        let at = if dimension > 0 { offset } else { 0 };
+        let _ = writeln!(out, "    - stream: 0\n      offset: {at}\n      format: 0\n      dimension: {dimension}");
+        offset += dimension * 4;
+    }
+
+    let mut data = Vec::with_capacity(mesh.vertices.len() * offset);
+    for v in &mesh.vertices {
+        let mut floats = vec![v.x, v.y, v.z];
+        floats.extend(v.normal.unwrap_or([0.0, 1.0, 0.0]));
+        if tangents {
+            floats.extend(v.tangent.unwrap_or([1.0, 0.0, 0.0, 1.0]));
+        }
+        if colors {
+            floats.extend(v.color.unwrap_or([1.0; 3]));
+            floats.push(1.0);
+        }
+        floats.extend([v.u, v.v]);
+        for f in floats {
+            data.extend_from_slice(&f.to_le_bytes());
+        }
+    }
+    let _ = writeln!(out, "    m_DataSize: {}\n    _typelessdata: {}", data.len(), hex(&data));
+
+    let (center, extent) = bounds(mesh.vertices.iter());
+    let _ = writeln!(out, "  m_LocalAABB:\n    m_Center: {}\n    m_Extent: {}", xyz(center), xyz(extent));
+    let _ = writeln!(out, "  m_MeshUsageFlags: 0\n  m_MeshOptimizationFlags: 1");
+
+    (out, groups.into_iter().map(|(m, _)| m).collect())
+}
+
+/// Standard shader material, in the rendering mode `pbr.alpha_mode` calls for.
+fn material_yaml(pbr: &PbrMaterial, texture: Option<&str>) -> String {
+    // (mode, keyword, render type, queue, src blend, dst blend, z write)
+    let (mode, keyword, render_type, queue, src, dst, zwrite) = match pbr.alpha_mode {
+        AlphaMode::Opaque => (0, "", "", -1, 1, 0, 1),
+        AlphaMode::Mask { .. } => (1, "_ALPHATEST_ON", "TransparentCutout", 2450, 1, 0, 1),
+        AlphaMode::Blend => (2, "_ALPHABLEND_ON", "Transparent", 3000, 5, 10, 0),
+    };
+    let cutoff = match pbr.alpha_mode {
+        AlphaMode::Mask { cutoff } => cutoff,
+        _ => 0.5,
+    };
+
+    let mut out = String::from(YAML_HEADER);
+    let _ = writeln!(out, "--- !u!21 &{MATERIAL_ID}\nMaterial:\n  serializedVersion: 6");
+    let _ = writeln!(out, "  m_ObjectHideFlags: 0\n  m_CorrespondingSourceObject: {{fileID: 0}}");
+    let _ = writeln!(out, "  m_PrefabInstance: {{fileID: 0}}\n  m_PrefabAsset: {{fileID: 0}}");
+    let _ = writeln!(out, "  m_Name: {}", yaml_str(&pbr.name));
+    let _ = writeln!(out, "  m_Shader: {{fileID: 46, guid: 0000000000000000f000000000000000, type: 0}}");
+    let _ = writeln!(out, "  m_ShaderKeywords: {keyword}\n  m_LightmapFlags: 4\n  m_EnableInstancingVariants: 0");
+    let _ = writeln!(out, "  m_DoubleSidedGI: {}\n  m_CustomRenderQueue: {queue}", u8::from(pbr.double_sided));
+    if render_type.is_empty() {
+        let _ = writeln!(out, "  stringTagMap: {{}}");
+    } else {
+        let _ = writeln!(out, "  stringTagMap:\n    RenderType: {render_type}");
+    }
+    let _ = writeln!(out, "  disabledShaderPasses: []\n  m_SavedProperties:\n    serializedVersion: 3");
+    let _ = writeln!(out, "    m_TexEnvs:\n    - _MainTex:");
+    match texture {
+        Some(guid) => {
+            let _ = writeln!(out, "        m_Texture: {{fileID: {TEXTURE_ID}, guid: {guid}, type: 3}}");
+        }
+        None => {
+            let _ = writeln!(out, "        m_Texture: {{fileID: 0}}");
+        }
+    }
+    let _ = writeln!(out, "        m_Scale: {{x: 1, y: 1}}\n        m_Offset: {{x: 0, y: 0}}");
+    let _ = writeln!(out, "    m_Floats:\n    - _Mode: {mode}\n    - _Cutoff: {cutoff}");
+    let _ = writeln!(out, "    - _Glossiness: {}\n    - _Metallic: {}", 1.0 - pbr.roughness, pbr.metallic);
+    let _ = writeln!(out, "    - _SrcBlend: {src}\n    - _DstBlend: {dst}\n    - _ZWrite: {zwrite}");
+    let c = pbr.base_color;
+    let _ = writeln!(out, "    m_Colors:\n    - _Color: {{r: {}, g: {}, b: {}, a: {}}}", c[0], c[1], c[2], c[3]);
+    out
+}
+
+/// `name`, with `_2`, `_3`, ... before the extension if the folder already
+/// has it.
+fn unique(name: &str, taken: &mut Vec<String>) -> String {
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let mut out = name.to_string();
+    let mut n = 1;
+    while taken.iter().any(|t| t.eq_ignore_ascii_case(&out)) {
+        n += 1;
+        out = format!("{stem}_{n}.{ext}");
+    }
+    taken.push(out.clone());
+    out
+}
+
+/// `name` with characters that are not safe in file names replaced.
+fn safe_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect()
+}
+
+/// `s` as a YAML scalar, quoted when it would not read back as itself.
+fn yaml_str(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.trim() == s
+        && !s.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`~".contains(c))
+        && !s.contains(": ")
+        && !s.contains(" #");
+    if plain { s.to_string() } else { format!("'{}'", s.replace('\'', "''")) }
+}
+
+fn bounds<'v>(vertices: impl Iterator<Item = &'v Vertex>) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for v in vertices {
+        for (k, c) in [v.x, v.y, v.z].into_iter().enumerate() {
+            min[k] = min[k].min(c);
+            max[k] = max[k].max(c);
+        }
+    }
+    if min[0] > max[0] {
+        return ([0.0; 3], [0.0; 3]);
+    }
+    ([0, 1, 2].map(|k| (min[k] + max[k]) * 0.5), [0, 1, 2].map(|k| (max[k] - min[k]) * 0.5))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn xyz(v: [f32; 3]) -> String {
+    format!("{{x: {}, y: {}, z: {}}}", v[0], v[1], v[2])
+}
+
+fn xyzw(v: [f32; 4]) -> String {
+    format!("{{x: {}, y: {}, z: {}, w: {}}}", v[0], v[1], v[2], v[3])
+}
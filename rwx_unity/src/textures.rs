@@ -0,0 +1,66 @@
+//! Texture discovery.
+//!
+//! RWX refers to textures by bare name, and object paths are not consistent
+//! about case, so `Texture Wood` may be `wood.JPG` on disk. A name is looked
+//! up in each search directory in turn, trying the extensions in
+//! [`IMAGE_EXTENSIONS`] order and ignoring case. A `.zip` holds the image
+//! itself, as on world servers; the entry whose stem matches the name is
+//! used, or else the first image in the archive. The lookup itself is
+//! [`rwx_archive`]'s, shared with the other exporters.
+//!
+//! Masks are not packaged on their own: [`merge_mask`] folds one into its
+//! texture's alpha as `<texture>_<mask>.png`, the name the OBJ exporter's
+//! MTL refers to.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rwx_archive::{extract, is_zip, IMAGE_EXTENSIONS};
+use rwx_parser::Material;
+use rwx_texture::{converted_name, TextureFormat, TextureOptions};
+
+use crate::UnityError;
+
+/// Every texture, mask and bump map `materials` use, each once, in order of
+/// first use.
+pub fn referenced_textures(materials: &[Material]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for mat in materials {
+        for name in [&mat.texture, &mat.mask, &mat.bump].into_iter().flatten() {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names
+}
+
+/// The file for texture `name` in the first of `dirs` that has one.
+pub fn find_texture(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    rwx_archive::find_file(name, dirs, &IMAGE_EXTENSIONS)
+}
+
+/// File name and contents of the image at `path`, taken out of the archive
+/// if it is a `.zip`.
+pub fn read_texture(path: &Path) -> Result<(String, Vec<u8>), UnityError> {
+    let data = fs::read(path).map_err(UnityError::read(path))?;
+    if !is_zip(path) {
+        return Ok((file_name(path), data));
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    extract(&data, &stem, &IMAGE_EXTENSIONS).map_err(|source| UnityError::Archive { path: path.to_path_buf(), source })
+}
+
+/// Texture `texture` at `base` with mask `mask` at `mask_path` merged into
+/// its alpha, as a PNG file name and contents.
+pub fn merge_mask(texture: &str, mask: &str, base: &Path, mask_path: &Path) -> Result<(String, Vec<u8>), UnityError> {
+    let (_, base_data) = read_texture(base)?;
+    let (_, mask_data) = read_texture(mask_path)?;
+    let data = rwx_texture::convert(&base_data, Some(&mask_data), &TextureOptions::default())
+        .map_err(|source| UnityError::Texture { path: base.to_path_buf(), source })?;
+    Ok((converted_name(texture, Some(mask), TextureFormat::Png), data))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
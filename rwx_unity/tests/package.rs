@@ -0,0 +1,198 @@
+mod common;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use common::out_dir;
+use flate2::read::GzDecoder;
+use rwx_parser::Material;
+use rwx_unity::{asset_guid, package_unity, ImportSettings, LodGroup, NormalsMode, UnityError, WrapMode};
+
+/// Files of a package by tar path.
+fn entries(path: &PathBuf) -> HashMap<String, Vec<u8>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(path).unwrap()));
+    let mut out = HashMap::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        out.insert(name, data);
+    }
+    out
+}
+
+fn material(texture: &str) -> Material {
+    Material { name: texture.to_string(), texture: Some(texture.to_string()), ..Default::default() }
+}
+
+/// Writes the model files and a texture folder under `dir`, returning the
+/// folder and the paths of the OBJ and MTL.
+fn inputs(dir: &str) -> (PathBuf, PathBuf, PathBuf) {
+    let dir = out_dir(dir);
+    let textures = dir.join("textures");
+    fs::create_dir_all(&textures).unwrap();
+    fs::write(dir.join("chair.obj"), "o chair\n").unwrap();
+    fs::write(dir.join("chair.mtl"), "newmtl wood\n").unwrap();
+    fs::write(textures.join("wood.png"), b"png").unwrap();
+    (dir.clone(), dir.join("chair.obj"), dir.join("chair.mtl"))
+}
+
+fn package(dir: &str, settings: &ImportSettings) -> PathBuf {
+    let (dir, obj, mtl) = inputs(dir);
+    let out = dir.join("out");
+    package_unity("chair", &obj, &mtl, &[material("wood")], &[dir.join("textures")], &out, settings).unwrap();
+    out.join("chair.unitypackage")
+}
+
+#[test]
+fn package_has_guid_folders() {
+    let path = package("layout", &ImportSettings::default());
+    let entries = entries(&path);
+
+    let obj = asset_guid("chair", "Assets/RWX/chair/chair.obj");
+    assert_eq!(obj.len(), 32);
+    assert_eq!(entries[&format!("{obj}/asset")], b"o chair\n");
+    assert_eq!(entries[&format!("{obj}/pathname")], b"Assets/RWX/chair/chair.obj");
+    let meta = String::from_utf8(entries[&format!("{obj}/asset.meta")].clone()).unwrap();
+    assert!(meta.starts_with(&format!("fileFormatVersion: 2\nguid: {obj}\nModelImporter:")));
+
+    // two folders with only a meta and pathname, three files with all three
+    let guids: Vec<&str> = entries.keys().filter_map(|k| k.strip_suffix("/pathname")).collect();
+    assert_eq!(guids.len(), 5);
+    assert_eq!(entries.len(), 2 * 2 + 3 * 3);
+
+    let folder = asset_guid("chair", "Assets/RWX/chair");
+    let meta = String::from_utf8(entries[&format!("{folder}/asset.meta")].clone()).unwrap();
+    assert!(meta.contains("folderAsset: yes"));
+}
+
+#[test]
+fn package_is_deterministic() {
+    let a = fs::read(package("same_a", &ImportSettings::default())).unwrap();
+    let b = fs::read(package("same_b", &ImportSettings::default())).unwrap();
+    assert_eq!(a, b);
+    assert_ne!(asset_guid("chair", "Assets/RWX/chair/chair.obj"), asset_guid("table", "Assets/RWX/chair/chair.obj"));
+}
+
+#[test]
+fn meta_files_carry_import_settings() {
+    let settings = ImportSettings {
+        scale_factor: 0.1,
+        normals: NormalsMode::Calculate,
+        smoothing_angle: 45.0,
+        texture_wrap: WrapMode::Clamp,
+    };
+    let entries = entries(&package("settings", &settings));
+
+    let model = asset_guid("chair", "Assets/RWX/chair/chair.obj");
+    let meta = String::from_utf8(entries[&format!("{model}/asset.meta")].clone()).unwrap();
+    assert!(meta.contains("    globalScale: 0.1\n"));
+    assert!(meta.contains("    normalImportMode: 1\n"));
+    assert!(meta.contains("    normalSmoothAngle: 45\n"));
+
+    let texture = asset_guid("chair", "Assets/RWX/chair/wood.png");
+    let meta = String::from_utf8(entries[&format!("{texture}/asset.meta")].clone()).unwrap();
+    assert!(meta.contains("TextureImporter:"));
+    assert!(meta.contains("    wrapU: 1\n    wrapV: 1\n"));
+}
+
+#[test]
+fn textures_come_from_materials() {
+    let (dir, obj, mtl) = inputs("lookup");
+    let first = dir.join("first");
+    let second = dir.join("second");
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    fs::write(dir.join("textures/unused.jpg"), b"jpg").unwrap();
+    fs::write(second.join("Leaves.JPG"), b"leaves").unwrap();
+    fs::write(first.join("wood.bmp"), b"bmp").unwrap();
+
+    let mut leaves = material("leaves");
+    leaves.mask = Some("leavesm".to_string());
+    let out = dir.join("out");
+    let dirs = [first, second, dir.join("textures")];
+    let pack = package_unity("chair", &obj, &mtl, &[material("wood"), leaves], &dirs, &out, &ImportSettings::default()).unwrap();
+
+    // the first directory with a match wins, whatever the case
+    assert_eq!(pack.textures, ["Assets/RWX/chair/wood.bmp", "Assets/RWX/chair/Leaves.JPG"]);
+    assert_eq!(pack.missing_textures, ["leavesm"]);
+
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(out.join("chair.unitypack.json")).unwrap()).unwrap();
+    assert_eq!(manifest["missing_textures"], serde_json::json!(["leavesm"]));
+
+    let entries = entries(&out.join("chair.unitypackage"));
+    assert!(!entries.values().any(|v| v == b"Assets/RWX/chair/unused.jpg"));
+    assert_eq!(entries[&format!("{}/asset", asset_guid("chair", "Assets/RWX/chair/Leaves.JPG"))], b"leaves");
+}
+
+#[test]
+fn masks_are_packaged_merged() {
+    let (dir, obj, mtl) = inputs("mask");
+    let textures = dir.join("textures");
+    image::RgbImage::from_pixel(1, 1, image::Rgb([10, 20, 30])).save(textures.join("leaves.bmp")).unwrap();
+    image::GrayImage::from_pixel(1, 1, image::Luma([0])).save(textures.join("leavesm.bmp")).unwrap();
+
+    let mut leaves = material("leaves");
+    leaves.mask = Some("leavesm".to_string());
+    let (dirs, out) = ([textures], dir.join("out"));
+    let pack = package_unity("chair", &obj, &mtl, &[leaves.clone()], &dirs, &out, &ImportSettings::default()).unwrap();
+    // the MTL only refers to the merged image
+    assert_eq!(pack.textures, ["Assets/RWX/chair/leaves_leavesm.png"]);
+    assert!(pack.missing_textures.is_empty());
+
+    let entries = entries(&out.join("chair.unitypackage"));
+    let merged = &entries[&format!("{}/asset", asset_guid("chair", "Assets/RWX/chair/leaves_leavesm.png"))];
+    assert_eq!(image::load_from_memory(merged).unwrap().to_rgba8().get_pixel(0, 0).0, [10, 20, 30, 0]);
+    assert!(!entries.contains_key(&format!("{}/asset", asset_guid("chair", "Assets/RWX/chair/leaves.bmp"))));
+
+    // a material drawing the texture unmasked still needs the original
+    let pack = package_unity("chair", &obj, &mtl, &[leaves, material("leaves")], &dirs, &out, &ImportSettings::default()).unwrap();
+    assert_eq!(pack.textures, ["Assets/RWX/chair/leaves_leavesm.png", "Assets/RWX/chair/leaves.bmp"]);
+}
+
+#[test]
+fn zipped_textures_are_extracted() {
+    let (dir, obj, mtl) = inputs("zip");
+    let zipped = dir.join("zipped");
+    fs::create_dir_all(&zipped).unwrap();
+    let mut zip = zip::ZipWriter::new(fs::File::create(zipped.join("bark.zip")).unwrap());
+    zip.start_file("readme.txt", zip::write::SimpleFileOptions::default()).unwrap();
+    zip.write_all(b"not an image").unwrap();
+    zip.start_file("bark.jpg", zip::write::SimpleFileOptions::default()).unwrap();
+    zip.write_all(b"bark").unwrap();
+    zip.finish().unwrap();
+
+    let out = dir.join("out");
+    let pack = package_unity("chair", &obj, &mtl, &[material("bark")], &[zipped], &out, &ImportSettings::default()).unwrap();
+    assert_eq!(pack.textures, ["Assets/RWX/chair/bark.jpg"]);
+
+    let entries = entries(&out.join("chair.unitypackage"));
+    assert_eq!(entries[&format!("{}/asset", asset_guid("chair", "Assets/RWX/chair/bark.jpg"))], b"bark");
+}
+
+#[test]
+fn missing_model_is_an_error() {
+    let (dir, _, mtl) = inputs("missing");
+    let obj = dir.join("nothing.obj");
+    let err = package_unity("chair", &obj, &mtl, &[], &[], &dir.join("out"), &ImportSettings::default()).unwrap_err();
+    assert!(matches!(&err, UnityError::Read { path, .. } if *path == obj));
+    assert!(err.to_string().contains("nothing.obj"));
+}
+
+#[test]
+fn lod_group_write_errors_name_the_file() {
+    let dir = out_dir("lodgroup");
+    let group = LodGroup::new("chair", &[("chair_LOD0.obj".to_string(), 1.0, 12), ("chair_LOD1.obj".to_string(), 0.5, 6)]);
+
+    let path = dir.join("chair.lodgroup.json");
+    group.write(&path).unwrap();
+    let back: LodGroup = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(back.levels.len(), 2);
+
+    let missing = dir.join("no such dir").join("chair.lodgroup.json");
+    let err = group.write(&missing).unwrap_err();
+    assert!(matches!(&err, UnityError::Write { path, .. } if *path == missing));
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::out_dir;
+use rwx_lexer::lex;
+use rwx_parser::parse;
+use rwx_scene::RwxScene;
+use rwx_unity::{asset_guid, prefab_assets, write_prefab, PackageAsset, PrefabOptions};
+
+const LAMP: &str = "
+ModelBegin
+ClumpBegin
+Tag 100
+AxisAlignment ZOrientY
+Texture shade Mask shadem
+VertexExt 0 0 0 UV 0 0
+VertexExt 1 0 0 UV 1 0
+VertexExt 1 1 0 UV 1 1
+VertexExt 0 1 0 UV 0 1
+Quad 1 2 3 4
+ClumpBegin
+Collision off
+Texture null
+Color 1 0 0
+Opacity 0.5
+VertexExt 0 2 0
+VertexExt 1 2 0
+VertexExt 1 3 0
+Polygon 3 1 2 3
+ClumpEnd
+ClumpEnd
+ModelEnd
+";
+
+fn assets(dir: &str) -> (Vec<PackageAsset>, PathBuf) {
+    let dir = out_dir(dir);
+    fs::write(dir.join("shade.png"), b"png").unwrap();
+
+    let scene = RwxScene::from_model(&parse(&lex(LAMP)).unwrap());
+    let opts = PrefabOptions { texture_dirs: vec![dir.clone()], ..Default::default() };
+    (prefab_assets(&scene, "lamp", &opts).unwrap(), dir)
+}
+
+fn text<'a>(assets: &'a [PackageAsset], pathname: &str) -> &'a str {
+    let asset = assets.iter().find(|a| a.pathname == pathname).unwrap_or_else(|| panic!("no {pathname}"));
+    std::str::from_utf8(asset.data.as_ref().unwrap()).unwrap()
+}
+
+#[test]
+fn prefab_follows_clumps() {
+    let (assets, _) = assets("prefab_tree");
+    let prefab = text(&assets, "Assets/RWX/lamp/lamp.prefab");
+
+    // model root, clump_1 and its child clump_2
+    assert_eq!(prefab.matches("--- !u!1 &").count(), 3);
+    assert!(prefab.contains("  m_Name: lamp\n"));
+    assert!(prefab.contains("--- !u!4 &400001\nTransform:"));
+    assert!(prefab.contains("  m_Children:\n  - {fileID: 400002}\n  m_Father: {fileID: 400000}\n"));
+    assert_eq!(prefab.matches("MeshFilter:").count(), 2);
+    assert_eq!(prefab.matches("MeshRenderer:").count(), 2);
+
+    // the child clump has collision off
+    assert_eq!(prefab.matches("MeshCollider:").count(), 1);
+    assert!(prefab.contains("--- !u!64 &6400001\n"));
+
+    let mesh = asset_guid("lamp", "Assets/RWX/lamp/clump_1.asset");
+    assert!(prefab.contains(&format!("  m_Mesh: {{fileID: 4300000, guid: {mesh}, type: 2}}")));
+}
+
+#[test]
+fn info_component_holds_tag_and_alignment() {
+    let (assets, _) = assets("prefab_info");
+    let prefab = text(&assets, "Assets/RWX/lamp/lamp.prefab");
+
+    assert_eq!(prefab.matches("MonoBehaviour:").count(), 1);
+    let script = asset_guid("", "Assets/RWX/Scripts/RwxObjectInfo.cs");
+    assert!(prefab.contains(&format!("  m_Script: {{fileID: 11500000, guid: {script}, type: 3}}")));
+    assert!(prefab.contains("  rwxTag: 100\n  axisAlignment: 2\n"));
+    assert!(text(&assets, "Assets/RWX/Scripts/RwxObjectInfo.cs").contains("class RwxObjectInfo : MonoBehaviour"));
+}
+
+#[test]
+fn materials_follow_rwx_surface() {
+    let (assets, _) = assets("prefab_materials");
+
+    let masked = text(&assets, "Assets/RWX/lamp/shade.mat");
+    let texture = asset_guid("lamp", "Assets/RWX/lamp/shade.png");
+    assert!(masked.contains("  m_ShaderKeywords: _ALPHATEST_ON\n"));
+    assert!(masked.contains("    RenderType: TransparentCutout\n"));
+    assert!(masked.contains("    - _Mode: 1\n    - _Cutoff: 0.5\n"));
+    assert!(masked.contains(&format!("m_Texture: {{fileID: 2800000, guid: {texture}, type: 3}}")));
+
+    let faded = text(&assets, "Assets/RWX/lamp/NULL.mat");
+    assert!(faded.contains("    - _Mode: 2\n"));
+    assert!(faded.contains("    - _Color: {r: 1, g: 0, b: 0, a: 0.5}\n"));
+    assert!(faded.contains("m_Texture: {fileID: 0}"));
+}
+
+#[test]
+fn masks_are_merged_into_texture_alpha() {
+    let dir = out_dir("prefab_mask");
+    image::RgbImage::from_pixel(2, 2, image::Rgb([0, 128, 255])).save(dir.join("shade.png")).unwrap();
+    image::GrayImage::from_fn(2, 2, |x, _| image::Luma([if x == 0 { 255 } else { 0 }])).save(dir.join("shadem.bmp")).unwrap();
+
+    let scene = RwxScene::from_model(&parse(&lex(LAMP)).unwrap());
+    let opts = PrefabOptions { texture_dirs: vec![dir], ..Default::default() };
+    let assets = prefab_assets(&scene, "lamp", &opts).unwrap();
+
+    let pathname = "Assets/RWX/lamp/shade_shadem.png";
+    let texture = asset_guid("lamp", pathname);
+    assert!(text(&assets, "Assets/RWX/lamp/shade.mat").contains(&format!("guid: {texture}, type: 3")));
+    assert!(!assets.iter().any(|a| a.pathname.ends_with("shadem.bmp")));
+
+    let data = assets.iter().find(|a| a.pathname == pathname).unwrap().data.as_ref().unwrap();
+    let merged = image::load_from_memory(data).unwrap().to_rgba8();
+    assert_eq!(merged.get_pixel(0, 0).0, [0, 128, 255, 255]);
+    assert_eq!(merged.get_pixel(1, 0).0, [0, 128, 255, 0]);
+}
+
+#[test]
+fn meshes_are_in_unity_space() {
+    let (assets, _) = assets("prefab_mesh");
+    let mesh = text(&assets, "Assets/RWX/lamp/clump_1.asset");
+
+    assert!(mesh.contains("    indexCount: 6\n"));
+    assert!(mesh.contains("    m_VertexCount: 4\n"));
+    // X is mirrored and RWX units become metres
+    assert!(mesh.contains("  m_LocalAABB:\n    m_Center: {x: -5, y: 5, z: 0}\n    m_Extent: {x: 5, y: 5, z: 0}\n"));
+
+    let size: usize = mesh.split("m_DataSize: ").nth(1).unwrap().lines().next().unwrap().parse().unwrap();
+    let data = mesh.split("_typelessdata: ").nth(1).unwrap().lines().next().unwrap();
+    assert_eq!(data.len(), size * 2);
+}
+
+#[test]
+fn write_prefab_places_assets_with_meta() {
+    let (_, dir) = assets("prefab_write");
+    let project = dir.join("project");
+    let scene = RwxScene::from_model(&parse(&lex(LAMP)).unwrap());
+    let opts = PrefabOptions { texture_dirs: vec![dir], ..Default::default() };
+    write_prefab(&scene, "lamp", &project, &opts).unwrap();
+
+    for file in ["lamp.prefab", "clump_1.asset", "clump_2.asset", "shade.mat", "shade.png"] {
+        let path = project.join("Assets/RWX/lamp").join(file);
+        assert!(path.is_file(), "{file}");
+        let meta = fs::read_to_string(format!("{}.meta", path.display())).unwrap();
+        let guid = asset_guid("lamp", &format!("Assets/RWX/lamp/{file}"));
+        assert!(meta.starts_with(&format!("fileFormatVersion: 2\nguid: {guid}\n")));
+    }
+    assert!(project.join("Assets/RWX.meta").is_file());
+}